@@ -0,0 +1,675 @@
+use block_cipher::{BlockCipher, CipherError, AES_BLOCK_SIZE, AES_KEY_SIZES};
+
+// AES-256 has the most
+pub const AES_MAX_ROUNDS: usize = 14;
+
+// Column-major, like the spec: byte r + 4c is row r of column c
+pub type AesBlock = [u8; AES_BLOCK_SIZE];
+
+// Multiplication by x in GF(2^8), mod x^8 + x^4 + x^3 + x + 1
+const fn xtime(b: u8) -> u8 {
+    (b << 1) ^ ((b >> 7) * 0x1b)
+}
+
+const fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+// x^254 is the inverse of x, and 0 goes to 0 like the S-box wants
+const fn gf_inv(x: u8) -> u8 {
+    let mut inv = 1;
+    let mut i = 0;
+    while i < 254 {
+        inv = gf_mul(inv, x);
+        i += 1;
+    }
+    inv
+}
+
+// Inversion, then the affine map. Worked out instead of written out, the same way the bitsliced
+// backend does it
+const fn make_sbox() -> [u8; 256] {
+    let mut sbox = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        let x = gf_inv(i as u8);
+        sbox[i] = x ^ x.rotate_left(1) ^ x.rotate_left(2) ^ x.rotate_left(3) ^ x.rotate_left(4)
+                  ^ 0x63;
+        i += 1;
+    }
+    sbox
+}
+
+const fn make_inv_sbox() -> [u8; 256] {
+    let sbox = make_sbox();
+    let mut inv = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv[sbox[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv
+}
+
+pub static SBOX: [u8; 256] = make_sbox();
+pub static INV_SBOX: [u8; 256] = make_inv_sbox();
+
+const RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+// A round's SubBytes, ShiftRows and MixColumns for one byte of the state, as the column it adds
+// in. Entry i is rotated right by 8i bits, for the byte's row
+const fn make_enc_tables() -> [[u32; 256]; 4] {
+    let sbox = make_sbox();
+    let mut tables = [[0u32; 256]; 4];
+    let mut x = 0;
+    while x < 256 {
+        let s = sbox[x];
+        let column = (gf_mul(s, 2) as u32) << 24 | (s as u32) << 16 | (s as u32) << 8
+                     | gf_mul(s, 3) as u32;
+        tables[0][x] = column;
+        tables[1][x] = column.rotate_right(8);
+        tables[2][x] = column.rotate_right(16);
+        tables[3][x] = column.rotate_right(24);
+        x += 1;
+    }
+    tables
+}
+
+// Same for the inverse round: InvSubBytes then InvMixColumns
+const fn make_dec_tables() -> [[u32; 256]; 4] {
+    let inv_sbox = make_inv_sbox();
+    let mut tables = [[0u32; 256]; 4];
+    let mut x = 0;
+    while x < 256 {
+        let s = inv_sbox[x];
+        let column = (gf_mul(s, 14) as u32) << 24 | (gf_mul(s, 9) as u32) << 16
+                     | (gf_mul(s, 13) as u32) << 8 | gf_mul(s, 11) as u32;
+        tables[0][x] = column;
+        tables[1][x] = column.rotate_right(8);
+        tables[2][x] = column.rotate_right(16);
+        tables[3][x] = column.rotate_right(24);
+        x += 1;
+    }
+    tables
+}
+
+static ENC_TABLES: [[u32; 256]; 4] = make_enc_tables();
+static DEC_TABLES: [[u32; 256]; 4] = make_dec_tables();
+
+// The steps of a round, a byte at a time. Too slow for the backends, but they're what to reach for
+// to stop a block partway through, or to change it there
+pub fn sub_bytes(state: &mut AesBlock) {
+    for b in state.iter_mut() {
+        *b = SBOX[*b as usize];
+    }
+}
+
+pub fn inv_sub_bytes(state: &mut AesBlock) {
+    for b in state.iter_mut() {
+        *b = INV_SBOX[*b as usize];
+    }
+}
+
+// Row r moves r columns to the left
+pub fn shift_rows(state: &mut AesBlock) {
+    let old = *state;
+    for c in 0..4 {
+        for r in 0..4 {
+            state[r + 4*c] = old[r + 4*((c + r) % 4)];
+        }
+    }
+}
+
+pub fn inv_shift_rows(state: &mut AesBlock) {
+    let old = *state;
+    for c in 0..4 {
+        for r in 0..4 {
+            state[r + 4*((c + r) % 4)] = old[r + 4*c];
+        }
+    }
+}
+
+pub fn mix_columns(state: &mut AesBlock) {
+    for column in state.chunks_mut(4) {
+        let a = [column[0], column[1], column[2], column[3]];
+        for r in 0..4 {
+            column[r] = xtime(a[r] ^ a[(r+1) % 4]) ^ a[(r+1) % 4] ^ a[(r+2) % 4] ^ a[(r+3) % 4];
+        }
+    }
+}
+
+pub fn inv_mix_columns(state: &mut AesBlock) {
+    for column in state.chunks_mut(4) {
+        let a = [column[0], column[1], column[2], column[3]];
+        for r in 0..4 {
+            column[r] = gf_mul(a[r], 14) ^ gf_mul(a[(r+1) % 4], 11) ^ gf_mul(a[(r+2) % 4], 13)
+                        ^ gf_mul(a[(r+3) % 4], 9);
+        }
+    }
+}
+
+pub fn add_round_key(state: &mut AesBlock, round_key: &AesBlock) {
+    for (b, k) in state.iter_mut().zip(round_key.iter()) {
+        *b ^= *k;
+    }
+}
+
+fn full_rounds(key: &[u8]) -> Result<usize, CipherError> {
+    if AES_KEY_SIZES.contains(&key.len()) {
+        Ok(key.len() / 4 + 6)
+    } else {
+        Err(CipherError::KeyLength { found: key.len(), allowed: &AES_KEY_SIZES })
+    }
+}
+
+// The key schedule: one round key for the start and one for each round. sub_word is the S-box on
+// each byte of a word, so the bitsliced backend can get by without table lookups
+fn expand_with(key: &[u8], rounds: usize, sub_word: fn([u8; 4]) -> [u8; 4]) -> Vec<AesBlock> {
+    let nk = key.len() / 4;
+    let mut words = key.chunks(4).map(|w| [w[0], w[1], w[2], w[3]]).collect::<Vec<[u8; 4]>>();
+    for i in nk..4*(rounds + 1) {
+        let prev = words[i-1];
+        let mut w = if i % nk == 0 {
+            let mut w = sub_word([prev[1], prev[2], prev[3], prev[0]]);
+            w[0] ^= RCON[i/nk - 1];
+            w
+        } else if nk > 6 && i % nk == 4 {
+            sub_word(prev)
+        } else {
+            prev
+        };
+        for (b, k) in w.iter_mut().zip(words[i-nk].iter()) {
+            *b ^= *k;
+        }
+        words.push(w);
+    }
+
+    words.chunks(4).take(rounds + 1).map(|ws| {
+        let mut round_key = [0u8; AES_BLOCK_SIZE];
+        for (chunk, w) in round_key.chunks_mut(4).zip(ws.iter()) {
+            chunk.copy_from_slice(w);
+        }
+        round_key
+    }).collect()
+}
+
+fn table_sub_word(w: [u8; 4]) -> [u8; 4] {
+    [SBOX[w[0] as usize], SBOX[w[1] as usize], SBOX[w[2] as usize], SBOX[w[3] as usize]]
+}
+
+// Every round key for a full-strength AES with this key
+pub fn expand_key(key: &[u8]) -> Result<Vec<AesBlock>, CipherError> {
+    let rounds = full_rounds(key)?;
+    Ok(expand_with(key, rounds, table_sub_word))
+}
+
+// T-table backend. Each column of the state is a big-endian u32, and a round is 16 lookups. Fast,
+// but which table entries get used depends on the key and data, so it leaks through the cache
+
+type TableKeys = [[u32; 4]; AES_MAX_ROUNDS + 1];
+
+fn to_columns(block: &AesBlock) -> [u32; 4] {
+    let mut columns = [0u32; 4];
+    for (column, bytes) in columns.iter_mut().zip(block.chunks(4)) {
+        *column = (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8
+                  | bytes[3] as u32;
+    }
+    columns
+}
+
+fn from_columns(columns: &[u32; 4], block: &mut AesBlock) {
+    for (bytes, column) in block.chunks_mut(4).zip(columns.iter()) {
+        bytes[0] = (column >> 24) as u8;
+        bytes[1] = (column >> 16) as u8;
+        bytes[2] = (column >> 8) as u8;
+        bytes[3] = *column as u8;
+    }
+}
+
+// Decryption runs the equivalent inverse cipher, so that it can have tables too. That needs the
+// middle round keys put through InvMixColumns
+fn table_keys(round_keys: &[AesBlock]) -> (TableKeys, TableKeys) {
+    let rounds = round_keys.len() - 1;
+    let mut enc = [[0u32; 4]; AES_MAX_ROUNDS + 1];
+    let mut dec = [[0u32; 4]; AES_MAX_ROUNDS + 1];
+    for (i, round_key) in round_keys.iter().enumerate() {
+        enc[i] = to_columns(round_key);
+        let mut mixed = *round_key;
+        if i != 0 && i != rounds {
+            inv_mix_columns(&mut mixed);
+        }
+        dec[rounds - i] = to_columns(&mixed);
+    }
+    (enc, dec)
+}
+
+// Row r of output column c comes from column c + r going in, or c - r going out
+fn table_round(tables: &[[u32; 256]; 4], s: &[u32; 4], key: &[u32; 4],
+               step: usize) -> [u32; 4] {
+    let mut out = [0u32; 4];
+    for c in 0..4 {
+        out[c] = tables[0][(s[c] >> 24) as usize]
+                 ^ tables[1][(s[(c + step) % 4] >> 16) as usize & 0xff]
+                 ^ tables[2][(s[(c + 2*step) % 4] >> 8) as usize & 0xff]
+                 ^ tables[3][s[(c + 3*step) % 4] as usize & 0xff]
+                 ^ key[c];
+    }
+    out
+}
+
+fn table_last_round(sbox: &[u8; 256], s: &[u32; 4], key: &[u32; 4], step: usize) -> [u32; 4] {
+    let mut out = [0u32; 4];
+    for c in 0..4 {
+        out[c] = ((sbox[(s[c] >> 24) as usize] as u32) << 24
+                  | (sbox[(s[(c + step) % 4] >> 16) as usize & 0xff] as u32) << 16
+                  | (sbox[(s[(c + 2*step) % 4] >> 8) as usize & 0xff] as u32) << 8
+                  | sbox[s[(c + 3*step) % 4] as usize & 0xff] as u32)
+                 ^ key[c];
+    }
+    out
+}
+
+fn table_crypt(tables: &[[u32; 256]; 4], sbox: &[u8; 256], keys: &TableKeys, rounds: usize,
+               step: usize, block: &mut AesBlock) {
+    let mut s = to_columns(block);
+    for c in 0..4 {
+        s[c] ^= keys[0][c];
+    }
+    for round in 1..rounds {
+        s = table_round(tables, &s, &keys[round], step);
+    }
+    s = table_last_round(sbox, &s, &keys[rounds], step);
+    from_columns(&s, block);
+}
+
+// Bitsliced backend. Plane i holds bit i of all 16 bytes, byte j in bit j, so every step is the
+// same ANDs, XORs and shifts whatever the key and data are. The S-box is inversion in GF(2^8),
+// done with multiplications, then the affine map
+
+type Planes = [u16; 8];
+
+fn slice(bytes: &[u8]) -> Planes {
+    let mut planes = [0u16; 8];
+    for (j, &b) in bytes.iter().enumerate() {
+        for (i, plane) in planes.iter_mut().enumerate() {
+            *plane |= ((b as u16 >> i) & 1) << j;
+        }
+    }
+    planes
+}
+
+fn unslice(planes: &Planes, bytes: &mut [u8]) {
+    for (j, b) in bytes.iter_mut().enumerate() {
+        *b = 0;
+        for (i, plane) in planes.iter().enumerate() {
+            *b |= (((plane >> j) & 1) as u8) << i;
+        }
+    }
+}
+
+fn sliced_xor(a: &Planes, b: &Planes) -> Planes {
+    let mut out = *a;
+    for (o, x) in out.iter_mut().zip(b.iter()) {
+        *o ^= *x;
+    }
+    out
+}
+
+// Schoolbook multiplication, then reduction from the top down
+fn sliced_mul(a: &Planes, b: &Planes) -> Planes {
+    let mut product = [0u16; 15];
+    for i in 0..8 {
+        for j in 0..8 {
+            product[i+j] ^= a[i] & b[j];
+        }
+    }
+    for k in (8..15).rev() {
+        product[k-4] ^= product[k];
+        product[k-5] ^= product[k];
+        product[k-7] ^= product[k];
+        product[k-8] ^= product[k];
+    }
+    let mut out = [0u16; 8];
+    out.copy_from_slice(&product[..8]);
+    out
+}
+
+// x^254, by way of x^3, x^15 and x^252
+fn sliced_inv(x: &Planes) -> Planes {
+    let x2 = sliced_mul(x, x);
+    let x3 = sliced_mul(&x2, x);
+    let x6 = sliced_mul(&x3, &x3);
+    let x12 = sliced_mul(&x6, &x6);
+    let x15 = sliced_mul(&x12, &x3);
+    let mut x240 = x15;
+    for _ in 0..4 {
+        x240 = sliced_mul(&x240, &x240);
+    }
+    let x252 = sliced_mul(&x240, &x12);
+    sliced_mul(&x252, &x2)
+}
+
+fn sliced_sub(x: &Planes) -> Planes {
+    let inv = sliced_inv(x);
+    let mut out = [0u16; 8];
+    for i in 0..8 {
+        out[i] = inv[i] ^ inv[(i+4) % 8] ^ inv[(i+5) % 8] ^ inv[(i+6) % 8] ^ inv[(i+7) % 8];
+        // Adding 0x63; this depends on which plane it is, not on anything secret
+        if (0x63 >> i) & 1 == 1 {
+            out[i] = !out[i];
+        }
+    }
+    out
+}
+
+fn sliced_inv_sub(x: &Planes) -> Planes {
+    let mut affine = [0u16; 8];
+    for i in 0..8 {
+        affine[i] = x[(i+2) % 8] ^ x[(i+5) % 8] ^ x[(i+7) % 8];
+        if (0x05 >> i) & 1 == 1 {
+            affine[i] = !affine[i];
+        }
+    }
+    sliced_inv(&affine)
+}
+
+// Row r's bits are r, r + 4, r + 8 and r + 12, so shifting the row is rotating those bits
+fn sliced_shift_rows(x: &Planes, inverse: bool) -> Planes {
+    let mut out = [0u16; 8];
+    for (o, plane) in out.iter_mut().zip(x.iter()) {
+        for r in 0..4u32 {
+            let row = plane & (0x1111 << r);
+            *o |= if inverse { row.rotate_left(4*r) } else { row.rotate_right(4*r) };
+        }
+    }
+    out
+}
+
+// Byte r + 4c becomes byte r + k + 4c, within each column
+fn sliced_rotate_columns(x: &Planes, k: usize) -> Planes {
+    let low = match k { 1 => 0x7777, 2 => 0x3333, _ => 0x1111 };
+    let mut out = [0u16; 8];
+    for (o, plane) in out.iter_mut().zip(x.iter()) {
+        *o = ((plane >> k) & low) | ((plane << (4 - k)) & !low);
+    }
+    out
+}
+
+fn sliced_xtime(x: &Planes) -> Planes {
+    [x[7], x[0] ^ x[7], x[1], x[2] ^ x[7], x[3] ^ x[7], x[4], x[5], x[6]]
+}
+
+fn sliced_mix_columns(x: &Planes) -> Planes {
+    let r1 = sliced_rotate_columns(x, 1);
+    let r2 = sliced_rotate_columns(x, 2);
+    let r3 = sliced_rotate_columns(x, 3);
+    let doubled = sliced_xtime(&sliced_xor(x, &r1));
+    sliced_xor(&sliced_xor(&doubled, &r1), &sliced_xor(&r2, &r3))
+}
+
+// InvMixColumns is MixColumns after adding 4 times the byte two rows down to each byte
+fn sliced_inv_mix_columns(x: &Planes) -> Planes {
+    let quadrupled = sliced_xtime(&sliced_xtime(&sliced_xor(x, &sliced_rotate_columns(x, 2))));
+    sliced_mix_columns(&sliced_xor(x, &quadrupled))
+}
+
+fn sliced_sub_word(w: [u8; 4]) -> [u8; 4] {
+    let mut out = [0u8; 4];
+    unslice(&sliced_sub(&slice(&w)), &mut out);
+    out
+}
+
+fn sliced_encrypt(keys: &[Planes], rounds: usize, block: &mut AesBlock) {
+    let mut s = sliced_xor(&slice(block), &keys[0]);
+    for round in 1..rounds {
+        s = sliced_mix_columns(&sliced_shift_rows(&sliced_sub(&s), false));
+        s = sliced_xor(&s, &keys[round]);
+    }
+    s = sliced_xor(&sliced_shift_rows(&sliced_sub(&s), false), &keys[rounds]);
+    unslice(&s, block);
+}
+
+fn sliced_decrypt(keys: &[Planes], rounds: usize, block: &mut AesBlock) {
+    let mut s = sliced_xor(&slice(block), &keys[rounds]);
+    s = sliced_inv_sub(&sliced_shift_rows(&s, true));
+    for round in (1..rounds).rev() {
+        s = sliced_inv_mix_columns(&sliced_xor(&s, &keys[round]));
+        s = sliced_inv_sub(&sliced_shift_rows(&s, true));
+    }
+    s = sliced_xor(&s, &keys[0]);
+    unslice(&s, block);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AesBackend {
+    // T-tables: fast, but not constant time
+    Table,
+    // Constant time, and a lot slower
+    Bitsliced,
+}
+
+#[derive(Clone, Copy)]
+enum Schedule {
+    Table { enc: TableKeys, dec: TableKeys },
+    Bitsliced([Planes; AES_MAX_ROUNDS + 1]),
+}
+
+// AES with any of the three key sizes, picked by the length of the key. The key schedule is
+// expanded once, up front, in whatever form the backend wants
+#[derive(Clone, Copy)]
+pub struct Aes {
+    round_keys: [AesBlock; AES_MAX_ROUNDS + 1],
+    rounds: usize,
+    key_size: usize,
+    schedule: Schedule,
+}
+
+impl Aes {
+    pub fn new(key: &[u8]) -> Result<Aes, CipherError> {
+        Aes::with_backend(key, AesBackend::Table)
+    }
+
+    pub fn with_backend(key: &[u8], backend: AesBackend) -> Result<Aes, CipherError> {
+        let rounds = full_rounds(key)?;
+        Aes::reduced(key, backend, rounds)
+    }
+
+    // AES cut down to its first few rounds. The last one skips MixColumns, same as in the full
+    // cipher, and the round keys are the full key schedule's first few
+    pub fn reduced(key: &[u8], backend: AesBackend, rounds: usize) -> Result<Aes, CipherError> {
+        let max = full_rounds(key)?;
+        if rounds == 0 || rounds > max {
+            return Err(CipherError::Rounds { found: rounds, max: max });
+        }
+
+        let expanded = match backend {
+            AesBackend::Table => expand_with(key, rounds, table_sub_word),
+            AesBackend::Bitsliced => expand_with(key, rounds, sliced_sub_word),
+        };
+        let mut round_keys = [[0u8; AES_BLOCK_SIZE]; AES_MAX_ROUNDS + 1];
+        round_keys[..rounds+1].copy_from_slice(&expanded);
+
+        let schedule = match backend {
+            AesBackend::Table => {
+                let (enc, dec) = table_keys(&expanded);
+                Schedule::Table { enc: enc, dec: dec }
+            },
+            AesBackend::Bitsliced => {
+                let mut sliced = [[0u16; 8]; AES_MAX_ROUNDS + 1];
+                for (planes, round_key) in sliced.iter_mut().zip(expanded.iter()) {
+                    *planes = slice(round_key);
+                }
+                Schedule::Bitsliced(sliced)
+            },
+        };
+
+        Ok(Aes {
+            round_keys: round_keys,
+            rounds: rounds,
+            key_size: key.len(),
+            schedule: schedule,
+        })
+    }
+
+    // In bytes
+    pub fn key_size(&self) -> usize {
+        self.key_size
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    pub fn backend(&self) -> AesBackend {
+        match self.schedule {
+            Schedule::Table { .. } => AesBackend::Table,
+            Schedule::Bitsliced(_) => AesBackend::Bitsliced,
+        }
+    }
+
+    // The key for the start, then one for each round
+    pub fn round_keys(&self) -> &[AesBlock] {
+        &self.round_keys[..self.rounds+1]
+    }
+
+    // The state after the first AddRoundKey, then after each round. The last one is the
+    // ciphertext. Goes a byte at a time, whatever the backend
+    pub fn encrypt_trace(&self, block: &AesBlock) -> Vec<AesBlock> {
+        let mut state = *block;
+        add_round_key(&mut state, &self.round_keys[0]);
+        let mut trace = vec![state];
+        for round in 1..self.rounds+1 {
+            sub_bytes(&mut state);
+            shift_rows(&mut state);
+            if round != self.rounds {
+                mix_columns(&mut state);
+            }
+            add_round_key(&mut state, &self.round_keys[round]);
+            trace.push(state);
+        }
+        trace
+    }
+}
+
+impl BlockCipher for Aes {
+    type Block = AesBlock;
+
+    fn encrypt_block(&self, block: &mut AesBlock) {
+        match self.schedule {
+            Schedule::Table { ref enc, .. } =>
+                table_crypt(&ENC_TABLES, &SBOX, enc, self.rounds, 1, block),
+            Schedule::Bitsliced(ref keys) => sliced_encrypt(keys, self.rounds, block),
+        }
+    }
+
+    fn decrypt_block(&self, block: &mut AesBlock) {
+        match self.schedule {
+            Schedule::Table { ref dec, .. } =>
+                table_crypt(&DEC_TABLES, &INV_SBOX, dec, self.rounds, 3, block),
+            Schedule::Bitsliced(ref keys) => sliced_decrypt(keys, self.rounds, block),
+        }
+    }
+}
+
+#[test]
+fn tst_aes() {
+    use c01::decode_hex;
+    use rand::{self, Rng};
+
+    assert_eq!((SBOX[0x00], SBOX[0x53], SBOX[0xff]), (0x63, 0xed, 0x16));
+    assert_eq!((INV_SBOX[0x63], INV_SBOX[0xed], INV_SBOX[0x16]), (0x00, 0x53, 0xff));
+
+    // FIPS-197, appendix A: the last round key of each expansion
+    let expansions = [
+        ("2b7e151628aed2a6abf7158809cf4f3c", 11, "d014f9a8c9ee2589e13f0cc8b6630ca6"),
+        ("8e73b0f7da0e6452c810f32b809079e562f8ead2522c6b7b", 13,
+         "e98ba06f448c773c8ecc720401002202"),
+        ("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4", 15,
+         "fe4890d1e6188d0b046df344706c631e"),
+    ];
+    for &(key, n_keys, last) in expansions.iter() {
+        let round_keys = expand_key(&decode_hex(key)).unwrap();
+        assert_eq!(round_keys.len(), n_keys);
+        assert_eq!(&round_keys[0][..], &decode_hex(key)[..16]);
+        assert_eq!(&round_keys[n_keys-1][..], &decode_hex(last)[..]);
+    }
+
+    // FIPS-197, appendix C, on both backends
+    let plaintext = Aes::block_from(&decode_hex("00112233445566778899aabbccddeeff"));
+    let vectors = [
+        ("000102030405060708090a0b0c0d0e0f", "69c4e0d86a7b0430d8cdb78070b4c55a"),
+        ("000102030405060708090a0b0c0d0e0f1011121314151617", "dda97ca4864cdfe06eaf70a0ec0d7191"),
+        ("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+         "8ea2b7ca516745bfeafc49904b496089"),
+    ];
+    for &backend in [AesBackend::Table, AesBackend::Bitsliced].iter() {
+        for &(key, ciphertext) in vectors.iter() {
+            let aes = Aes::with_backend(&decode_hex(key), backend).unwrap();
+            assert_eq!(aes.backend(), backend);
+            assert_eq!(aes.round_keys(), &expand_key(&decode_hex(key)).unwrap()[..]);
+
+            let mut block = plaintext;
+            aes.encrypt_block(&mut block);
+            assert_eq!(&block[..], &decode_hex(ciphertext)[..]);
+            aes.decrypt_block(&mut block);
+            assert_eq!(block, plaintext);
+        }
+    }
+
+    // The round states in C.1. Round i's start is the state after round i - 1
+    let aes = Aes::new(&decode_hex(vectors[0].0)).unwrap();
+    let trace = aes.encrypt_trace(&plaintext);
+    assert_eq!(trace.len(), 11);
+    assert_eq!(&trace[0][..], &decode_hex("00102030405060708090a0b0c0d0e0f0")[..]);
+    assert_eq!(&trace[1][..], &decode_hex("89d810e8855ace682d1843d8cb128fe4")[..]);
+    assert_eq!(&trace[2][..], &decode_hex("4915598f55e5d7a0daca94fa1f0a63f7")[..]);
+    assert_eq!(&trace[10][..], &decode_hex(vectors[0].1)[..]);
+
+    // The byte-at-a-time steps undo each other
+    let mut state = trace[4];
+    mix_columns(&mut state);
+    shift_rows(&mut state);
+    sub_bytes(&mut state);
+    inv_sub_bytes(&mut state);
+    inv_shift_rows(&mut state);
+    inv_mix_columns(&mut state);
+    assert_eq!(state, trace[4]);
+
+    // Reduced rounds, and random keys and blocks: the backends agree with each other and with
+    // the trace, and decryption undoes them
+    let mut rng = rand::thread_rng();
+    for &key_size in AES_KEY_SIZES.iter() {
+        let key = rng.gen_iter::<u8>().take(key_size).collect::<Vec<u8>>();
+        let max = key_size / 4 + 6;
+        for rounds in 1..max+1 {
+            let table = Aes::reduced(&key, AesBackend::Table, rounds).unwrap();
+            let sliced = Aes::reduced(&key, AesBackend::Bitsliced, rounds).unwrap();
+            assert_eq!(table.rounds(), rounds);
+            assert_eq!(table.round_keys(), sliced.round_keys());
+
+            let mut block = [0u8; AES_BLOCK_SIZE];
+            rng.fill_bytes(&mut block);
+            let mut a = block;
+            let mut b = block;
+            table.encrypt_block(&mut a);
+            sliced.encrypt_block(&mut b);
+            assert_eq!(a, b);
+            assert_eq!(Some(&a), table.encrypt_trace(&block).last());
+            table.decrypt_block(&mut a);
+            sliced.decrypt_block(&mut b);
+            assert_eq!((a, b), (block, block));
+        }
+        assert_eq!(Aes::reduced(&key, AesBackend::Table, 0).err(),
+                   Some(CipherError::Rounds { found: 0, max: max }));
+        assert_eq!(Aes::reduced(&key, AesBackend::Table, max + 1).err(),
+                   Some(CipherError::Rounds { found: max + 1, max: max }));
+    }
+}
@@ -0,0 +1,97 @@
+use std::error::Error;
+use std::fmt;
+
+pub const AES_BLOCK_SIZE: usize = 16;
+
+// AES-128, AES-192 and AES-256
+pub const AES_KEY_SIZES: [usize; 3] = [16, 24, 32];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CipherError {
+    // The cipher doesn't take keys of this many bytes
+    KeyLength { found: usize, allowed: &'static [usize] },
+    // Asked for more rounds than the cipher has, or none at all
+    Rounds { found: usize, max: usize },
+    // The blocks are too small for the mode, like a 64-bit CTR counter in a 4-byte block
+    BlockSize { found: usize, min: usize },
+}
+
+impl fmt::Display for CipherError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CipherError::KeyLength { found, allowed } =>
+                write!(f, "key is {} bytes, but it has to be one of {:?}", found, allowed),
+            CipherError::Rounds { found, max } =>
+                write!(f, "can't do {} rounds; it has to be between 1 and {}", found, max),
+            CipherError::BlockSize { found, min } =>
+                write!(f, "blocks are {} bytes, but this needs at least {}", found, min),
+        }
+    }
+}
+
+impl Error for CipherError { }
+
+// A keyed block cipher. The key schedule is worked out once, when the cipher is made, so
+// encrypting a block is just the rounds
+pub trait BlockCipher {
+    // [u8; n] for an n-byte block
+    type Block: Copy + Default + AsRef<[u8]> + AsMut<[u8]>;
+
+    fn encrypt_block(&self, block: &mut Self::Block);
+    fn decrypt_block(&self, block: &mut Self::Block);
+
+    fn block_size(&self) -> usize {
+        Self::Block::default().as_ref().len()
+    }
+
+    // Copies a slice into a block, for callers holding a chunk of something bigger. Panics if the
+    // slice isn't exactly one block long
+    fn block_from(bytes: &[u8]) -> Self::Block where Self: Sized {
+        let mut block = Self::Block::default();
+        assert_eq!(bytes.len(), block.as_ref().len(), "Slice isn't one block long!");
+        block.as_mut().copy_from_slice(bytes);
+        block
+    }
+}
+
+// So modes and attacks can borrow a cipher instead of taking it
+impl<'a, C: BlockCipher + ?Sized> BlockCipher for &'a C {
+    type Block = C::Block;
+
+    fn encrypt_block(&self, block: &mut C::Block) {
+        (**self).encrypt_block(block)
+    }
+
+    fn decrypt_block(&self, block: &mut C::Block) {
+        (**self).decrypt_block(block)
+    }
+}
+
+#[test]
+fn tst_block_cipher() {
+    use aes::Aes;
+    use c01::decode_hex;
+
+    // FIPS-197, appendix C
+    let plaintext = Aes::block_from(&decode_hex("00112233445566778899aabbccddeeff"));
+    let vectors = [
+        ("000102030405060708090a0b0c0d0e0f", "69c4e0d86a7b0430d8cdb78070b4c55a"),
+        ("000102030405060708090a0b0c0d0e0f1011121314151617", "dda97ca4864cdfe06eaf70a0ec0d7191"),
+        ("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+         "8ea2b7ca516745bfeafc49904b496089"),
+    ];
+    for &(key, ciphertext) in vectors.iter() {
+        let aes = Aes::new(&decode_hex(key)).unwrap();
+        assert_eq!(aes.key_size(), key.len() / 2);
+        assert_eq!(aes.block_size(), AES_BLOCK_SIZE);
+
+        let mut block = plaintext;
+        aes.encrypt_block(&mut block);
+        assert_eq!(&block[..], &decode_hex(ciphertext)[..]);
+        aes.decrypt_block(&mut block);
+        assert_eq!(block, plaintext);
+    }
+
+    assert_eq!(Aes::new(b"YELLOW SUB").err(),
+               Some(CipherError::KeyLength { found: 10, allowed: &AES_KEY_SIZES }));
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+// DES and friends, AES, and anything with 256-bit blocks
+pub const DEFAULT_BLOCK_SIZES: [usize; 3] = [8, 16, 32];
+
+// Repeated blocks in one ciphertext, for one block size and alignment
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockRepeats {
+    pub block_size: usize,
+    // Where the first block starts. Anything before it, and any partial block at the end, is
+    // ignored
+    pub offset: usize,
+    // How many blocks are copies of an earlier block
+    pub duplicates: usize,
+    // Byte positions of every set of identical blocks, each set and the sets themselves in order
+    // of appearance
+    pub groups: Vec<Vec<usize>>,
+    // Bits of evidence against the ciphertext being random: -log2 of the chance that random bytes
+    // of the same length would have this many duplicates at some block size and offset we tried.
+    // 0 if there are no duplicates
+    pub score: f64,
+}
+
+impl BlockRepeats {
+    // Looks at a single block size and offset. n_tries is how many (block size, offset) pairs
+    // are being tried in all, which the score has to account for
+    fn scan(bytes: &[u8], block_size: usize, offset: usize, n_tries: usize) -> BlockRepeats {
+        // Block contents -> index into groups
+        let mut seen = HashMap::<&[u8], usize>::new();
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut n_blocks = 0usize;
+        let start = offset.min(bytes.len());
+        for (i, block) in bytes[start..].chunks(block_size).enumerate() {
+            if block.len() < block_size {
+                break;
+            }
+            n_blocks += 1;
+            let pos = offset + i*block_size;
+            let n_groups = groups.len();
+            let idx = *seen.entry(block).or_insert(n_groups);
+            if idx == n_groups {
+                groups.push(vec![pos]);
+            } else {
+                groups[idx].push(pos);
+            }
+        }
+        groups.retain(|g| g.len() > 1);
+        let duplicates = groups.iter().map(|g| g.len() - 1).sum();
+
+        BlockRepeats {
+            block_size: block_size,
+            offset: offset,
+            duplicates: duplicates,
+            groups: groups,
+            score: evidence_bits(n_blocks, block_size, duplicates, n_tries),
+        }
+    }
+
+    // Bytes covered by blocks that show up more than once
+    fn coverage(&self) -> usize {
+        self.groups.iter().map(|g| g.len()).sum::<usize>() * self.block_size
+    }
+}
+
+// The number of pairs of equal blocks among n random blocks is about Poisson with mean
+// lambda = C(n, 2) / 256^block_size, so seeing d duplicates has probability about lambda^d / d!
+fn evidence_bits(n_blocks: usize, block_size: usize, duplicates: usize, n_tries: usize) -> f64 {
+    if duplicates == 0 {
+        return 0f64;
+    }
+    let n = n_blocks as f64;
+    let log2_lambda = (n * (n-1f64) / 2f64).log2() - 8f64 * block_size as f64;
+    let log2_factorial = (1..(duplicates+1)).map(|k| (k as f64).log2()).sum::<f64>();
+    let bits = log2_factorial - (duplicates as f64) * log2_lambda - (n_tries as f64).log2();
+
+    bits.max(0f64)
+}
+
+// Tries every block size at every offset and returns the one that explains the most repeated
+// bytes. Half a block size finds every repeat that the real one does, and so does a multiple of
+// it when whole runs of blocks repeat, so ties go to the largest size. None if there are no
+// duplicates at all
+pub fn find_block_repeats(bytes: &[u8], block_sizes: &[usize]) -> Option<BlockRepeats> {
+    let n_tries = block_sizes.iter().sum();
+    let mut best: Option<BlockRepeats> = None;
+    for &size in block_sizes {
+        assert!(size > 0, "Block size can't be 0!");
+        for offset in 0..size {
+            let candidate = BlockRepeats::scan(bytes, size, offset, n_tries);
+            if candidate.duplicates == 0 {
+                continue;
+            }
+            let better = match best {
+                None => true,
+                Some(ref b) => candidate.coverage() > b.coverage()
+                               || (candidate.coverage() == b.coverage()
+                                   && candidate.block_size > b.block_size),
+            };
+            if better {
+                best = Some(candidate);
+            }
+        }
+    }
+
+    best
+}
+
+// Scores every ciphertext and returns (index, repeats) for the ones with any duplicates, most
+// likely ECB first. Only one ciphertext is held at a time, so this can be fed straight from a
+// file reader
+pub fn rank_block_repeats<I, T>(ciphertexts: I, block_sizes: &[usize]) -> Vec<(usize, BlockRepeats)>
+        where I: IntoIterator<Item=T>, T: AsRef<[u8]> {
+    let mut out = ciphertexts.into_iter().enumerate()
+                             .filter_map(|(i, ct)| find_block_repeats(ct.as_ref(), block_sizes)
+                                                       .map(|r| (i, r)))
+                             .collect::<Vec<(usize, BlockRepeats)>>();
+    // Stable, so ties stay in input order
+    out.sort_by(|&(_, ref a), &(_, ref b)| b.score.partial_cmp(&a.score).unwrap());
+
+    out
+}
+
+#[test]
+fn tst_block_repeats() {
+    use c01::decode_hex;
+    use std::fs::File;
+    use std::io::{BufRead, BufReader};
+
+    // Straight from the file, a line at a time
+    let lines = BufReader::new(File::open("c08.txt").unwrap()).lines();
+    let ranked = rank_block_repeats(lines.map(|l| decode_hex(&l.unwrap())), &DEFAULT_BLOCK_SIZES);
+    assert_eq!(ranked.len(), 1);
+    let (idx, ref repeats) = ranked[0];
+    assert_eq!(idx, 132);
+    assert_eq!((repeats.block_size, repeats.offset, repeats.duplicates), (16, 0, 3));
+    assert_eq!(repeats.groups, vec![vec![16, 48, 80, 112]]);
+    assert!(repeats.score > 300f64);
+
+    // Some random-looking bytes from an xorshift generator
+    let mut state = 0x9e3779b97f4a7c15u64;
+    let mut random = |n: usize| (0..n).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 24) as u8
+    }).collect::<Vec<u8>>();
+
+    // An 8-byte block cipher, with a 5-byte header in front and a partial block at the end
+    let a = random(8);
+    let b = random(8);
+    let mut des = random(5);
+    for block in [&a, &b, &a, &a, &b].iter() {
+        des.extend_from_slice(block);
+        des.extend(random(8));
+    }
+    des.extend(random(3));
+    let repeats = find_block_repeats(&des, &DEFAULT_BLOCK_SIZES).unwrap();
+    assert_eq!((repeats.block_size, repeats.offset, repeats.duplicates), (8, 5, 3));
+    assert_eq!(repeats.groups, vec![vec![5, 37, 53], vec![21, 69]]);
+
+    // Every block the same, which is as consistent with 32-byte blocks as with 16
+    let block = random(16);
+    let same = (0..10).flat_map(|_| block.clone()).collect::<Vec<u8>>();
+    let repeats = find_block_repeats(&same, &[8, 16, 32]).unwrap();
+    assert_eq!(repeats.block_size, 32);
+    assert_eq!(find_block_repeats(&same, &[8, 16]).unwrap().block_size, 16);
+
+    // A couple of duplicates rank above one, and random data doesn't rank at all
+    let one = [&a[..], &b[..], &random(16)[..], &a[..], &b[..]].concat();
+    let two = [&b[..], &a[..], &random(16)[..], &b[..], &a[..], &random(16)[..], &b[..], &a[..]]
+                  .concat();
+    let ranked = rank_block_repeats(vec![random(64), one, two], &[16]);
+    assert_eq!(ranked.iter().map(|&(i, _)| i).collect::<Vec<usize>>(), vec![2, 1]);
+    assert_eq!(ranked[0].1.duplicates, 2);
+}
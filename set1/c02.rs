@@ -1,23 +1,8 @@
 use c01::decode_hex;
-
-fn nibble_to_char(nibble: u8) -> char {
-    match nibble {
-          0...9 => (nibble + 48) as char,
-        10...15 => (nibble + 87) as char,
-                _ => panic!("Input is bigger than a nibble! {:08x}", nibble)
-    }
-}
+use codec::Encoding;
 
 pub fn encode_hex(input: &[u8]) -> String {
-    let mut out = String::new();
-    for byte in input {
-        let high = nibble_to_char(byte >> 4);
-        let low = nibble_to_char(byte & 15);
-
-        out.push(high); out.push(low);
-    }
-
-    out
+    Encoding::Hex.encode(input)
 }
 
 pub fn xor_bytes(a: &[u8], b: &[u8]) -> Vec<u8> {
@@ -1,9 +1,10 @@
 use c01::decode_hex;
 use c02::xor_bytes;
+use scoring::Scorer;
+use std::cmp::Ordering;
 use std::f64::INFINITY;
 
-pub fn coincidence_err(s: String) -> f64 {
-    let bytes = s.as_bytes();
+pub fn coincidence_err(bytes: &[u8]) -> f64 {
     for b in bytes {
         match *b {
                0...31 => return INFINITY,
@@ -43,37 +44,91 @@ pub fn make_key_vec(key: &[u8], out_len: usize) -> Vec<u8> {
     v
 }
 
-pub fn test_all_keys<F: Fn(String) -> f64>(input: &[u8], err_func: F) -> (u8, f64) {
-    let mut winning_key = 0;
-    let mut lowest_err = INFINITY;
-    for u in 0..255 {
-        let test_vec = make_key_vec(&[u], input.len());
-        let xored = xor_bytes(input, &test_vec);
-        /*{
-            let s = String::from_utf8(xored.clone());
-            if s.is_ok() { println!("\"{}\"", s.unwrap()); }
-        }*/
-        let s = match String::from_utf8(xored) {
-            Ok(t)  => t,
-            Err(_) => continue,
-        };
-        let err = err_func(s);
-        if err < lowest_err {
-            lowest_err = err;
-            winning_key = u;
+// One possible key byte for a single-byte XOR, with the plaintext it gives
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyCandidate {
+    pub key: u8,
+    pub score: f64,
+    pub plaintext: Vec<u8>,
+}
+
+// The best candidates from a single-byte XOR key search, best first. confidence is the gap between
+// the first and second scores relative to the second, so 0 means a tie and 1 means the runner-up
+// was rejected outright (an infinite or otherwise incomparable score)
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyRanking {
+    pub candidates: Vec<KeyCandidate>,
+    pub confidence: f64,
+}
+
+// Orders scores ascending with NaN last, so a misbehaving scorer can't break the sort
+pub fn cmp_scores(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.partial_cmp(&b).unwrap(),
+    }
+}
+
+fn confidence_margin(best: f64, second: f64) -> f64 {
+    if !second.is_finite() || second.is_nan() {
+        if best.is_finite() { 1f64 } else { 0f64 }
+    } else if second == 0f64 {
+        0f64
+    } else {
+        ((second - best) / second.abs()).max(0f64).min(1f64)
+    }
+}
+
+// Tries all 256 key bytes and returns the top_k by score. Ties keep the smaller key first
+pub fn rank_keys<S: Scorer + ?Sized>(input: &[u8], scorer: &S, top_k: usize) -> KeyRanking {
+    let mut candidates = (0usize..256).map(|u| {
+        let key = u as u8;
+        let plaintext = input.iter().map(|&b| b ^ key).collect::<Vec<u8>>();
+        KeyCandidate {
+            key: key,
+            score: scorer.score(&plaintext),
+            plaintext: plaintext,
         }
+    }).collect::<Vec<KeyCandidate>>();
+    candidates.sort_by(|a, b| cmp_scores(a.score, b.score));
+
+    let confidence = confidence_margin(candidates[0].score, candidates[1].score);
+    candidates.truncate(top_k);
+
+    KeyRanking {
+        candidates: candidates,
+        confidence: confidence,
     }
+}
+
+pub fn test_all_keys<S: Scorer + ?Sized>(input: &[u8], scorer: &S) -> (u8, f64) {
+    let best = rank_keys(input, scorer, 1).candidates.remove(0);
 
-    (winning_key, lowest_err)
+    (best.key, best.score)
 }
 
 #[test]
 fn tst03 () {
     let b = decode_hex("1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736");
-    let (winning_key, _) = test_all_keys(&b, coincidence_err);
+    let (winning_key, _) = test_all_keys(&b, &coincidence_err);
     //println!("{} won with an error of {}", winning_key, min_err);
     let winning_key_vec = make_key_vec(&[winning_key], b.len());
     let xored = xor_bytes(&b, &winning_key_vec);
     let plaintext = String::from_utf8(xored).unwrap();
     assert_eq!(plaintext, "Cooking MC's like a pound of bacon");
+
+    // The index of coincidence can't tell apart keys that permute the letters, so rank with
+    // something that can
+    let ranking = rank_keys(&b, &::c04::chi_sq_monogram, 5);
+    assert_eq!(ranking.candidates.len(), 5);
+    assert_eq!(ranking.candidates[0].key, winning_key);
+    assert_eq!(ranking.candidates[0].plaintext, plaintext.as_bytes());
+    assert!(ranking.candidates.windows(2).all(|w| w[0].score <= w[1].score));
+    assert!(ranking.confidence > 0f64 && ranking.confidence <= 1f64);
+
+    // 0xff used to be skipped
+    let ff_ct = plaintext.bytes().map(|b| b ^ 0xff).collect::<Vec<u8>>();
+    assert_eq!(test_all_keys(&ff_ct, &::c04::chi_sq_monogram).0, 0xff);
 }
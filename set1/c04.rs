@@ -1,15 +1,37 @@
-use iterslide::SlideIterator;
-
 use c01::decode_hex;
 use c02::xor_bytes;
 use c03::{make_key_vec, test_all_keys};
-use std::ascii::AsciiExt;
-use std::collections::HashMap;
+use scoring::Scorer;
+use std::cell::RefCell;
 use std::f64;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Read};
 use std::str::FromStr;
 
+// Letter frequencies, from Wikipedia. They sum to 1 over the letters alone
+const ENGLISH_FREQS: [f64; 26] = [0.08167, 0.01492, 0.02782, 0.04253,
+                                  0.12702, 0.02228, 0.02015, 0.06094,
+                                  0.06966, 0.00153, 0.00772, 0.04025,
+                                  0.02406, 0.06749, 0.07507, 0.01929,
+                                  0.00095, 0.05987, 0.06327, 0.09056,
+                                  0.02758, 0.00978, 0.02360, 0.00150,
+                                  0.01974, 0.00074];
+
+// Rough shares of English text taken up by letters, whitespace, and other printable characters.
+// Ignoring the last two is what made the chi-squared tests prefer line noise with a few letters
+// in it over real sentences
+const LETTER_SHARE: f64 = 0.78;
+const SPACE_SHARE: f64 = 0.18;
+const OTHER_SHARE: f64 = 0.04;
+// Same idea for adjacent pairs, indexed by [first][second] class (letter, whitespace, other). The
+// letter-letter share is split further using bigrams.txt
+const PAIR_CLASS_SHARES: [[f64; 3]; 3] = [[0.59,  0.15,  0.03],
+                                          [0.17,  0.005, 0.01],
+                                          [0.01,  0.03,  0.005]];
+
+// Parsed once per thread instead of on every call
+thread_local!(static BIGRAM_FREQS: RefCell<Option<Vec<f64>>> = RefCell::new(None));
+
 pub fn get_lines(filename: &str) -> Vec<String> {
     let file = File::open(filename).unwrap();
     let buf = BufReader::new(file);
@@ -17,130 +39,143 @@ pub fn get_lines(filename: &str) -> Vec<String> {
     buf.lines().map(|s| s.unwrap()).collect()
 }
 
-fn fill_bigram_hashmap() -> HashMap<String, f64> {
-    let mut out = HashMap::<String, f64>::new();
-    for line in get_lines("bigrams.txt") {
+// Tab, newline, and carriage return are fine; all other control characters and non-ASCII aren't
+fn is_unprintable(b: u8) -> bool {
+    match b {
+        b'\x00'...b'\x08'
+      | b'\x0B'...b'\x0C'
+      | b'\x0E'...b'\x1F'
+      | b'\x7F'...b'\xFF' => true,
+                        _ => false
+    }
+}
+
+// 0 for letters, 1 for whitespace, 2 for everything else
+fn char_class(b: u8) -> usize {
+    match b {
+        b'a'...b'z' | b'A'...b'Z' => 0,
+        b' ' | b'\t' | b'\n' | b'\r' => 1,
+                                  _ => 2
+    }
+}
+
+fn letter_index(b: u8) -> Option<usize> {
+    match b {
+        b'a'...b'z' => Some((b - b'a') as usize),
+        b'A'...b'Z' => Some((b - b'A') as usize),
+                  _ => None
+    }
+}
+
+// Frequencies of the 676 letter pairs, indexed by 26*first + second. bigrams.txt is embedded so
+// this works from any working directory
+fn bigram_freqs() -> Vec<f64> {
+    let mut out = vec![0f64; 26*26];
+    for line in include_str!("bigrams.txt").lines() {
         let mut i = line.split(' ');
-        let key = i.next().unwrap(); let val = i.next().unwrap();
-        out.insert(key.to_string(), f64::from_str(val).unwrap());
+        let key = i.next().unwrap().as_bytes(); let val = i.next().unwrap();
+        let idx = 26*letter_index(key[0]).unwrap() + letter_index(key[1]).unwrap();
+        out[idx] = f64::from_str(val).unwrap();
     }
 
     out
 }
 
-// How far away is this from English?
-pub fn chi_sq_monogram(s: String) -> f64 {
-
-    let histogram: &mut [usize; 26] = &mut [0; 26];
-    for c in s.to_ascii_lowercase().chars() {
-        if !c.is_ascii() { return f64::INFINITY; } // We only want ascii
-        match c as u8 {
-                      b'a'...b'z' => histogram[((c as u8)-97) as usize] += 1,
-                b'\x00'...b'\x08'
-              | b'\x0B'...b'\x0C'
-              | b'\x0E'...b'\x1F'
-              | b'\x7F'...b'\xFF' => return f64::INFINITY,
-                                _ => ()
+fn chi_sq(observed: &[f64], expected: &[f64]) -> f64 {
+    observed.iter().zip(expected).filter(|&(_, &e)| e > 0f64)
+            .fold(0f64, |acc, (&o, &e)| acc + (o-e)*(o-e) / e)
+}
+
+// How far away is this from English? Letters, whitespace, and other printable characters are
+// each their own category
+pub fn chi_sq_monogram(s: &[u8]) -> f64 {
+    // 26 letters, then whitespace, then everything else
+    let mut histogram = [0f64; 28];
+    for &b in s {
+        if is_unprintable(b) { return f64::INFINITY; } // We only want ascii
+        match letter_index(b) {
+            Some(i) => histogram[i] += 1f64,
+            None => histogram[25 + char_class(b)] += 1f64,
         }
     }
-    let total = histogram.iter().fold(0, |acc, &f| acc + f) as f64;
-
-    let english_freqs = [0.08167, 0.01492, 0.02782, 0.04253f64,
-                         0.12702, 0.02228, 0.02015, 0.06094,
-                         0.06966, 0.00153, 0.00772, 0.04025,
-                         0.02406, 0.06749, 0.07507, 0.01929,
-                         0.00095, 0.05987, 0.06327, 0.09056,
-                         0.02758, 0.00978, 0.02360, 0.00150,
-                         0.01974, 0.00074];
+    let total = s.len() as f64;
 
-    let mut err = 0f64;
-    for i in 0..histogram.len() {
-        let diff = (histogram[i] as f64) - total*english_freqs[i];
-        err += diff*diff / (total*english_freqs[i]);
+    let mut expected = [0f64; 28];
+    for i in 0..26 {
+        expected[i] = total * LETTER_SHARE * ENGLISH_FREQS[i];
     }
-    err
+    expected[26] = total * SPACE_SHARE;
+    expected[27] = total * OTHER_SHARE;
+
+    chi_sq(&histogram, &expected)
 }
 
-pub fn chi_sq_bigram(s: String) -> f64 {
-    for c in s.to_ascii_lowercase().chars() {
-        if !c.is_ascii() { return f64::INFINITY; } // We only want ascii
-        match c as u8 {
-                b'\x00'...b'\x08'
-              | b'\x0B'...b'\x0C'
-              | b'\x0E'...b'\x1F'
-              | b'\x7F'...b'\xFF' => return f64::INFINITY,
-                                _ => ()
-        }
+// Same as above over adjacent pairs. Every pair that isn't two letters goes in a bucket for its
+// classes, e.g., letter-whitespace
+pub fn chi_sq_bigram(s: &[u8]) -> f64 {
+    if s.iter().any(|&b| is_unprintable(b)) {
+        return f64::INFINITY; // We only want ascii
+    }
+    if s.len() < 2 {
+        return 0f64;
     }
 
-    let mut histogram = HashMap::<String, usize>::new();
-    for chrs in s.to_ascii_lowercase().chars().slide(2) {
-        let mut bi = String::new();
-        bi.push(chrs[0]); bi.push(chrs[1]);
-        let curr = *histogram.get(&bi).unwrap_or(&0);
-        let _ = histogram.insert(bi, curr+1);
+    // 676 letter pairs, then the 9 class pairs (the letter-letter one stays empty)
+    let mut histogram = vec![0f64; 26*26 + 9];
+    for pair in s.windows(2) {
+        match (letter_index(pair[0]), letter_index(pair[1])) {
+            (Some(a), Some(b)) => histogram[26*a + b] += 1f64,
+            _ => histogram[26*26 + 3*char_class(pair[0]) + char_class(pair[1])] += 1f64,
+        }
     }
-    //let total = histogram.values().fold(0, |acc, &f| acc + f) as f64;
     let total = (s.len()-1) as f64;
 
-    let eng_bi_freqs: HashMap<String, f64> = fill_bigram_hashmap();
+    BIGRAM_FREQS.with(|cell| {
+        let mut cached = cell.borrow_mut();
+        if cached.is_none() {
+            *cached = Some(bigram_freqs());
+        }
+        let freqs = cached.as_ref().unwrap();
 
-    let mut err = 0f64;
-    for (key, eng_freq) in eng_bi_freqs.iter() {
-        let diff = (*histogram.get(key).unwrap_or(&0) as f64) - total*eng_freq;
-        err += diff*diff / (total*eng_freq);
-    }
+        let mut expected = freqs.iter().map(|f| total * PAIR_CLASS_SHARES[0][0] * f)
+                                .collect::<Vec<f64>>();
+        for i in 0..9 {
+            expected.push(if i == 0 { 0f64 } else { total * PAIR_CLASS_SHARES[i/3][i%3] });
+        }
 
-    err
+        chi_sq(&histogram, &expected)
+    })
 }
 
-pub fn braindead_err(s: String) -> f64 {
+pub fn braindead_err(s: &[u8]) -> f64 {
     let mut err = 0f64;
-    let english_freqs = [0.08167, 0.01492, 0.02782, 0.04253f64,
-                         0.12702, 0.02228, 0.02015, 0.06094,
-                         0.06966, 0.00153, 0.00772, 0.04025,
-                         0.02406, 0.06749, 0.07507, 0.01929,
-                         0.00095, 0.05987, 0.06327, 0.09056,
-                         0.02758, 0.00978, 0.02360, 0.00150,
-                         0.01974, 0.00074];
-    for c in s.to_ascii_lowercase().chars() {
-        if !c.is_ascii() { return f64::INFINITY; } // We only want ascii
-        match c as u8 {
-                      b'a'...b'z' => err += english_freqs[((c as u8)-97) as usize],
-                b'\x00'...b'\x08'
-              | b'\x0B'...b'\x0C'
-              | b'\x0E'...b'\x1F'
-              | b'\x7F'...b'\xFF' => return f64::INFINITY,
-                                _ => ()
+    for &b in s {
+        if is_unprintable(b) { return f64::INFINITY; } // We only want ascii
+        if let Some(i) = letter_index(b) {
+            err += ENGLISH_FREQS[i];
         }
     }
 
     1f64 / err
 }
 
-pub fn extra_braindead_err(s: String) -> f64 {
+pub fn extra_braindead_err(s: &[u8]) -> f64 {
     let mut count = 0f64;
-    for c in s.to_ascii_lowercase().chars() {
-        if !c.is_ascii() { return f64::INFINITY; } // We only want ascii
-        match c as u8 {
-                b'a'...b'z'       => count += 1f64,
-                b'\x00'...b'\x08'
-              | b'\x0B'...b'\x0C'
-              | b'\x0E'...b'\x1F'
-              | b'\x7F'...b'\xFF' => return f64::INFINITY,
-                                _ => ()
+    for &b in s {
+        if is_unprintable(b) { return f64::INFINITY; } // We only want ascii
+        if letter_index(b).is_some() {
+            count += 1f64;
         }
     }
     1f64 / (count / s.len() as f64)
 }
 
-fn test_all<F: Copy+Fn(String) -> f64>(ciphertexts: &[&[u8]],
-                                       err_func: F) -> (u8, usize, f64) {
+pub fn test_all<S: Scorer + ?Sized>(ciphertexts: &[&[u8]], scorer: &S) -> (u8, usize, f64) {
     let mut lowest_err = f64::INFINITY;
     let mut winning_line_idx = 0usize;
     let mut winning_key = 0;
     for (i, ct) in ciphertexts.iter().enumerate() {
-        let (key, err) = test_all_keys(ct, err_func);
+        let (key, err) = test_all_keys(ct, scorer);
         if err < lowest_err {
             //println!("New winning err: {}", err);
             lowest_err = err;
@@ -153,15 +188,12 @@ fn test_all<F: Copy+Fn(String) -> f64>(ciphertexts: &[&[u8]],
 
 #[test]
 fn tst04() {
-    // braindead_err and extra_braindead_err both work here.
-    // chi_sq_monogram does not because "R4^Ho+[7tRO_dV)84fi##[R3LihkwG" has
-    //   a lower score than "Now that the party is jumping\n"...go figure.
-    // chi_sq_bigram also does not work and I'm not entirely certain why
-    let err_func = braindead_err;
+    // Every scorer works here; see the benchmark in scoring.rs
+    let scorer = braindead_err;
     let lines: Vec<Vec<u8>> = get_lines("c04.txt").iter().map(|s| decode_hex(&*s)).collect();
     let borrowed = lines.iter().map(|s| &s[..]).collect::<Vec<&[u8]>>();
 
-    let (key_byte, idx, _) = test_all(&borrowed[..], err_func);
+    let (key_byte, idx, _) = test_all(&borrowed[..], &scorer);
 
     let key_vec = make_key_vec(&[key_byte], lines[0].len());
     let xored = xor_bytes(borrowed[idx], &key_vec);
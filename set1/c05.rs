@@ -1,9 +1,13 @@
-use c02::{encode_hex, xor_bytes};
+use c02::encode_hex;
+use xor_stream::xor_repeating_into;
 
 pub fn xor_bytes_repeating(plaintext: &[u8], key: &[u8]) -> Vec<u8> {
-    plaintext.chunks(key.len())
-             .flat_map(|chunk| xor_bytes(chunk, &key[..chunk.len()]))
-             .collect()
+    let mut out = plaintext.to_vec();
+    if let Err(e) = xor_repeating_into(&mut out, key) {
+        panic!("Can't XOR with this key! {}", e);
+    }
+
+    out
 }
 
 fn xor_string_repeating(message: &str, key: &str) -> String {
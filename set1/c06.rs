@@ -2,6 +2,8 @@ use c01::decode_hex;
 use c02::{encode_hex, xor_bytes};
 use c03::{coincidence_err, make_key_vec, test_all_keys};
 use c04::{chi_sq_monogram, chi_sq_bigram, braindead_err, extra_braindead_err};
+use codec::Encoding;
+use scoring::Scorer;
 use std::fs::File;
 use std::io::{BufReader, Read};
 
@@ -15,47 +17,15 @@ pub fn dump_file(filename: &str) -> String {
     out
 }
 
-fn b64_to_sextet(b: char) -> u8 {
-    match b {
-        'A'...'Z' => (b as u8) - 65,
-        'a'...'z' => (b as u8) - 71,
-        '0'...'9' => (b as u8) + 4,
-              '+' => 62u8,
-              '/' => 63u8,
-              '=' => 0u8, // Placeholder value, caller should handle this
-               _  => panic!("Invalid base64 input!")
-    }
-}
-
+// Panicking wrapper kept for the challenge code; use Encoding::Base64.decode for untrusted input
 pub fn decode_b64(b64: &str) -> Vec<u8> {
-    let mut out = Vec::<u8>::new();
-
-    let chars: Vec<char> = b64.chars().collect();
-
-    for chunk in (&chars).chunks(4) {
-        if chunk.len() != 4 {
-            panic!("Base64 input's length is not a multiple of four!");
-        }
-
-        let vals: Vec<u8> = chunk.iter().map(|&i| b64_to_sextet(i)).collect();
-        let (a,b,c,d) = (vals[0], vals[1], vals[2], vals[3]);
-
-        let x: u8 = (a << 2) | (b >> 4);
-        out.push(x);
-
-        if chunk[2] == '=' { break; }
-        let y: u8 = ((b & 15) << 4) | (c >> 2);
-        out.push(y);
-
-        if chunk[3] == '=' { break; }
-        let z: u8 = ((c & 3) << 6) | d;
-        out.push(z);
+    match Encoding::Base64.decode(b64) {
+        Ok(bytes) => bytes,
+        Err(e) => panic!("Invalid base64 input! {}", e)
     }
-
-    out
 }
 
-fn hamming_dist(a: &[u8], b: &[u8]) -> u32 {
+pub fn hamming_dist(a: &[u8], b: &[u8]) -> u32 {
     if a.len() != b.len() {
         panic!("Hamming distance only works for strings of equal length!");
     }
@@ -78,7 +48,7 @@ fn hamming_score(bytes: &[u8], chunk_size: usize) -> f64 {
     running_avg
 }
 
-fn sorted_key_sizes(ciphertext: &[u8]) -> Vec<(usize, f64)> {
+pub fn sorted_key_sizes(ciphertext: &[u8]) -> Vec<(usize, f64)> {
     let mut out = Vec::<(usize, f64)>::new(); // (key_size, hamming_score)
     for size in 2..40usize {
         let score = hamming_score(ciphertext, size);
@@ -90,7 +60,8 @@ fn sorted_key_sizes(ciphertext: &[u8]) -> Vec<(usize, f64)> {
 }
 
 // Returns the greedy lowest-error key and the average error
-fn break_with_key_size(ciphertext: &[u8], key_size: usize) -> (Vec<u8>, f64) {
+pub fn break_with_key_size<S: Scorer + ?Sized>(ciphertext: &[u8], key_size: usize,
+                                               scorer: &S) -> (Vec<u8>, f64) {
     // Split ciphertext into repeating-key xored substrings
     let mut substrs = Vec::<Vec<u8>>::new();
     for _ in 0..key_size {
@@ -106,8 +77,7 @@ fn break_with_key_size(ciphertext: &[u8], key_size: usize) -> (Vec<u8>, f64) {
     let mut key = Vec::<u8>::new();
     let mut avg_err = 0f64;
     for substr in substrs {
-        // God dammit, why is braindead_err always better than chi_sq_monogram?!
-        let (key_byte, err) = test_all_keys(&substr, braindead_err);
+        let (key_byte, err) = test_all_keys(&substr, scorer);
         key.push(key_byte);
         avg_err += err / (key_size as f64);
     }
@@ -136,7 +106,7 @@ fn tst06() {
     keysize_err_tuples.truncate(5); // Only test the top 4
 
     let mut key_err_tuples = keysize_err_tuples.iter().map(|&(keysize, _)| 
-                                                           break_with_key_size(&ciphertext_bytes, keysize))
+                                                           break_with_key_size(&ciphertext_bytes, keysize, &braindead_err))
                                                       .collect::<Vec<(Vec<u8>, f64)>>();
     key_err_tuples.sort_by(|&(_,b), &(_,d)| b.partial_cmp(&d).unwrap());
 
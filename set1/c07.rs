@@ -1,9 +1,13 @@
+use aes::Aes;
+use block_cipher::AES_BLOCK_SIZE;
 use c02::encode_hex;
 use c06::{decode_b64, dump_file};
-use openssl::crypto::symm;
+use modes::{decrypt_with_mode, Mode};
+use padding::{Padding, Pkcs7};
 
 pub fn decrypt_aes_ecb(ciphertext: &[u8], key: &[u8]) -> Vec<u8> {
-    symm::decrypt(symm::Type::AES_128_ECB, key, &*Vec::<u8>::new(), ciphertext)
+    let padded = decrypt_with_mode(Mode::Ecb, &Aes::new(key).unwrap(), &[], ciphertext).unwrap();
+    Pkcs7.unpad(&padded, AES_BLOCK_SIZE).unwrap().to_vec()
 }
 
 #[test]
@@ -1,24 +1,16 @@
 use c01::decode_hex;
 use c04::get_lines;
-use std::collections::HashMap;
+use std::collections::HashSet;
 
-// Returns the index of the ECB-encrypted ciphertext; None if it cannot be found
-fn detect_repeating_blocks(ciphertexts: &[&[u8]], block_size: usize) -> Option<usize> {
-    for (idx, bytes) in ciphertexts.iter().enumerate() {
-        let mut hm = HashMap::<Vec<u8>, bool>::new();
-
-        for chunk in bytes.chunks(block_size) {
-            let chunk_vec = chunk.to_vec();
-            // This text has been repeated
-            if hm.contains_key(&chunk_vec) {
-                return Some(idx);
-            }
-
-            hm.insert(chunk_vec, true);
-        }
-    }
+// How many block_size chunks are copies of an earlier chunk
+pub fn count_repeated_blocks(bytes: &[u8], block_size: usize) -> usize {
+    let mut seen = HashSet::<&[u8]>::new();
+    bytes.chunks(block_size).filter(|&chunk| !seen.insert(chunk)).count()
+}
 
-    None
+// Returns the index of the ECB-encrypted ciphertext; None if it cannot be found
+pub fn detect_repeating_blocks(ciphertexts: &[&[u8]], block_size: usize) -> Option<usize> {
+    ciphertexts.iter().position(|bytes| count_repeated_blocks(bytes, block_size) > 0)
 }
 
 #[test]
@@ -0,0 +1,547 @@
+use c03::cmp_scores;
+use rand::{Rng, SeedableRng, XorShiftRng};
+use scoring::Scorer;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+// English letters from most to least common, for the substitution breaker's first guess
+const ENGLISH_ORDER: &'static [u8] = b"etaoinshrdlcumwfgypbvkjxqz";
+// Transpositions up to this wide are broken by trying every column order
+const MAX_BRUTE_FORCE_WIDTH: usize = 6;
+// Hill climbs from a fresh start this many times and keeps the best
+const CLIMB_RESTARTS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClassicalError {
+    // A key for the letter-by-letter ciphers needs at least one letter
+    EmptyKey,
+    // Keys are letters only, and this byte isn't one
+    NotALetter { byte: u8 },
+    // A substitution key has to use every letter once, and this one never turns up
+    NotAPermutation { missing: u8 },
+    // A column order has to number the columns 0 up to its length, once each
+    BadColumnOrder,
+    // No width from 2 to max_width fits in the ciphertext
+    NoWidths { max_width: usize, len: usize },
+}
+
+impl fmt::Display for ClassicalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClassicalError::EmptyKey => write!(f, "the key is empty"),
+            ClassicalError::NotALetter { byte } =>
+                write!(f, "key byte {:#04x} isn't a letter", byte),
+            ClassicalError::NotAPermutation { missing } =>
+                write!(f, "the key never uses {}", missing as char),
+            ClassicalError::BadColumnOrder => write!(f, "the column order isn't a permutation"),
+            ClassicalError::NoWidths { max_width, len } =>
+                write!(f, "no width up to {} fits {} bytes of ciphertext", max_width, len),
+        }
+    }
+}
+
+impl Error for ClassicalError { }
+
+// A broken classical cipher
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassicalSolution<K> {
+    pub key: K,
+    pub plaintext: Vec<u8>,
+    pub score: f64,
+}
+
+// The hill climbers shake things up at random, but the same ciphertext should always break the
+// same way
+fn seeded_rng() -> XorShiftRng {
+    XorShiftRng::from_seed([0x9e3779b9, 0x7f4a7c15, 0x2545f491, 0x4f6cdd1d])
+}
+
+// 0..26 for a letter of either case
+fn letter_index(b: u8) -> Option<u8> {
+    match b {
+        b'a'...b'z' => Some(b - b'a'),
+        b'A'...b'Z' => Some(b - b'A'),
+                  _ => None
+    }
+}
+
+// Replaces the letter b with letter number idx, keeping b's case
+fn with_index(b: u8, idx: u8) -> u8 {
+    if b.is_ascii_uppercase() { b'A' + idx } else { b'a' + idx }
+}
+
+// The letter indices of a key
+fn letter_key(key: &[u8]) -> Result<Vec<u8>, ClassicalError> {
+    if key.is_empty() {
+        return Err(ClassicalError::EmptyKey);
+    }
+    key.iter().map(|&k| letter_index(k).ok_or(ClassicalError::NotALetter { byte: k })).collect()
+}
+
+// Applies f(letter index, key index) to every letter, where the key advances one step per letter
+// and everything that isn't a letter passes through untouched. key is letter indices, and can't
+// be empty
+fn map_letters<F: Fn(u8, u8) -> u8>(text: &[u8], key: &[u8], f: F) -> Vec<u8> {
+    let mut i = 0;
+    text.iter().map(|&b| match letter_index(b) {
+        Some(p) => {
+            let out = with_index(b, f(p, key[i % key.len()]));
+            i += 1;
+            out
+        }
+        None => b,
+    }).collect()
+}
+
+// c = p + k, and the rest undo or mirror it
+fn add_letters(p: u8, k: u8) -> u8 {
+    (p + k) % 26
+}
+
+fn sub_letters(c: u8, k: u8) -> u8 {
+    (c + 26 - k) % 26
+}
+
+fn beaufort_letters(p: u8, k: u8) -> u8 {
+    (k + 26 - p) % 26
+}
+
+pub fn caesar_encrypt(text: &[u8], shift: u8) -> Vec<u8> {
+    map_letters(text, &[shift % 26], add_letters)
+}
+
+pub fn caesar_decrypt(text: &[u8], shift: u8) -> Vec<u8> {
+    caesar_encrypt(text, 26 - shift % 26)
+}
+
+// c = p + k
+pub fn vigenere_encrypt(text: &[u8], key: &[u8]) -> Result<Vec<u8>, ClassicalError> {
+    Ok(map_letters(text, &letter_key(key)?, add_letters))
+}
+
+pub fn vigenere_decrypt(text: &[u8], key: &[u8]) -> Result<Vec<u8>, ClassicalError> {
+    Ok(map_letters(text, &letter_key(key)?, sub_letters))
+}
+
+// c = k - p, which is its own inverse
+pub fn beaufort(text: &[u8], key: &[u8]) -> Result<Vec<u8>, ClassicalError> {
+    Ok(map_letters(text, &letter_key(key)?, beaufort_letters))
+}
+
+// key[i] is the index of what the ith letter turns into
+fn substitute_indices(text: &[u8], key: &[u8]) -> Vec<u8> {
+    text.iter().map(|&b| match letter_index(b) {
+        Some(p) => with_index(b, key[p as usize]),
+        None => b,
+    }).collect()
+}
+
+// key[i] is what the ith letter of the alphabet turns into. Case is kept
+pub fn substitute(text: &[u8], key: &[u8; 26]) -> Result<Vec<u8>, ClassicalError> {
+    Ok(substitute_indices(text, &letter_key(key)?))
+}
+
+// The key that undoes substitute(_, key)
+pub fn invert_substitution(key: &[u8; 26]) -> Result<[u8; 26], ClassicalError> {
+    let mut out = [0u8; 26];
+    for (p, &c) in letter_key(key)?.iter().enumerate() {
+        out[c as usize] = b'a' + p as u8;
+    }
+    match out.iter().position(|&b| b == 0) {
+        Some(missing) => Err(ClassicalError::NotAPermutation { missing: b'a' + missing as u8 }),
+        None => Ok(out),
+    }
+}
+
+// The order a keyword says to read the columns in: the column under the alphabetically first
+// letter goes first, and repeated letters go left to right
+pub fn column_order(keyword: &[u8]) -> Vec<usize> {
+    let mut order = (0..keyword.len()).collect::<Vec<usize>>();
+    order.sort_by_key(|&i| keyword[i].to_ascii_lowercase());
+
+    order
+}
+
+fn check_order(order: &[usize]) -> Result<(), ClassicalError> {
+    let mut sorted = order.to_vec();
+    sorted.sort();
+    if sorted.iter().enumerate().all(|(i, &col)| i == col) {
+        Ok(())
+    } else {
+        Err(ClassicalError::BadColumnOrder)
+    }
+}
+
+// Writes the text in rows as wide as the key, then reads it off a column at a time in the given
+// order. The last row can be short. Every byte is transposed, not just the letters. An empty
+// order leaves the text alone
+pub fn columnar_encrypt(text: &[u8], order: &[usize]) -> Result<Vec<u8>, ClassicalError> {
+    check_order(order)?;
+    Ok(transpose(text, order))
+}
+
+pub fn columnar_decrypt(text: &[u8], order: &[usize]) -> Result<Vec<u8>, ClassicalError> {
+    check_order(order)?;
+    Ok(untranspose(text, order))
+}
+
+// columnar_encrypt and columnar_decrypt, for orders known to be good
+fn transpose(text: &[u8], order: &[usize]) -> Vec<u8> {
+    let width = order.len();
+    if width == 0 {
+        return text.to_vec();
+    }
+    let mut out = Vec::with_capacity(text.len());
+    for &col in order {
+        out.extend(text.iter().skip(col).step_by(width));
+    }
+
+    out
+}
+
+fn untranspose(text: &[u8], order: &[usize]) -> Vec<u8> {
+    let width = order.len();
+    if width == 0 {
+        return text.to_vec();
+    }
+    let (n_rows, n_long) = (text.len() / width, text.len() % width);
+    let mut out = vec![0u8; text.len()];
+    let mut pos = 0;
+    for &col in order {
+        let col_len = n_rows + if col < n_long { 1 } else { 0 };
+        for (row, &b) in text[pos..pos+col_len].iter().enumerate() {
+            out[row*width + col] = b;
+        }
+        pos += col_len;
+    }
+
+    out
+}
+
+pub fn break_caesar<S: Scorer + ?Sized>(ciphertext: &[u8], scorer: &S) -> ClassicalSolution<u8> {
+    (0..26u8).map(|shift| {
+        let plaintext = caesar_decrypt(ciphertext, shift);
+        ClassicalSolution {
+            key: shift,
+            score: scorer.score(&plaintext),
+            plaintext: plaintext,
+        }
+    }).min_by(|a, b| cmp_scores(a.score, b.score)).unwrap()
+}
+
+// Index of coincidence of the letters, scaled by 26 so that random letters come out near 1 and
+// English near 1.7. 0 if there are fewer than two letters
+pub fn letter_ioc(text: &[u8]) -> f64 {
+    let mut counts = [0u64; 26];
+    let mut n = 0u64;
+    for idx in text.iter().filter_map(|&b| letter_index(b)) {
+        counts[idx as usize] += 1;
+        n += 1;
+    }
+    if n < 2 {
+        return 0f64;
+    }
+    let pairs = counts.iter().fold(0u64, |acc, &c| acc + c*c.saturating_sub(1));
+
+    26f64 * (pairs as f64) / ((n * (n-1)) as f64)
+}
+
+// Guesses the key length of a periodic polyalphabetic cipher. Each column of the right length is
+// a single alphabet with English's lopsided letter counts. So are the columns of any multiple of
+// it, which is why this takes the shortest length that gets close to the best average IoC. Every
+// text has a period of 1, so that's the answer when max_len doesn't allow anything
+pub fn guess_period(ciphertext: &[u8], max_len: usize) -> usize {
+    let max_len = max_len.max(1);
+    let letters = ciphertext.iter().cloned().filter(|&b| letter_index(b).is_some())
+                            .collect::<Vec<u8>>();
+    let iocs = (1..(max_len+1)).map(|len| {
+        let total = (0..len).map(|col| {
+            letter_ioc(&letters.iter().skip(col).step_by(len).cloned().collect::<Vec<u8>>())
+        }).sum::<f64>();
+        total / len as f64
+    }).collect::<Vec<f64>>();
+    let best = iocs.iter().cloned().fold(0f64, f64::max);
+
+    iocs.iter().position(|&ioc| ioc >= 0.9 * best).unwrap() + 1
+}
+
+// Finds the period, then each key letter on its own by scoring the letters it enciphers. Since a
+// column isn't contiguous text, the scorer should only look at letter frequencies. decrypt works
+// on letter indices, like map_letters
+fn break_periodic<S, F>(ciphertext: &[u8], max_key_len: usize, scorer: &S,
+                        decrypt: F) -> ClassicalSolution<Vec<u8>>
+        where S: Scorer + ?Sized, F: Fn(u8, u8) -> u8 {
+    let period = guess_period(ciphertext, max_key_len);
+    let letters = ciphertext.iter().cloned().filter(|&b| letter_index(b).is_some())
+                            .collect::<Vec<u8>>();
+    let key = (0..period).map(|col| {
+        let column = letters.iter().skip(col).step_by(period).cloned().collect::<Vec<u8>>();
+        let scores = (0..26u8).map(|k| scorer.score(&map_letters(&column, &[k], &decrypt)))
+                              .collect::<Vec<f64>>();
+        (0..26u8).min_by(|&a, &b| cmp_scores(scores[a as usize], scores[b as usize])).unwrap()
+    }).collect::<Vec<u8>>();
+    let plaintext = map_letters(ciphertext, &key, &decrypt);
+
+    ClassicalSolution {
+        key: key.iter().map(|&k| b'a' + k).collect(),
+        score: scorer.score(&plaintext),
+        plaintext: plaintext,
+    }
+}
+
+pub fn break_vigenere<S: Scorer + ?Sized>(ciphertext: &[u8], max_key_len: usize,
+                                          scorer: &S) -> ClassicalSolution<Vec<u8>> {
+    break_periodic(ciphertext, max_key_len, scorer, sub_letters)
+}
+
+pub fn break_beaufort<S: Scorer + ?Sized>(ciphertext: &[u8], max_key_len: usize,
+                                          scorer: &S) -> ClassicalSolution<Vec<u8>> {
+    break_periodic(ciphertext, max_key_len, scorer, beaufort_letters)
+}
+
+// Swaps pairs of entries in key for as long as some swap improves the score. Returns the score
+// of the local optimum it ends up at
+fn climb<T, F: Fn(&[T]) -> f64>(key: &mut [T], score: F) -> f64 {
+    let mut best = score(key);
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 0..key.len() {
+            for j in (i+1)..key.len() {
+                key.swap(i, j);
+                let s = score(key);
+                if cmp_scores(s, best) == Ordering::Less {
+                    best = s;
+                    improved = true;
+                } else {
+                    key.swap(i, j);
+                }
+            }
+        }
+    }
+
+    best
+}
+
+// Hill-climbs over swaps of two letters, starting from the key that matches up letter
+// frequencies and then from shakeups of the best key so far. The scorer has to look at more than
+// single letters, since every key has the same letter frequencies up to relabeling; the
+// quadgram model works well
+pub fn break_substitution<S: Scorer + ?Sized>(ciphertext: &[u8],
+                                              scorer: &S) -> ClassicalSolution<[u8; 26]> {
+    let mut counts = [0usize; 26];
+    for idx in ciphertext.iter().filter_map(|&b| letter_index(b)) {
+        counts[idx as usize] += 1;
+    }
+    let mut by_freq = (0..26).collect::<Vec<usize>>();
+    by_freq.sort_by(|&a, &b| counts[b].cmp(&counts[a]));
+    // decrypt_key[c] is the index of the plaintext letter for ciphertext letter c
+    let mut decrypt_key = [0u8; 26];
+    for (rank, &c) in by_freq.iter().enumerate() {
+        decrypt_key[c] = ENGLISH_ORDER[rank] - b'a';
+    }
+
+    let score = |key: &[u8]| scorer.score(&substitute_indices(ciphertext, key));
+    let mut rng = seeded_rng();
+    let mut best_score = climb(&mut decrypt_key, &score);
+    let mut best_key = decrypt_key;
+    for _ in 0..CLIMB_RESTARTS {
+        let mut key = best_key;
+        for _ in 0..5 {
+            key.swap(rng.gen_range(0, 26), rng.gen_range(0, 26));
+        }
+        let s = climb(&mut key, &score);
+        if cmp_scores(s, best_score) == Ordering::Less {
+            best_score = s;
+            best_key = key;
+        }
+    }
+
+    let mut key = [0u8; 26];
+    for (c, &p) in best_key.iter().enumerate() {
+        key[p as usize] = b'a' + c as u8;
+    }
+    ClassicalSolution {
+        key: key,
+        plaintext: substitute_indices(ciphertext, &best_key),
+        score: best_score,
+    }
+}
+
+// Calls f on every permutation of items, by Heap's algorithm
+fn for_each_permutation<F: FnMut(&[usize])>(items: &mut [usize], k: usize, f: &mut F) {
+    if k <= 1 {
+        f(items);
+        return;
+    }
+    for i in 0..(k-1) {
+        for_each_permutation(items, k-1, f);
+        if k % 2 == 0 { items.swap(i, k-1); } else { items.swap(0, k-1); }
+    }
+    for_each_permutation(items, k-1, f);
+}
+
+// The renumbering of order's columns that scores best. Adding the same amount to every column
+// number reads almost the same as the right order, shifted by a few characters, and swaps can't
+// get from one to the other
+fn best_renumbering<F: Fn(&[usize]) -> f64>(order: &[usize], score: &F) -> (Vec<usize>, f64) {
+    let width = order.len();
+    (0..width).map(|shift| {
+        let shifted = order.iter().map(|&c| (c + shift) % width).collect::<Vec<usize>>();
+        let s = score(&shifted);
+        (shifted, s)
+    }).min_by(|a, b| cmp_scores(a.1, b.1)).unwrap()
+}
+
+// Tries every width from 2 to max_width. Narrow ones get every column order, and wider ones are
+// hill-climbed over swaps of two columns from random starts, with each order scored by its best
+// renumbering. As with substitution, the scorer has to look at n-grams, since transposing doesn't
+// change letter frequencies.
+//
+// When the last row is short, the ciphertext can decrypt under two renumberings of the key: to
+// the plaintext, and to the plaintext rotated by a character. Both fit the column lengths, so only
+// the ends tell them apart, which is why candidates are scored with whitespace either side, as if
+// they stood on their own. A rotation that only moves whitespace around scores the same, and then
+// the one that doesn't start with whitespace wins
+pub fn break_columnar<S: Scorer + ?Sized>(ciphertext: &[u8], max_width: usize, scorer: &S)
+                                          -> Result<ClassicalSolution<Vec<usize>>, ClassicalError> {
+    if max_width.min(ciphertext.len()) < 2 {
+        return Err(ClassicalError::NoWidths { max_width: max_width, len: ciphertext.len() });
+    }
+    let score = |order: &[usize]| {
+        let mut framed = vec![b' '];
+        framed.extend(untranspose(ciphertext, order));
+        framed.push(b' ');
+        scorer.score(&framed)
+    };
+    let renumbered_score = |order: &[usize]| best_renumbering(order, &score).1;
+    let starts_with_space = |order: &[usize]| {
+        untranspose(ciphertext, order).first().map_or(false, |b| b.is_ascii_whitespace())
+    };
+    let mut rng = seeded_rng();
+    let mut best: Option<(Vec<usize>, f64)> = None;
+    {
+        let mut consider = |order: &[usize], s: f64| {
+            let better = match best {
+                Some((ref o, b)) => match cmp_scores(s, b) {
+                    Ordering::Less => true,
+                    Ordering::Equal => starts_with_space(o) && !starts_with_space(order),
+                    Ordering::Greater => false,
+                },
+                None => true,
+            };
+            if better {
+                best = Some((order.to_vec(), s));
+            }
+        };
+
+        for width in 2..(max_width.min(ciphertext.len())+1) {
+            let mut order = (0..width).collect::<Vec<usize>>();
+            if width <= MAX_BRUTE_FORCE_WIDTH {
+                for_each_permutation(&mut order, width, &mut |o: &[usize]| consider(o, score(o)));
+            } else {
+                for _ in 0..CLIMB_RESTARTS {
+                    for i in (1..width).rev() {
+                        order.swap(i, rng.gen_range(0, i+1));
+                    }
+                    climb(&mut order, &renumbered_score);
+                    for shift in 0..width {
+                        let shifted = order.iter().map(|&c| (c + shift) % width)
+                                           .collect::<Vec<usize>>();
+                        consider(&shifted, score(&shifted));
+                    }
+                }
+            }
+        }
+    }
+
+    // There was at least one width, so something got considered
+    let order = best.unwrap().0;
+    let plaintext = untranspose(ciphertext, &order);
+    Ok(ClassicalSolution {
+        score: scorer.score(&plaintext),
+        plaintext: plaintext,
+        key: order,
+    })
+}
+
+#[test]
+fn tst_classical() {
+    use c06::{decode_b64, dump_file};
+    use scoring::NgramModel;
+
+    // The plaintext of challenge 6 makes a handy chunk of English
+    let b64: String = dump_file("c06.txt").split_whitespace().collect();
+    let key = b"Terminator X: Bring the noise";
+    let lyrics = decode_b64(&b64).iter().zip(key.iter().cycle()).map(|(&c, &k)| c ^ k)
+                                 .collect::<Vec<u8>>();
+    let text = &lyrics[..600];
+    let quadgrams = NgramModel::english();
+    let unigrams = quadgrams.marginal(1);
+
+    // Known answers
+    assert_eq!(caesar_encrypt(b"Hello, World!", 3), b"Khoor, Zruog!");
+    assert_eq!(vigenere_encrypt(b"ATTACK AT DAWN", b"LEMON").unwrap(), b"LXFOPV EF RNHR");
+    assert_eq!(beaufort(b"DEFENDTHEEASTWALLOFTHECASTLE", b"FORTIFICATION").unwrap(),
+               b"CKMPVCPVWPIWUJOGIUAPVWRIWUUK");
+    assert_eq!(column_order(b"ZEBRAS"), vec![4, 2, 1, 3, 5, 0]);
+    assert_eq!(columnar_encrypt(b"WEAREDISCOVEREDFLEEATONCE", &column_order(b"ZEBRAS")).unwrap(),
+               b"EVLNACDTESEAROFODEECWIREE");
+
+    // Round trips
+    let transposed = |text: &[u8], keyword: &[u8]| {
+        columnar_encrypt(text, &column_order(keyword)).unwrap()
+    };
+    let order = column_order(b"CRYPTOGRAPHY");
+    assert_eq!(columnar_decrypt(&transposed(text, b"CRYPTOGRAPHY"), &order).unwrap(), text);
+    assert_eq!(caesar_decrypt(&caesar_encrypt(text, 29), 29), text);
+    let ct = vigenere_encrypt(text, b"Ice").unwrap();
+    assert_eq!(vigenere_decrypt(&ct, b"ICE").unwrap(), text);
+    assert_eq!(beaufort(&beaufort(text, b"vanilla").unwrap(), b"vanilla").unwrap(), text);
+    let sub_key = *b"qwertyuiopasdfghjklzxcvbnm";
+    let ct = substitute(text, &sub_key).unwrap();
+    assert_eq!(substitute(&ct, &invert_substitution(&sub_key).unwrap()).unwrap(), text);
+
+    // Breakers
+    assert_eq!(break_caesar(&caesar_encrypt(text, 13), &unigrams).key, 13);
+    let ct = vigenere_encrypt(text, b"vanillaice").unwrap();
+    let solved = break_vigenere(&ct, 20, &unigrams);
+    assert_eq!(solved.key, b"vanillaice");
+    assert_eq!(solved.plaintext, text);
+    let ct = beaufort(text, b"rapper").unwrap();
+    assert_eq!(break_beaufort(&ct, 20, &unigrams).key, b"rapper");
+    let solved = break_substitution(&substitute(text, &sub_key).unwrap(), &quadgrams);
+    assert_eq!(solved.key, sub_key);
+    assert_eq!(solved.plaintext, text);
+    let solved = break_columnar(&transposed(text, b"ZEBRAS"), 8, &quadgrams).unwrap();
+    assert_eq!(solved.key, column_order(b"ZEBRAS"));
+    // 600 letters leave a short last row of 5 here, and 4 in the one after
+    let solved = break_columnar(&transposed(text, b"HOTDOGS"), 8, &quadgrams).unwrap();
+    assert_eq!(solved.key, column_order(b"HOTDOGS"));
+    assert_eq!(solved.plaintext, text);
+    let short = &lyrics[..598];
+    let solved = break_columnar(&transposed(short, b"ZEBRAS"), 6, &quadgrams).unwrap();
+    assert_eq!(solved.plaintext, short);
+
+    // Keys and lengths with nothing to them
+    assert_eq!(columnar_encrypt(b"attack", &[]).unwrap(), b"attack");
+    assert_eq!(columnar_decrypt(b"attack", &[]).unwrap(), b"attack");
+    assert_eq!(guess_period(b"attack at dawn", 0), 1);
+
+    // Keys and lengths that don't work
+    assert_eq!(vigenere_encrypt(b"attack", b""), Err(ClassicalError::EmptyKey));
+    assert_eq!(beaufort(b"attack", b"key 2"), Err(ClassicalError::NotALetter { byte: b' ' }));
+    let mut bad_key = sub_key;
+    bad_key[0] = b'w';
+    assert_eq!(invert_substitution(&bad_key),
+               Err(ClassicalError::NotAPermutation { missing: b'q' }));
+    bad_key[1] = b'!';
+    assert_eq!(substitute(b"attack", &bad_key), Err(ClassicalError::NotALetter { byte: b'!' }));
+    assert_eq!(columnar_encrypt(b"attack", &[0, 2]), Err(ClassicalError::BadColumnOrder));
+    assert_eq!(columnar_decrypt(b"attack", &[1, 1]), Err(ClassicalError::BadColumnOrder));
+    assert_eq!(break_columnar(b"attack", 1, &quadgrams).err(),
+               Some(ClassicalError::NoWidths { max_width: 1, len: 6 }));
+    assert_eq!(break_columnar(b"a", 8, &quadgrams).err(),
+               Some(ClassicalError::NoWidths { max_width: 8, len: 1 }));
+}
@@ -0,0 +1,618 @@
+use std::cmp::min;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+const HEX_LOWER: &'static [u8] = b"0123456789abcdef";
+const HEX_UPPER: &'static [u8] = b"0123456789ABCDEF";
+const B64_STD: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const B64_URL: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const B32: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// How many bytes of encoded input a streaming Decoder pulls from its reader at a time
+const READ_CHUNK_SIZE: usize = 4096;
+
+// Every error carries the offset into the encoded input where it was noticed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodecError {
+    // A character that isn't in the encoding's alphabet
+    InvalidSymbol { pos: usize, symbol: u8 },
+    // Misplaced or excess padding, or data after the end of the encoded stream
+    InvalidPadding { pos: usize },
+    // The input stopped partway through a group that can't be decoded on its own
+    Truncated { pos: usize },
+    // An ascii85 group whose value doesn't fit in 32 bits
+    Overflow { pos: usize },
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CodecError::InvalidSymbol { pos, symbol } =>
+                write!(f, "invalid symbol {:?} at offset {}", symbol as char, pos),
+            CodecError::InvalidPadding { pos } => write!(f, "invalid padding at offset {}", pos),
+            CodecError::Truncated { pos } => write!(f, "input truncated at offset {}", pos),
+            CodecError::Overflow { pos } => write!(f, "ascii85 group overflows at offset {}", pos),
+        }
+    }
+}
+
+impl Error for CodecError { }
+
+impl From<CodecError> for io::Error {
+    fn from(err: CodecError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+// Hex decoding accepts both cases no matter which variant is used; the variant only picks the
+// case of the encoder's output. Likewise, every base64 and base32 variant decodes padded and
+// unpadded input alike; the NoPad variants just don't emit '=' when encoding. ASCII whitespace is
+// skipped everywhere, so line-wrapped dumps decode as-is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Hex,
+    HexUpper,
+    Base64,
+    Base64NoPad,
+    Base64Url,
+    Base64UrlNoPad,
+    Base32,
+    // The btoa/Adobe flavor: 'z' abbreviates a zero group and the decoder strips "<~" and "~>"
+    Ascii85,
+}
+
+impl Encoding {
+    pub fn encode(&self, input: &[u8]) -> String {
+        let mut enc = EncodeState::new(*self);
+        let mut out = String::new();
+        enc.feed(input, &mut out);
+        enc.finish(&mut out);
+
+        out
+    }
+
+    // Takes a &str, String, &[u8], etc.
+    pub fn decode<T: AsRef<[u8]>>(&self, input: T) -> Result<Vec<u8>, CodecError> {
+        let mut dec = DecodeState::new(*self);
+        let mut out = Vec::new();
+        dec.feed(input.as_ref(), &mut out)?;
+        dec.finish(&mut out)?;
+
+        Ok(out)
+    }
+
+    // Number of raw bytes in a full group
+    fn bytes_per_group(&self) -> usize {
+        match *self {
+            Encoding::Hex | Encoding::HexUpper => 1,
+            Encoding::Base64 | Encoding::Base64NoPad
+          | Encoding::Base64Url | Encoding::Base64UrlNoPad => 3,
+            Encoding::Base32 => 5,
+            Encoding::Ascii85 => 4,
+        }
+    }
+
+    // Number of encoded symbols in a full group
+    fn symbols_per_group(&self) -> usize {
+        match *self {
+            Encoding::Hex | Encoding::HexUpper => 2,
+            Encoding::Base64 | Encoding::Base64NoPad
+          | Encoding::Base64Url | Encoding::Base64UrlNoPad => 4,
+            Encoding::Base32 => 8,
+            Encoding::Ascii85 => 5,
+        }
+    }
+
+    // Bits per symbol for the power-of-two alphabets. Ascii85 is base 85, so it has no entry
+    fn bits_per_symbol(&self) -> u32 {
+        match *self {
+            Encoding::Hex | Encoding::HexUpper => 4,
+            Encoding::Base64 | Encoding::Base64NoPad
+          | Encoding::Base64Url | Encoding::Base64UrlNoPad => 6,
+            Encoding::Base32 => 5,
+            Encoding::Ascii85 => unreachable!(),
+        }
+    }
+
+    fn alphabet(&self) -> &'static [u8] {
+        match *self {
+            Encoding::Hex => HEX_LOWER,
+            Encoding::HexUpper => HEX_UPPER,
+            Encoding::Base64 | Encoding::Base64NoPad => B64_STD,
+            Encoding::Base64Url | Encoding::Base64UrlNoPad => B64_URL,
+            Encoding::Base32 => B32,
+            Encoding::Ascii85 => unreachable!(),
+        }
+    }
+
+    // Whether '=' may appear in the input, and whether the encoder writes it
+    fn accepts_padding(&self) -> bool {
+        match *self {
+            Encoding::Hex | Encoding::HexUpper | Encoding::Ascii85 => false,
+            _ => true,
+        }
+    }
+
+    fn emits_padding(&self) -> bool {
+        match *self {
+            Encoding::Base64 | Encoding::Base64Url | Encoding::Base32 => true,
+            _ => false,
+        }
+    }
+
+    fn symbol_value(&self, c: u8) -> Option<u8> {
+        match *self {
+            Encoding::Hex | Encoding::HexUpper => match c {
+                b'0'...b'9' => Some(c - b'0'),
+                b'a'...b'f' => Some(c - b'a' + 10),
+                b'A'...b'F' => Some(c - b'A' + 10),
+                          _ => None
+            },
+            // Base32 is case-insensitive in practice
+            Encoding::Base32 => match c {
+                b'A'...b'Z' => Some(c - b'A'),
+                b'a'...b'z' => Some(c - b'a'),
+                b'2'...b'7' => Some(c - b'2' + 26),
+                          _ => None
+            },
+            Encoding::Ascii85 => match c {
+                b'!'...b'u' => Some(c - b'!'),
+                          _ => None
+            },
+            _ => self.alphabet().iter().position(|&a| a == c).map(|i| i as u8),
+        }
+    }
+
+    // How many bytes a final group of n_symbols symbols decodes to, or None if no byte string
+    // encodes to exactly that many symbols
+    fn partial_group_len(&self, n_symbols: usize) -> Option<usize> {
+        let n_bytes = match *self {
+            Encoding::Ascii85 => n_symbols.saturating_sub(1),
+            _ => n_symbols * (self.bits_per_symbol() as usize) / 8,
+        };
+        if n_bytes > 0 && self.partial_symbol_count(n_bytes) == n_symbols {
+            Some(n_bytes)
+        } else {
+            None
+        }
+    }
+
+    // How many symbols a final group of n_bytes bytes encodes to
+    fn partial_symbol_count(&self, n_bytes: usize) -> usize {
+        match *self {
+            Encoding::Ascii85 => n_bytes + 1,
+            _ => {
+                let bits = self.bits_per_symbol() as usize;
+                (8*n_bytes + bits - 1) / bits
+            }
+        }
+    }
+}
+
+fn is_whitespace(c: u8) -> bool {
+    match c {
+        b' ' | b'\t' | b'\n' | b'\r' | b'\x0b' | b'\x0c' => true,
+                                                       _ => false
+    }
+}
+
+// Incremental encoder; keeps at most one partial group of input between feeds
+struct EncodeState {
+    encoding: Encoding,
+    group: [u8; 5],
+    len: usize,
+}
+
+impl EncodeState {
+    fn new(encoding: Encoding) -> EncodeState {
+        EncodeState {
+            encoding: encoding,
+            group: [0u8; 5],
+            len: 0,
+        }
+    }
+
+    fn feed(&mut self, input: &[u8], out: &mut String) {
+        let group_size = self.encoding.bytes_per_group();
+        for &byte in input {
+            self.group[self.len] = byte;
+            self.len += 1;
+            if self.len == group_size {
+                self.flush_group(out);
+            }
+        }
+    }
+
+    fn finish(&mut self, out: &mut String) {
+        if self.len > 0 {
+            self.flush_group(out);
+        }
+    }
+
+    fn flush_group(&mut self, out: &mut String) {
+        let enc = self.encoding;
+        let group_size = enc.bytes_per_group();
+        let n_symbols = enc.symbols_per_group();
+
+        // Missing bytes of a partial group are treated as 0
+        for b in &mut self.group[self.len..group_size] {
+            *b = 0;
+        }
+        let val = self.group[..group_size].iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+
+        let mut symbols = [0u8; 8];
+        if enc == Encoding::Ascii85 {
+            // An all-zero full group is abbreviated as 'z'
+            if self.len == group_size && val == 0 {
+                out.push('z');
+                self.len = 0;
+                return;
+            }
+            let mut v = val;
+            for i in (0..n_symbols).rev() {
+                symbols[i] = b'!' + (v % 85) as u8;
+                v /= 85;
+            }
+        } else {
+            let bits = enc.bits_per_symbol();
+            let mask = (1u64 << bits) - 1;
+            let alphabet = enc.alphabet();
+            for i in 0..n_symbols {
+                let shift = bits * ((n_symbols - 1 - i) as u32);
+                symbols[i] = alphabet[((val >> shift) & mask) as usize];
+            }
+        }
+
+        let n_out = if self.len == group_size { n_symbols }
+                    else { enc.partial_symbol_count(self.len) };
+        for &s in &symbols[..n_out] {
+            out.push(s as char);
+        }
+        if enc.emits_padding() {
+            for _ in n_out..n_symbols {
+                out.push('=');
+            }
+        }
+
+        self.len = 0;
+    }
+}
+
+// Incremental decoder; keeps at most one partial group of symbol values between feeds
+struct DecodeState {
+    encoding: Encoding,
+    group: [u8; 8],
+    len: usize,
+    // Number of '=' seen in the current group
+    pads: usize,
+    // Offset of the next input byte
+    pos: usize,
+    // Number of non-whitespace bytes seen, and the last of them
+    seen: usize,
+    prev: u8,
+    // Set once the encoded stream has ended (full padding or an ascii85 "~>")
+    done: bool,
+}
+
+impl DecodeState {
+    fn new(encoding: Encoding) -> DecodeState {
+        DecodeState {
+            encoding: encoding,
+            group: [0u8; 8],
+            len: 0,
+            pads: 0,
+            pos: 0,
+            seen: 0,
+            prev: 0,
+            done: false,
+        }
+    }
+
+    fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<(), CodecError> {
+        for &c in input {
+            let pos = self.pos;
+            self.pos += 1;
+            if is_whitespace(c) {
+                continue;
+            }
+            let prev = self.prev;
+            self.prev = c;
+            self.seen += 1;
+
+            if self.encoding == Encoding::Ascii85 {
+                self.feed_ascii85(c, prev, pos, out)?;
+            } else {
+                self.feed_radix(c, pos, out)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn feed_radix(&mut self, c: u8, pos: usize, out: &mut Vec<u8>) -> Result<(), CodecError> {
+        let n_symbols = self.encoding.symbols_per_group();
+        if self.done {
+            return Err(CodecError::InvalidPadding { pos: pos });
+        }
+
+        if c == b'=' && self.encoding.accepts_padding() {
+            if self.encoding.partial_group_len(self.len).is_none() {
+                return Err(CodecError::InvalidPadding { pos: pos });
+            }
+            self.pads += 1;
+            if self.len + self.pads == n_symbols {
+                self.flush_group(out);
+                self.done = true;
+            }
+            return Ok(());
+        }
+
+        let val = match self.encoding.symbol_value(c) {
+            Some(v) => v,
+            None => return Err(CodecError::InvalidSymbol { pos: pos, symbol: c }),
+        };
+        // Symbols can't follow padding
+        if self.pads > 0 {
+            return Err(CodecError::InvalidPadding { pos: pos });
+        }
+        self.group[self.len] = val;
+        self.len += 1;
+        if self.len == n_symbols {
+            self.flush_group(out);
+        }
+
+        Ok(())
+    }
+
+    fn feed_ascii85(&mut self, c: u8, prev: u8, pos: usize,
+                    out: &mut Vec<u8>) -> Result<(), CodecError> {
+        if self.done {
+            // Allow the '>' of the "~>" terminator, and nothing else
+            if c == b'>' && prev == b'~' {
+                return Ok(());
+            }
+            return Err(CodecError::InvalidPadding { pos: pos });
+        }
+
+        if c == b'~' {
+            // A leading "<~" is a delimiter, not data
+            if self.seen == 2 && prev == b'<' {
+                self.len = 0;
+                return Ok(());
+            }
+            self.done = true;
+            return self.finish(out);
+        }
+
+        if c == b'z' {
+            if self.len != 0 {
+                return Err(CodecError::InvalidSymbol { pos: pos, symbol: c });
+            }
+            out.extend_from_slice(&[0u8; 4]);
+            return Ok(());
+        }
+
+        let val = match self.encoding.symbol_value(c) {
+            Some(v) => v,
+            None => return Err(CodecError::InvalidSymbol { pos: pos, symbol: c }),
+        };
+        self.group[self.len] = val;
+        self.len += 1;
+        if self.len == 5 {
+            self.flush_ascii85_group(pos, out)?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<(), CodecError> {
+        let pos = self.pos;
+        if self.pads > 0 && !self.done {
+            return Err(CodecError::InvalidPadding { pos: pos });
+        }
+        if self.len > 0 {
+            if self.encoding.partial_group_len(self.len).is_none() {
+                return Err(CodecError::Truncated { pos: pos });
+            }
+            if self.encoding == Encoding::Ascii85 {
+                self.flush_ascii85_group(pos, out)?;
+            } else {
+                self.flush_group(out);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Decodes the current (possibly partial, already validated) group of a power-of-two alphabet
+    fn flush_group(&mut self, out: &mut Vec<u8>) {
+        let enc = self.encoding;
+        let bits = enc.bits_per_symbol();
+        let n_symbols = enc.symbols_per_group();
+        let group_size = enc.bytes_per_group();
+
+        let n_bytes = if self.len == n_symbols { group_size }
+                      else { enc.partial_group_len(self.len).unwrap() };
+        let val = (0..n_symbols).fold(0u64, |acc, i| {
+            let s = if i < self.len { self.group[i] } else { 0 };
+            (acc << bits) | s as u64
+        });
+        for i in 0..n_bytes {
+            out.push((val >> (8 * (group_size - 1 - i))) as u8);
+        }
+
+        self.len = 0;
+    }
+
+    // Decodes the current (possibly partial) ascii85 group. Partial groups are padded with 'u'
+    fn flush_ascii85_group(&mut self, pos: usize, out: &mut Vec<u8>) -> Result<(), CodecError> {
+        let n_bytes = if self.len == 5 { 4 } else { self.len - 1 };
+        let val = (0..5).fold(0u64, |acc, i| {
+            let d = if i < self.len { self.group[i] } else { 84 };
+            acc * 85 + d as u64
+        });
+        if val > 0xffffffffu64 {
+            return Err(CodecError::Overflow { pos: pos });
+        }
+        for i in 0..n_bytes {
+            out.push((val >> (8 * (3 - i))) as u8);
+        }
+
+        self.len = 0;
+        Ok(())
+    }
+}
+
+// Wraps a reader of encoded data and reads out the decoded bytes. Decoding errors come back as
+// io::Errors of kind InvalidData wrapping a CodecError
+pub struct Decoder<R: Read> {
+    inner: R,
+    state: DecodeState,
+    // Decoded bytes that haven't been read out yet
+    buf: Vec<u8>,
+    buf_pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(inner: R, encoding: Encoding) -> Decoder<R> {
+        Decoder {
+            inner: inner,
+            state: DecodeState::new(encoding),
+            buf: Vec::new(),
+            buf_pos: 0,
+            eof: false,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.buf_pos == self.buf.len() && !self.eof {
+            self.buf.clear();
+            self.buf_pos = 0;
+
+            let mut chunk = [0u8; READ_CHUNK_SIZE];
+            let n_read = self.inner.read(&mut chunk)?;
+            if n_read == 0 {
+                self.eof = true;
+                self.state.finish(&mut self.buf)?;
+            } else {
+                self.state.feed(&chunk[..n_read], &mut self.buf)?;
+            }
+        }
+
+        let n = min(out.len(), self.buf.len() - self.buf_pos);
+        out[..n].copy_from_slice(&self.buf[self.buf_pos..self.buf_pos+n]);
+        self.buf_pos += n;
+
+        Ok(n)
+    }
+}
+
+// Wraps a writer and writes the encoding of everything written to it. The final partial group
+// (and any padding) is only written by finish(), so don't just drop this
+pub struct Encoder<W: Write> {
+    inner: W,
+    state: EncodeState,
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(inner: W, encoding: Encoding) -> Encoder<W> {
+        Encoder {
+            inner: inner,
+            state: EncodeState::new(encoding),
+        }
+    }
+
+    // Writes out the last group and returns the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut out = String::new();
+        self.state.finish(&mut out);
+        self.inner.write_all(out.as_bytes())?;
+        self.inner.flush()?;
+
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, input: &[u8]) -> io::Result<usize> {
+        let mut out = String::new();
+        self.state.feed(input, &mut out);
+        self.inner.write_all(out.as_bytes())?;
+
+        Ok(input.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn tst_codec() {
+    // RFC 4648 test vectors
+    let plain: [&[u8]; 7] = [b"", b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar"];
+    let b64 = ["", "Zg==", "Zm8=", "Zm9v", "Zm9vYg==", "Zm9vYmE=", "Zm9vYmFy"];
+    let b32 = ["", "MY======", "MZXQ====", "MZXW6===", "MZXW6YQ=", "MZXW6YTB",
+               "MZXW6YTBOI======"];
+    let a85 = ["", "Ac", "Ao@", "AoDS", "AoDTs", "AoDTs@/", "AoDTs@<)"];
+    for i in 0..plain.len() {
+        assert_eq!(Encoding::Base64.encode(plain[i]), b64[i]);
+        assert_eq!(Encoding::Base64NoPad.encode(plain[i]), b64[i].trim_right_matches('='));
+        assert_eq!(Encoding::Base32.encode(plain[i]), b32[i]);
+        assert_eq!(Encoding::Ascii85.encode(plain[i]), a85[i]);
+
+        assert_eq!(Encoding::Base64.decode(b64[i]).unwrap(), plain[i]);
+        assert_eq!(Encoding::Base64.decode(b64[i].trim_right_matches('=')).unwrap(), plain[i]);
+        assert_eq!(Encoding::Base32.decode(b32[i]).unwrap(), plain[i]);
+        assert_eq!(Encoding::Base32.decode(b32[i].to_lowercase()).unwrap(), plain[i]);
+        assert_eq!(Encoding::Ascii85.decode(a85[i]).unwrap(), plain[i]);
+    }
+
+    assert_eq!(Encoding::Hex.encode(b"\xde\xad\xbe\xef"), "deadbeef");
+    assert_eq!(Encoding::HexUpper.encode(b"\xde\xad\xbe\xef"), "DEADBEEF");
+    assert_eq!(Encoding::Hex.decode("DeAd bEeF\n").unwrap(), b"\xde\xad\xbe\xef");
+    assert_eq!(Encoding::Base64Url.encode(b"\xfb\xff\xbf\xfe"), "-_-__g==");
+    assert_eq!(Encoding::Base64UrlNoPad.decode("-_-__g").unwrap(), b"\xfb\xff\xbf\xfe");
+    assert_eq!(Encoding::Ascii85.encode(b"\0\0\0\0hello\xff\xff\xff\xff"), "zBOu!rDu]k;rr");
+    assert_eq!(Encoding::Ascii85.decode("<~9jqo^BlbD-BleB1DJ+*+F(f,q~>").unwrap(),
+               b"Man is distinguished");
+    assert_eq!(Encoding::Base64.decode("Zm9v\nYmFy\n").unwrap(), b"foobar");
+
+    // Malformed input gives a typed error instead of a panic
+    assert_eq!(Encoding::Hex.decode("abc"), Err(CodecError::Truncated { pos: 3 }));
+    assert_eq!(Encoding::Hex.decode("0g"), Err(CodecError::InvalidSymbol { pos: 1, symbol: b'g' }));
+    assert_eq!(Encoding::Base64.decode("Zm9v-w=="),
+               Err(CodecError::InvalidSymbol { pos: 4, symbol: b'-' }));
+    assert_eq!(Encoding::Base64.decode("Zg==Zg=="), Err(CodecError::InvalidPadding { pos: 4 }));
+    assert_eq!(Encoding::Base64.decode("Z==="), Err(CodecError::InvalidPadding { pos: 1 }));
+    assert_eq!(Encoding::Base64.decode("Zg="), Err(CodecError::InvalidPadding { pos: 3 }));
+    assert_eq!(Encoding::Ascii85.decode("s8W-\""), Err(CodecError::Overflow { pos: 4 }));
+
+    // Streaming adapters agree with the one-shot functions
+    let data = (0..1000u32).map(|i| (i * 7 + 3) as u8).collect::<Vec<u8>>();
+    for &enc in &[Encoding::Hex, Encoding::Base64, Encoding::Base64UrlNoPad, Encoding::Base32,
+                  Encoding::Ascii85] {
+        let mut encoder = Encoder::new(Vec::new(), enc);
+        for chunk in data.chunks(7) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let encoded = encoder.finish().unwrap();
+        assert_eq!(encoded, enc.encode(&data).into_bytes());
+
+        let mut decoded = Vec::new();
+        Decoder::new(&*encoded, enc).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    let mut sink = Vec::new();
+    let err = Decoder::new(&b"Zm9v!"[..], Encoding::Base64).read_to_end(&mut sink).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,376 @@
+use c03::{cmp_scores, rank_keys};
+use scoring::Scorer;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+// How many of the best crib positions drag() returns
+const TOP_MATCHES: usize = 10;
+
+const CRIB_HELP: &'static str = "\
+    show                    Print the plaintexts as they stand
+    auto                    Guess every keystream byte that isn't asserted from column statistics
+    drag TEXT               List the places TEXT fits best, and what it makes the other lines say
+    set LINE OFFSET TEXT    Assert that LINE says TEXT starting at OFFSET
+    clear OFFSET [LEN]      Forget LEN (default 1) keystream bytes starting at OFFSET
+    key                     Print the keystream in hex, with ?? for unknown bytes
+    quit                    Leave
+
+Under the plaintexts, each column's confidence is shown as a digit from 0 to 9, * if it was
+asserted, and a space if it's unknown. Unknown plaintext bytes are ? and unprintable ones are .";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CribError {
+    // There's no such line
+    NoSuchLine { line: usize, n_lines: usize },
+    // The crib runs off the end of the line it was placed on
+    PastEndOfLine { line: usize, end: usize, len: usize },
+}
+
+impl fmt::Display for CribError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CribError::NoSuchLine { line, n_lines } =>
+                write!(f, "no line {}, there are only {}", line, n_lines),
+            CribError::PastEndOfLine { line, end, len } =>
+                write!(f, "line {} is {} bytes long, but the crib ends at {}", line, len, end),
+        }
+    }
+}
+
+impl Error for CribError { }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeySource {
+    // Picked by scoring the column
+    Statistical,
+    // Implied by plaintext the caller asserted
+    Asserted,
+}
+
+// One recovered byte of keystream. confidence runs from 0 to 1, and asserted bytes always have 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyByte {
+    pub value: u8,
+    pub confidence: f64,
+    pub source: KeySource,
+}
+
+// A place the crib could go, with what it makes the other lines say in the same columns
+#[derive(Clone, Debug, PartialEq)]
+pub struct CribMatch {
+    pub line: usize,
+    pub offset: usize,
+    // Mean score of the other lines' plaintexts in these columns. Lower is better
+    pub score: f64,
+    // (line, plaintext) for every other line that reaches these columns
+    pub plaintexts: Vec<(usize, Vec<u8>)>,
+}
+
+// Breaks many ciphertexts that were XORed with the same keystream, e.g., a reused one-time pad or
+// CTR with a fixed nonce. The keystream is filled in a byte at a time, from column statistics or
+// from plaintext the caller knows, and every byte of it decrypts every line that reaches it
+pub struct CribDragger {
+    ciphertexts: Vec<Vec<u8>>,
+    // As long as the longest ciphertext
+    keystream: Vec<Option<KeyByte>>,
+}
+
+impl CribDragger {
+    pub fn new(ciphertexts: Vec<Vec<u8>>) -> CribDragger {
+        let max_len = ciphertexts.iter().map(|ct| ct.len()).max().unwrap_or(0);
+        CribDragger {
+            ciphertexts: ciphertexts,
+            keystream: vec![None; max_len],
+        }
+    }
+
+    pub fn ciphertexts(&self) -> &[Vec<u8>] {
+        &self.ciphertexts
+    }
+
+    pub fn keystream(&self) -> &[Option<KeyByte>] {
+        &self.keystream
+    }
+
+    // The bytes of every line in column col
+    fn column(&self, col: usize) -> Vec<u8> {
+        self.ciphertexts.iter().filter_map(|ct| ct.get(col).cloned()).collect()
+    }
+
+    // Picks every keystream byte that hasn't been asserted by scoring its column, including the
+    // columns past the end of the shortest line. The fewer lines reach a column, the less its
+    // byte is trusted
+    pub fn solve_columns<S: Scorer + ?Sized>(&mut self, scorer: &S) {
+        let n_lines = self.ciphertexts.len() as f64;
+        for col in 0..self.keystream.len() {
+            if let Some(KeyByte { source: KeySource::Asserted, .. }) = self.keystream[col] {
+                continue;
+            }
+            let column = self.column(col);
+            let ranking = rank_keys(&column, scorer, 1);
+            self.keystream[col] = Some(KeyByte {
+                value: ranking.candidates[0].key,
+                confidence: ranking.confidence * (column.len() as f64) / n_lines,
+                source: KeySource::Statistical,
+            });
+        }
+    }
+
+    // Declares that line decrypts to plaintext starting at offset. This fixes the keystream in
+    // those columns, and so the plaintext of every other line there too
+    pub fn assert_plaintext(&mut self, line: usize, offset: usize,
+                            plaintext: &[u8]) -> Result<(), CribError> {
+        let ct = self.line(line)?;
+        let end = offset + plaintext.len();
+        if end > ct.len() {
+            return Err(CribError::PastEndOfLine { line: line, end: end, len: ct.len() });
+        }
+
+        let values = ct[offset..end].iter().zip(plaintext).map(|(&c, &p)| c ^ p)
+                                    .collect::<Vec<u8>>();
+        for (i, value) in values.into_iter().enumerate() {
+            self.keystream[offset+i] = Some(KeyByte {
+                value: value,
+                confidence: 1f64,
+                source: KeySource::Asserted,
+            });
+        }
+
+        Ok(())
+    }
+
+    // Forgets len keystream bytes starting at offset, asserted or not
+    pub fn clear(&mut self, offset: usize, len: usize) {
+        let end = (offset + len).min(self.keystream.len());
+        for k in self.keystream[offset.min(end)..end].iter_mut() {
+            *k = None;
+        }
+    }
+
+    fn line(&self, line: usize) -> Result<&[u8], CribError> {
+        self.ciphertexts.get(line).map(|ct| &ct[..]).ok_or(CribError::NoSuchLine {
+            line: line,
+            n_lines: self.ciphertexts.len(),
+        })
+    }
+
+    // Slides the crib over every position of every line and scores what the keystream it implies
+    // does to the other lines. Returns the best positions first. Nothing is changed; pass the one
+    // that looks right to assert_plaintext
+    pub fn drag<S: Scorer + ?Sized>(&self, crib: &[u8], scorer: &S) -> Vec<CribMatch> {
+        let mut matches = Vec::new();
+        if crib.is_empty() {
+            return matches;
+        }
+
+        for (line, ct) in self.ciphertexts.iter().enumerate() {
+            if ct.len() < crib.len() {
+                continue;
+            }
+            for offset in 0..(ct.len() - crib.len() + 1) {
+                let key = ct[offset..offset+crib.len()].iter().zip(crib).map(|(&c, &p)| c ^ p)
+                                                      .collect::<Vec<u8>>();
+                let plaintexts = self.ciphertexts.iter().enumerate()
+                                     .filter(|&(other, _)| other != line)
+                                     .map(|(other, other_ct)| {
+                    let end = (offset + crib.len()).min(other_ct.len());
+                    let pt = other_ct[offset.min(end)..end].iter().zip(&key)
+                                                           .map(|(&c, &k)| c ^ k)
+                                                           .collect::<Vec<u8>>();
+                    (other, pt)
+                }).filter(|&(_, ref pt)| !pt.is_empty()).collect::<Vec<(usize, Vec<u8>)>>();
+                // With nothing to check it against, a position tells us nothing
+                if plaintexts.is_empty() {
+                    continue;
+                }
+
+                let total = plaintexts.iter().map(|&(_, ref pt)| scorer.score(pt)).sum::<f64>();
+                matches.push(CribMatch {
+                    line: line,
+                    offset: offset,
+                    score: total / plaintexts.len() as f64,
+                    plaintexts: plaintexts,
+                });
+            }
+        }
+        matches.sort_by(|a, b| cmp_scores(a.score, b.score));
+        matches.truncate(TOP_MATCHES);
+
+        matches
+    }
+
+    // Every line decrypted as far as the keystream is known, with None for unknown bytes
+    pub fn plaintexts(&self) -> Vec<Vec<Option<u8>>> {
+        self.ciphertexts.iter().map(|ct| {
+            ct.iter().zip(&self.keystream).map(|(&c, k)| k.map(|k| c ^ k.value)).collect()
+        }).collect()
+    }
+}
+
+fn parse_num(arg: Option<&str>, what: &str) -> Result<usize, String> {
+    match arg {
+        Some(a) => a.parse().map_err(|_| format!("{} must be a number, not {}", what, a)),
+        None => Err(format!("missing {}", what)),
+    }
+}
+
+fn print_plaintexts<W: Write>(dragger: &CribDragger, out: &mut W) -> io::Result<()> {
+    let width = dragger.ciphertexts().len().to_string().len();
+    for (i, pt) in dragger.plaintexts().iter().enumerate() {
+        let line = pt.iter().map(|b| match *b {
+            Some(c) if c >= 0x20 && c < 0x7f => c as char,
+            Some(_) => '.',
+            None => '?',
+        }).collect::<String>();
+        writeln!(out, "{:>w$}  {}", i, line, w = width)?;
+    }
+    let conf = dragger.keystream().iter().map(|k| match *k {
+        Some(k) if k.source == KeySource::Asserted => '*',
+        Some(k) => (b'0' + (k.confidence * 9f64).round() as u8) as char,
+        None => ' ',
+    }).collect::<String>();
+    writeln!(out, "{:>w$}  {}", "", conf, w = width)
+}
+
+// Runs one REPL command. Ok(false) means it's time to quit
+fn crib_command<S, W>(dragger: &mut CribDragger, scorer: &S, line: &str,
+                      out: &mut W) -> Result<bool, String> where S: Scorer + ?Sized, W: Write {
+    let mut words = line.trim_left().splitn(2, ' ');
+    let cmd = words.next().unwrap_or("");
+    let rest = words.next().unwrap_or("");
+    let io_err = |e: io::Error| e.to_string();
+    match cmd {
+        "" => (),
+        "show" => print_plaintexts(dragger, out).map_err(io_err)?,
+        "auto" => {
+            dragger.solve_columns(scorer);
+            print_plaintexts(dragger, out).map_err(io_err)?;
+        }
+        "drag" => {
+            if rest.is_empty() {
+                return Err("nothing to drag".to_string());
+            }
+            for m in dragger.drag(rest.as_bytes(), scorer) {
+                writeln!(out, "line {} offset {} (score {:.3}):", m.line, m.offset, m.score)
+                    .map_err(io_err)?;
+                for (other, pt) in m.plaintexts {
+                    writeln!(out, "    {:>3}  {}", other, String::from_utf8_lossy(&pt))
+                        .map_err(io_err)?;
+                }
+            }
+        }
+        "set" => {
+            let mut args = rest.splitn(3, ' ');
+            let line = parse_num(args.next(), "LINE")?;
+            let offset = parse_num(args.next(), "OFFSET")?;
+            let text = args.next().ok_or("missing TEXT".to_string())?;
+            dragger.assert_plaintext(line, offset, text.as_bytes()).map_err(|e| e.to_string())?;
+            print_plaintexts(dragger, out).map_err(io_err)?;
+        }
+        "clear" => {
+            let mut args = rest.split_whitespace();
+            let offset = parse_num(args.next(), "OFFSET")?;
+            let len = match args.next() {
+                Some(a) => parse_num(Some(a), "LEN")?,
+                None => 1,
+            };
+            dragger.clear(offset, len);
+            print_plaintexts(dragger, out).map_err(io_err)?;
+        }
+        "key" => {
+            let key = dragger.keystream().iter().map(|k| match *k {
+                Some(k) => format!("{:02x}", k.value),
+                None => "??".to_string(),
+            }).collect::<String>();
+            writeln!(out, "{}", key).map_err(io_err)?;
+        }
+        "help" => writeln!(out, "{}", CRIB_HELP).map_err(io_err)?,
+        "quit" | "exit" => return Ok(false),
+        _ => return Err(format!("unknown command {}; try help", cmd)),
+    }
+
+    Ok(true)
+}
+
+// A prompt for crib-dragging by hand. Commands come a line at a time from input, and the
+// plaintexts get printed to out again after every change. Type help for the commands. Bad
+// commands print an error and carry on; it returns at quit or the end of the input
+pub fn crib_repl<S, R, W>(dragger: &mut CribDragger, scorer: &S, input: R,
+                          out: &mut W) -> io::Result<()>
+        where S: Scorer + ?Sized, R: BufRead, W: Write {
+    print_plaintexts(dragger, out)?;
+    let mut lines = input.lines();
+    loop {
+        write!(out, "crib> ")?;
+        out.flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(()),
+        };
+        match crib_command(dragger, scorer, line.trim_right_matches('\r'), out) {
+            Ok(true) => (),
+            Ok(false) => return Ok(()),
+            Err(e) => writeln!(out, "error: {}", e)?,
+        }
+    }
+}
+
+#[test]
+fn tst_crib() {
+    use scoring::{many_time_pad, NgramModel};
+
+    let (pts, cts, keystream) = many_time_pad();
+    let min_len = cts.iter().map(|ct| ct.len()).min().unwrap();
+    let max_len = cts.iter().map(|ct| ct.len()).max().unwrap();
+    let unigrams = NgramModel::english().marginal(1);
+
+    // The crib goes where it really is
+    let mut dragger = CribDragger::new(cts.clone());
+    assert!(dragger.plaintexts().iter().all(|pt| pt.iter().all(|b| b.is_none())));
+    let top = &dragger.drag(b"ringin' the bell", &unigrams)[0];
+    assert_eq!((top.line, top.offset), (0, 17));
+
+    // Column statistics alone get most of the way, past the shortest line too
+    dragger.solve_columns(&unigrams);
+    let correct = (0..max_len).filter(|&col| dragger.keystream()[col].unwrap().value
+                                             == keystream[col]).count();
+    assert!(correct > min_len);
+    assert!(correct as f64 / max_len as f64 > 0.8);
+    // Columns that only one line reaches are the least trusted
+    let conf = |col: usize| dragger.keystream()[col].unwrap().confidence;
+    assert!(conf(max_len-1) < conf(1));
+
+    // Asserting the longest line fixes its columns in every other line
+    let longest = (0..pts.len()).max_by_key(|&i| pts[i].len()).unwrap();
+    dragger.assert_plaintext(longest, 0, &pts[longest]).unwrap();
+    for (pt, recovered) in pts.iter().zip(dragger.plaintexts()) {
+        assert_eq!(recovered.into_iter().map(|b| b.unwrap()).collect::<Vec<u8>>(), *pt);
+    }
+    assert!(dragger.keystream().iter().all(|k| k.unwrap().source == KeySource::Asserted));
+
+    // Asserted bytes survive another statistical pass; cleared ones don't
+    dragger.clear(max_len - 5, 10);
+    dragger.solve_columns(&unigrams);
+    assert_eq!(dragger.keystream()[0].unwrap().confidence, 1f64);
+    assert_eq!(dragger.keystream()[max_len-1].unwrap().source, KeySource::Statistical);
+
+    assert_eq!(dragger.assert_plaintext(pts.len(), 0, b"x"),
+               Err(CribError::NoSuchLine { line: pts.len(), n_lines: pts.len() }));
+    assert_eq!(dragger.assert_plaintext(0, 32, b"bell"),
+               Err(CribError::PastEndOfLine { line: 0, end: 36, len: 34 }));
+
+    // The same through the prompt. Bad commands don't stop it, and quit does
+    let mut dragger = CribDragger::new(cts.clone());
+    let commands = format!("set {} 0 {}\nset 0\nfrobnicate\nkey\nquit\nshow\n", longest,
+                           String::from_utf8_lossy(&pts[longest]));
+    let mut out = Vec::new();
+    crib_repl(&mut dragger, &unigrams, commands.as_bytes(), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(&format!(" 0  {}\n", String::from_utf8_lossy(&pts[0]))));
+    assert!(out.contains("error: missing OFFSET"));
+    assert!(out.contains("error: unknown command frobnicate"));
+    let hex_key = keystream[..max_len].iter().map(|k| format!("{:02x}", k)).collect::<String>();
+    assert!(out.contains(&hex_key));
+    assert_eq!(out.matches("crib> ").count(), 5);
+}
@@ -0,0 +1,288 @@
+use block_cipher::{BlockCipher, AES_BLOCK_SIZE};
+use modes::{Counter, Mode, ModeCipher};
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, Mul};
+
+// An element of GF(2^128) laid out the way GCM does it: the first bit of a block is the
+// coefficient of x^0 and the last bit is the coefficient of x^127. So as a big-endian u128, the
+// top bit is x^0. The modulus is x^128 + x^7 + x^2 + x + 1
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Gf128(pub u128);
+
+impl Gf128 {
+    pub fn zero() -> Gf128 {
+        Gf128(0)
+    }
+
+    pub fn one() -> Gf128 {
+        Gf128(1 << 127)
+    }
+
+    // Shorter blocks are padded with zeros on the right, like GHASH does to a trailing partial
+    // block
+    pub fn from_bytes(bytes: &[u8]) -> Gf128 {
+        assert!(bytes.len() <= AES_BLOCK_SIZE, "More than a block!");
+        let mut block = [0u8; AES_BLOCK_SIZE];
+        block[..bytes.len()].copy_from_slice(bytes);
+        Gf128(block.iter().fold(0u128, |acc, &b| (acc << 8) | b as u128))
+    }
+
+    pub fn to_bytes(&self) -> [u8; AES_BLOCK_SIZE] {
+        let mut block = [0u8; AES_BLOCK_SIZE];
+        for (i, b) in block.iter_mut().enumerate() {
+            *b = (self.0 >> (8 * (AES_BLOCK_SIZE - 1 - i))) as u8;
+        }
+        block
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn square(&self) -> Gf128 {
+        *self * *self
+    }
+
+    // a^(2^128 - 2), which is a^-1 for everything but 0. None for 0
+    pub fn inverse(&self) -> Option<Gf128> {
+        if self.is_zero() {
+            return None;
+        }
+        let mut result = Gf128::one();
+        let mut power = *self;
+        for _ in 1..128 {
+            power = power.square();
+            result = result * power;
+        }
+        Some(result)
+    }
+}
+
+// Addition is XOR, so it's also subtraction
+impl Add for Gf128 {
+    type Output = Gf128;
+
+    fn add(self, other: Gf128) -> Gf128 {
+        Gf128(self.0 ^ other.0)
+    }
+}
+
+// Algorithm 1 from the GCM spec. Shifting right multiplies by x, since the top bit is x^0
+impl Mul for Gf128 {
+    type Output = Gf128;
+
+    fn mul(self, other: Gf128) -> Gf128 {
+        let r = 0xe1u128 << 120;
+        let mut z = 0u128;
+        let mut v = other.0;
+        for i in 0..128 {
+            if (self.0 >> (127 - i)) & 1 == 1 {
+                z ^= v;
+            }
+            v = if v & 1 == 1 { (v >> 1) ^ r } else { v >> 1 };
+        }
+        Gf128(z)
+    }
+}
+
+// The blocks GHASH runs over: the AAD and the ciphertext, each zero-padded to a whole block, then
+// both their lengths in bits as big-endian u64s
+pub fn ghash_blocks(aad: &[u8], ciphertext: &[u8]) -> Vec<Gf128> {
+    let mut blocks = aad.chunks(AES_BLOCK_SIZE).map(Gf128::from_bytes).collect::<Vec<Gf128>>();
+    blocks.extend(ciphertext.chunks(AES_BLOCK_SIZE).map(Gf128::from_bytes));
+    let lengths = ((aad.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+    blocks.push(Gf128(lengths));
+
+    blocks
+}
+
+// X_1 H^m + X_2 H^(m-1) + ... + X_m H, evaluated Horner-style
+pub fn ghash(h: Gf128, aad: &[u8], ciphertext: &[u8]) -> Gf128 {
+    ghash_blocks(aad, ciphertext).into_iter().fold(Gf128::zero(), |y, x| (y + x) * h)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GcmError {
+    // GCM doesn't define a zero-length nonce
+    EmptyNonce,
+    // Only full 16-byte tags are accepted
+    TagLength { found: usize },
+    // The tag doesn't match. Nothing about why is given out
+    BadTag,
+    // Recovering H from repeated nonces takes at least two messages
+    TooFewMessages { found: usize },
+}
+
+impl fmt::Display for GcmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GcmError::EmptyNonce => write!(f, "nonce is empty"),
+            GcmError::TagLength { found } =>
+                write!(f, "tag is {} bytes, but it has to be {}", found, AES_BLOCK_SIZE),
+            GcmError::BadTag => write!(f, "tag doesn't match"),
+            GcmError::TooFewMessages { found } =>
+                write!(f, "got {} messages, but it takes at least 2", found),
+        }
+    }
+}
+
+impl Error for GcmError { }
+
+// Galois/counter mode over any 128-bit block cipher. The ciphertext is CTR mode starting one past
+// the counter block J_0, and the tag is GHASH of the AAD and ciphertext, masked with E(J_0)
+pub struct Gcm<C: BlockCipher<Block=[u8; AES_BLOCK_SIZE]>> {
+    cipher: C,
+    // The hash key, E(0)
+    h: Gf128,
+}
+
+impl<C: BlockCipher<Block=[u8; AES_BLOCK_SIZE]>> Gcm<C> {
+    pub fn new(cipher: C) -> Gcm<C> {
+        let mut h = [0u8; AES_BLOCK_SIZE];
+        cipher.encrypt_block(&mut h);
+        Gcm {
+            cipher: cipher,
+            h: Gf128::from_bytes(&h),
+        }
+    }
+
+    // Anyone who has this can forge tags. Exposed so attacks can check their work
+    pub fn h(&self) -> Gf128 {
+        self.h
+    }
+
+    // 96-bit nonces are used as is, with a 32-bit counter after them. Any other length is hashed
+    // into a counter block
+    fn j0(&self, nonce: &[u8]) -> Result<[u8; AES_BLOCK_SIZE], GcmError> {
+        match nonce.len() {
+            0 => Err(GcmError::EmptyNonce),
+            12 => {
+                let mut j0 = [0u8; AES_BLOCK_SIZE];
+                j0[..12].copy_from_slice(nonce);
+                j0[15] = 1;
+                Ok(j0)
+            },
+            // This is exactly GHASH with the nonce as ciphertext and no AAD
+            _ => Ok(ghash(self.h, &[], nonce).to_bytes()),
+        }
+    }
+
+    // CTR from J_0. The first block of keystream is E(J_0), which masks the tag, and what's left
+    // is ready to en/decrypt the message
+    fn ctr(&self, j0: &[u8; AES_BLOCK_SIZE]) -> (Gf128, ModeCipher<&C>) {
+        let mut ctr = ModeCipher::encryptor(Mode::Ctr(Counter::BigEndian32), &self.cipher, j0)
+                                 .unwrap();
+        let mut mask = [0u8; AES_BLOCK_SIZE];
+        ctr.update(&mut mask).unwrap();
+
+        (Gf128::from_bytes(&mask), ctr)
+    }
+
+    // Returns (ciphertext, tag)
+    pub fn seal(&self, nonce: &[u8], aad: &[u8],
+                plaintext: &[u8]) -> Result<(Vec<u8>, [u8; AES_BLOCK_SIZE]), GcmError> {
+        let (mask, mut ctr) = self.ctr(&self.j0(nonce)?);
+        let mut ciphertext = plaintext.to_vec();
+        ctr.update(&mut ciphertext).unwrap();
+        let tag = ghash(self.h, aad, &ciphertext) + mask;
+
+        Ok((ciphertext, tag.to_bytes()))
+    }
+
+    // Checks the tag, in constant time, before decrypting anything
+    pub fn open(&self, nonce: &[u8], aad: &[u8], ciphertext: &[u8],
+                tag: &[u8]) -> Result<Vec<u8>, GcmError> {
+        if tag.len() != AES_BLOCK_SIZE {
+            return Err(GcmError::TagLength { found: tag.len() });
+        }
+        let (mask, mut ctr) = self.ctr(&self.j0(nonce)?);
+        let expected = (ghash(self.h, aad, ciphertext) + mask).to_bytes();
+        let diff = expected.iter().zip(tag).fold(0u8, |acc, (&a, &b)| acc | (a ^ b));
+        if diff != 0 {
+            return Err(GcmError::BadTag);
+        }
+
+        let mut plaintext = ciphertext.to_vec();
+        ctr.update(&mut plaintext).unwrap();
+        Ok(plaintext)
+    }
+}
+
+#[test]
+fn tst_gcm() {
+    use aes::Aes;
+    use c01::decode_hex;
+
+    // Test cases 1-6, 13, 14 and 16 from McGrew and Viega's GCM spec. These use 128- and 256-bit
+    // keys, no AAD and some, and 96-bit and other nonces
+    let key = "feffe9928665731c6d6a8f9467308308";
+    let nonce = "cafebabefacedbaddecaf888";
+    let pt = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+              1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b391aafd255";
+    let aad = "feedfacedeadbeeffeedfacedeadbeefabaddad2";
+    let zeros = "00000000000000000000000000000000";
+    let vectors = [
+        (zeros, "000000000000000000000000", "", "", "", "58e2fccefa7e3061367f1d57a4e7455a"),
+        (zeros, "000000000000000000000000", "", zeros, "0388dace60b6a392f328c2b971b2fe78",
+         "ab6e47d42cec13bdf53a67b21257bddf"),
+        (key, nonce, "", pt,
+         "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+          21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091473f5985",
+         "4d5c2af327cd64a62cf35abd2ba6fab4"),
+        (key, nonce, aad, &pt[..120],
+         "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+          21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091",
+         "5bc94fbc3221a5db94fae95ae7121a47"),
+        (key, "cafebabefacedbad", aad, &pt[..120],
+         "61353b4c2806934a777ff51fa22a4755699b2a714fcdc6f83766e5f97b6c7423\
+          73806900e49f24b22b097544d4896b424989b5e1ebac0f07c23f4598",
+         "3612d2e79e3b0785561be14aaca2fccb"),
+        (key, "9313225df88406e555909c5aff5269aa6a7a9538534f7da1e4c303d2a318a728\
+               c3c0c95156809539fcf0e2429a6b525416aedbf5a0de6a57a637b39b", aad, &pt[..120],
+         "8ce24998625615b603a033aca13fb894be9112a5c3a211a8ba262a3cca7e2ca7\
+          01e4a9a4fba43c90ccdcb281d48c7c6fd62875d2aca417034c34aee5",
+         "619cc5aefffe0bfa462af43c1699d050"),
+        ("0000000000000000000000000000000000000000000000000000000000000000",
+         "000000000000000000000000", "", "", "", "530f8afbc74536b9a963b4f1c4cb738b"),
+        ("0000000000000000000000000000000000000000000000000000000000000000",
+         "000000000000000000000000", "", zeros, "cea7403d4d606b6e074ec5d3baf39d18",
+         "d0d1c8a799996bf0265b98b5d48ab919"),
+        ("feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308", nonce, aad,
+         &pt[..120],
+         "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa\
+          8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662",
+         "76fc6ece0f4e1768cddf8853bb2d551b"),
+    ];
+    for &(key, nonce, aad, pt, ct, tag) in vectors.iter() {
+        let gcm = Gcm::new(Aes::new(&decode_hex(key)).unwrap());
+        let (nonce, aad, pt) = (decode_hex(nonce), decode_hex(aad), decode_hex(pt));
+        let (ciphertext, t) = gcm.seal(&nonce, &aad, &pt).unwrap();
+        assert_eq!(ciphertext, decode_hex(ct));
+        assert_eq!(&t[..], &decode_hex(tag)[..]);
+        assert_eq!(gcm.open(&nonce, &aad, &ciphertext, &t).unwrap(), pt);
+    }
+
+    // Any change at all is caught
+    let gcm = Gcm::new(Aes::new(&decode_hex(key)).unwrap());
+    let (nonce, aad) = (decode_hex(nonce), decode_hex(aad));
+    let (ciphertext, tag) = gcm.seal(&nonce, &aad, &decode_hex(pt)).unwrap();
+    let mut flipped = ciphertext.clone();
+    flipped[30] ^= 0x80;
+    assert_eq!(gcm.open(&nonce, &aad, &flipped, &tag), Err(GcmError::BadTag));
+    assert_eq!(gcm.open(&nonce, &aad[1..], &ciphertext, &tag), Err(GcmError::BadTag));
+    assert_eq!(gcm.open(&nonce, &aad, &ciphertext[..63], &tag), Err(GcmError::BadTag));
+    assert_eq!(gcm.open(&nonce, &aad, &ciphertext, &tag[..12]),
+               Err(GcmError::TagLength { found: 12 }));
+    assert_eq!(gcm.seal(&[], &aad, b"hi"), Err(GcmError::EmptyNonce));
+
+    // The field behaves
+    let a = Gf128::from_bytes(b"YELLOW SUBMARINE");
+    let b = Gf128::from_bytes(b"yellow");
+    assert_eq!(a * Gf128::one(), a);
+    assert_eq!(a * b, b * a);
+    assert_eq!(a * (b + Gf128::one()), a * b + a);
+    assert_eq!(a * a.inverse().unwrap(), Gf128::one());
+    assert_eq!(Gf128::zero().inverse(), None);
+    assert_eq!(Gf128::from_bytes(&a.to_bytes()), a);
+}
@@ -0,0 +1,248 @@
+use block_cipher::AES_BLOCK_SIZE;
+use gcm::{ghash, ghash_blocks, GcmError, Gf128};
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+// A GCM message as seen on the wire
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GcmMessage {
+    pub aad: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub tag: [u8; AES_BLOCK_SIZE],
+}
+
+impl GcmMessage {
+    pub fn new(aad: &[u8], ciphertext: &[u8], tag: &[u8]) -> Result<GcmMessage, GcmError> {
+        if tag.len() != AES_BLOCK_SIZE {
+            return Err(GcmError::TagLength { found: tag.len() });
+        }
+        let mut t = [0u8; AES_BLOCK_SIZE];
+        t.copy_from_slice(tag);
+
+        Ok(GcmMessage {
+            aad: aad.to_vec(),
+            ciphertext: ciphertext.to_vec(),
+            tag: t,
+        })
+    }
+
+    // The tag as a polynomial in the unknown H: the GHASH blocks are the coefficients, from H^m
+    // down to H, and the tag is the constant term. So with the mask E(J_0) added in, H is a root.
+    // Lowest degree first
+    fn tag_polynomial(&self) -> Vec<Gf128> {
+        let mut poly = ghash_blocks(&self.aad, &self.ciphertext);
+        poly.push(Gf128::from_bytes(&self.tag));
+        poly.reverse();
+        poly
+    }
+}
+
+// Polynomials over GF(2^128), as coefficient vectors with the lowest degree first and no zeros on
+// the end. The zero polynomial is empty
+fn trim(mut p: Vec<Gf128>) -> Vec<Gf128> {
+    while p.last().map(|c| c.is_zero()).unwrap_or(false) {
+        p.pop();
+    }
+    p
+}
+
+fn poly_add(a: &[Gf128], b: &[Gf128]) -> Vec<Gf128> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = long.to_vec();
+    for (s, &c) in sum.iter_mut().zip(short) {
+        *s = *s + c;
+    }
+    trim(sum)
+}
+
+fn poly_mul(a: &[Gf128], b: &[Gf128]) -> Vec<Gf128> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut product = vec![Gf128::zero(); a.len() + b.len() - 1];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            product[i+j] = product[i+j] + x * y;
+        }
+    }
+    trim(product)
+}
+
+// Scales p so its leading coefficient is 1
+fn monic(p: &[Gf128]) -> Vec<Gf128> {
+    match p.last() {
+        None => Vec::new(),
+        Some(lead) => {
+            let inv = lead.inverse().unwrap();
+            p.iter().map(|&c| c * inv).collect()
+        },
+    }
+}
+
+// (quotient, remainder) of a divided by a monic m
+fn poly_divmod(a: &[Gf128], m: &[Gf128]) -> (Vec<Gf128>, Vec<Gf128>) {
+    assert_eq!(m.last(), Some(&Gf128::one()), "Divisor isn't monic!");
+    let mut rem = a.to_vec();
+    if rem.len() < m.len() {
+        return (Vec::new(), rem);
+    }
+    let mut quot = vec![Gf128::zero(); rem.len() - m.len() + 1];
+    for shift in (0..quot.len()).rev() {
+        let c = rem[shift + m.len() - 1];
+        if c.is_zero() {
+            continue;
+        }
+        quot[shift] = c;
+        for (i, &mc) in m.iter().enumerate() {
+            rem[shift+i] = rem[shift+i] + c * mc;
+        }
+    }
+    (trim(quot), trim(rem))
+}
+
+// Monic gcd
+fn poly_gcd(a: &[Gf128], b: &[Gf128]) -> Vec<Gf128> {
+    let mut a = monic(a);
+    let mut b = monic(b);
+    while !b.is_empty() {
+        let (_, r) = poly_divmod(&a, &b);
+        a = b;
+        b = monic(&r);
+    }
+    a
+}
+
+fn square_mod(p: &[Gf128], m: &[Gf128]) -> Vec<Gf128> {
+    poly_divmod(&poly_mul(p, p), m).1
+}
+
+fn random_gf128(rng: &mut XorShiftRng) -> Gf128 {
+    Gf128(((rng.next_u64() as u128) << 64) | rng.next_u64() as u128)
+}
+
+// Splits f, a product of distinct monic linear factors, and pushes their roots. The trace map
+// r + r^2 + r^4 + ... + r^(2^127) sends every element of GF(2^128) to 0 or 1. Taken mod f with
+// r = aX + b for random a and b, it's 0 at about half the roots of f, so its gcd with f is a
+// proper factor about half the time
+fn split_linear(f: &[Gf128], rng: &mut XorShiftRng, roots: &mut Vec<Gf128>) {
+    match f.len() {
+        0 | 1 => return,
+        2 => {
+            // X + c, which is 0 at X = c
+            roots.push(f[0]);
+            return;
+        },
+        _ => (),
+    }
+    loop {
+        let r = poly_divmod(&trim(vec![random_gf128(rng), random_gf128(rng)]), f).1;
+        let mut power = r.clone();
+        let mut trace = r;
+        for _ in 1..128 {
+            power = square_mod(&power, f);
+            trace = poly_add(&trace, &power);
+        }
+        let g = poly_gcd(f, &trace);
+        if g.len() > 1 && g.len() < f.len() {
+            split_linear(&g, rng, roots);
+            split_linear(&poly_divmod(f, &g).0, rng, roots);
+            return;
+        }
+    }
+}
+
+// Every root of p in GF(2^128). The roots of X^(2^128) - X are exactly the field elements, so
+// its gcd with p is the product of p's distinct linear factors, and those get split apart
+fn roots(p: &[Gf128]) -> Vec<Gf128> {
+    let p = monic(&trim(p.to_vec()));
+    if p.len() < 2 {
+        return Vec::new();
+    }
+
+    let x = vec![Gf128::zero(), Gf128::one()];
+    let mut x_power = poly_divmod(&x, &p).1;
+    for _ in 0..128 {
+        x_power = square_mod(&x_power, &p);
+    }
+    let linear = poly_gcd(&p, &poly_add(&x_power, &x));
+
+    // Seeded, so splitting is repeatable
+    let mut rng = XorShiftRng::from_seed([0x243f6a88, 0x85a308d3, 0x13198a2e, 0x03707344]);
+    let mut out = Vec::new();
+    split_linear(&linear, &mut rng, &mut out);
+    out.sort_by_key(|h| h.0);
+    out
+}
+
+// The "forbidden attack" on GCM with a repeated nonce. Each tag is GHASH plus the same mask
+// E(J_0), so adding two tag polynomials cancels the mask and leaves one with H as a root. Every
+// pair narrows down the candidates; with two messages there are usually only a handful, and a
+// third almost always leaves just the real H. Needs at least two messages, all under one key and
+// nonce
+pub fn recover_ghash_keys(messages: &[GcmMessage]) -> Result<Vec<Gf128>, GcmError> {
+    if messages.len() < 2 {
+        return Err(GcmError::TooFewMessages { found: messages.len() });
+    }
+    let first = messages[0].tag_polynomial();
+    let mut candidates = roots(&poly_add(&first, &messages[1].tag_polynomial()));
+    for msg in &messages[2..] {
+        let diff = poly_add(&first, &msg.tag_polynomial());
+        candidates.retain(|&h| eval(&diff, h).is_zero());
+    }
+
+    Ok(candidates)
+}
+
+fn eval(p: &[Gf128], x: Gf128) -> Gf128 {
+    p.iter().rev().fold(Gf128::zero(), |acc, &c| acc * x + c)
+}
+
+// With H and any message sent under a nonce, makes a valid tag for any other AAD and ciphertext
+// under the same nonce. The ciphertext is CTR, so flipping its bits flips the plaintext's
+pub fn forge_tag(h: Gf128, known: &GcmMessage, aad: &[u8],
+                 ciphertext: &[u8]) -> [u8; AES_BLOCK_SIZE] {
+    let mask = Gf128::from_bytes(&known.tag) + ghash(h, &known.aad, &known.ciphertext);
+    (ghash(h, aad, ciphertext) + mask).to_bytes()
+}
+
+#[test]
+fn tst_gcm_attack() {
+    use aes::Aes;
+    use c02::xor_bytes;
+    use gcm::{Gcm, GcmError};
+
+    let gcm = Gcm::new(Aes::new(b"YELLOW SUBMARINE").unwrap());
+    let nonce = b"same nonce!!";
+    let messages = [
+        (&b"user=alice"[..], &b"Transfer $10 to Bob, please. Thanks!"[..]),
+        (&b"user=carol"[..], &b"Let's get lunch on Friday"[..]),
+        (&b"user=dave"[..], &b"The quick brown fox jumps over the lazy dog"[..]),
+    ].iter().map(|&(aad, pt)| {
+        let (ct, tag) = gcm.seal(nonce, aad, pt).unwrap();
+        GcmMessage::new(aad, &ct, &tag).unwrap()
+    }).collect::<Vec<GcmMessage>>();
+
+    // Two messages leave a few candidates, including the real one. A third pins it down
+    let candidates = recover_ghash_keys(&messages[..2]).unwrap();
+    assert!(candidates.contains(&gcm.h()));
+    assert_eq!(recover_ghash_keys(&messages), Ok(vec![gcm.h()]));
+    assert_eq!(recover_ghash_keys(&messages[..1]), Err(GcmError::TooFewMessages { found: 1 }));
+    assert_eq!(GcmMessage::new(b"", b"", &[0; 12]), Err(GcmError::TagLength { found: 12 }));
+
+    // Change the amount in the first message and fix up the tag
+    let known = b"Transfer $10 to Bob, please. Thanks!";
+    let wanted = b"Transfer $99 to Eve, please. Thanks!";
+    let ct = xor_bytes(&messages[0].ciphertext, &xor_bytes(known, wanted));
+    let tag = forge_tag(gcm.h(), &messages[0], b"user=alice", &ct);
+    assert_eq!(gcm.open(nonce, b"user=alice", &ct, &tag).unwrap(), &wanted[..]);
+    assert_eq!(gcm.open(nonce, b"user=alice", &ct, &messages[0].tag), Err(GcmError::BadTag));
+
+    // The polynomial machinery on its own: (X + a)(X + b)(X^2 + X + c) has roots a and b, and a
+    // quadratic factor that may or may not split
+    let a = Gf128::from_bytes(b"a");
+    let b = Gf128::from_bytes(b"b");
+    let p = poly_mul(&poly_mul(&[a, Gf128::one()], &[b, Gf128::one()]),
+                     &[Gf128::from_bytes(b"c"), Gf128::one(), Gf128::one()]);
+    let found = roots(&p);
+    assert!(found.contains(&a) && found.contains(&b));
+    assert!(found.iter().all(|&r| eval(&p, r).is_zero()));
+}
@@ -1,7 +1,23 @@
 #![allow(dead_code, unused_imports)]
 
-extern crate iterslide;
-extern crate openssl;
+extern crate rand;
+
+mod aes;
+mod block_cipher;
+mod block_repeats;
+mod classical;
+mod codec;
+mod crib;
+mod gcm;
+mod gcm_attack;
+mod modes;
+mod oracle;
+mod padding;
+mod remote;
+mod repeating_xor;
+mod scoring;
+mod triage;
+mod xor_stream;
 
 mod c01;
 mod c02;
@@ -12,7 +28,34 @@ mod c06;
 mod c07;
 mod c08;
 
-pub use c01::decode_hex;
+pub use aes::{add_round_key, expand_key, inv_mix_columns, inv_shift_rows, inv_sub_bytes,
+              mix_columns, shift_rows, sub_bytes, Aes, AesBackend, AesBlock, AES_MAX_ROUNDS,
+              INV_SBOX, SBOX};
+pub use block_cipher::{BlockCipher, CipherError, AES_BLOCK_SIZE, AES_KEY_SIZES};
+pub use block_repeats::{find_block_repeats, rank_block_repeats, BlockRepeats,
+                        DEFAULT_BLOCK_SIZES};
+pub use classical::{beaufort, break_beaufort, break_caesar, break_columnar, break_substitution,
+                    break_vigenere, caesar_decrypt, caesar_encrypt, column_order, columnar_decrypt,
+                    columnar_encrypt, guess_period, invert_substitution, letter_ioc, substitute,
+                    vigenere_decrypt, vigenere_encrypt, ClassicalError, ClassicalSolution};
+pub use codec::{CodecError, Decoder, Encoder, Encoding};
+pub use crib::{crib_repl, CribDragger, CribError, CribMatch, KeyByte, KeySource};
+pub use gcm::{ghash, ghash_blocks, Gcm, GcmError, Gf128};
+pub use gcm_attack::{forge_tag, recover_ghash_keys, GcmMessage};
+pub use modes::{decrypt_with_mode, encrypt_with_mode, Counter, Mode, ModeCipher, ModeError};
+pub use oracle::{log_cost, Cost, CostReport, FromTranscript, Meter, Metered, Oracle,
+                 OracleError, Replay, Transcribe, Transcript, REPORT_VAR};
+pub use padding::{AnsiX923, Iso10126, Iso7816, Padding, PaddingError, Pkcs7, ZeroPadding};
+pub use remote::{OracleClient, OracleServer};
+pub use repeating_xor::{KeySizeEstimator, RepeatingXorBreaker, RepeatingXorCandidate};
+pub use scoring::{NgramModel, Scorer};
+pub use triage::{triage, unwrap_encodings, ByteStats, Finding, Hypothesis, TriageReport};
+pub use xor_stream::{xor_into, xor_repeating_into, XorError, XorStream};
+
+pub use c01::{decode_hex, hex_to_b64};
 pub use c02::{encode_hex, xor_bytes};
-pub use c04::get_lines;
+pub use c03::{coincidence_err, rank_keys, test_all_keys, KeyCandidate, KeyRanking};
+pub use c04::{braindead_err, chi_sq_bigram, chi_sq_monogram, extra_braindead_err, get_lines};
+pub use c05::xor_bytes_repeating;
 pub use c06::{decode_b64, dump_file};
+pub use c08::{count_repeated_blocks, detect_repeating_blocks};
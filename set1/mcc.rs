@@ -0,0 +1,331 @@
+extern crate set1;
+
+use set1::{crib_repl, rank_block_repeats, rank_keys, triage, unwrap_encodings, xor_into,
+           AnsiX923, CostReport, CribDragger, Encoding, Iso10126, Iso7816, NgramModel, Padding,
+           Pkcs7, RepeatingXorBreaker, XorStream, ZeroPadding, DEFAULT_BLOCK_SIZES};
+use std::ascii;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process;
+
+const USAGE: &'static str = "\
+usage: mcc <command> [args]
+
+Commands read from FILE, or from stdin if FILE is missing or -, and write to stdout.
+
+    encode ENCODING [FILE]           Encode FILE. ENCODING is one of hex, base64, base64url,
+                                     base32, or ascii85
+    decode ENCODING [FILE]           Decode FILE, ignoring whitespace
+    xor [--fixed] KEY [FILE]         XOR FILE with the hex KEY, repeated as needed. With --fixed,
+                                     KEY has to be exactly as long as FILE
+    break-single [FILE]              Find the single-byte XOR key of FILE
+    break-repeating [FILE]           Find the repeating XOR key of FILE
+    detect-ecb [BLOCK_SIZE] [FILE]   Rank the hex or base64 ciphertexts in FILE, one per line, by
+                                     how many repeated blocks they have. Without BLOCK_SIZE, tries
+                                     8, 16, and 32
+    pad [--scheme NAME] BLOCK_SIZE [FILE]
+                                     Pad FILE to a whole number of blocks. NAME is one of pkcs7
+                                     (the default), x923, iso10126, iso7816, or zero
+    unpad [--scheme NAME] BLOCK_SIZE [FILE]
+                                     Check and remove padding
+    triage [FILE]                    Guess how FILE is encoded and encrypted, most likely first
+    crib FILE                        Interactively crib-drag ciphertexts that share a keystream,
+                                     one hex or base64 ciphertext per line of FILE. Type help at
+                                     the prompt for more
+    oracle-report [FILE]             Tabulate the attack costs in FILE. Running the challenge
+                                     tests with MCC_ORACLE_REPORT=FILE fills it in
+
+Ciphertexts are read as raw bytes, so pipe them through decode first if they're encoded. The exit
+code is 1 if something goes wrong, and 2 if the arguments are bad.";
+
+enum CliError {
+    // Bad arguments; the usage gets printed and the exit code is 2
+    Usage(String),
+    // Anything that goes wrong once the command is running; the exit code is 1
+    Failed(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CliError::Usage(ref msg) => write!(f, "{}\n\n{}", msg, USAGE),
+            CliError::Failed(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> CliError {
+        CliError::Failed(err.to_string())
+    }
+}
+
+// stdin is whatever run was given in its place
+fn open_input<'a, R: Read + 'a>(path: Option<&str>,
+                                stdin: &'a mut R) -> Result<Box<Read + 'a>, CliError> {
+    match path {
+        None | Some("-") => Ok(Box::new(stdin)),
+        Some(p) => {
+            let file = File::open(p).map_err(|e| CliError::Failed(format!("{}: {}", p, e)))?;
+            Ok(Box::new(file))
+        }
+    }
+}
+
+fn read_input<R: Read>(path: Option<&str>, stdin: &mut R) -> Result<Vec<u8>, CliError> {
+    let mut out = Vec::new();
+    open_input(path, stdin)?.read_to_end(&mut out)?;
+
+    Ok(out)
+}
+
+// One ciphertext per nonblank line, each in hex or base64
+fn read_encoded_lines(input: &[u8]) -> Result<Vec<Vec<u8>>, CliError> {
+    let mut out = Vec::new();
+    for (i, line) in input.split(|&b| b == b'\n').enumerate() {
+        if line.iter().all(|&b| (b as char).is_whitespace()) {
+            continue;
+        }
+        let (layers, ct) = unwrap_encodings(line);
+        if layers.is_empty() {
+            return Err(CliError::Failed(format!("line {} isn't hex or base64", i+1)));
+        }
+        out.push(ct);
+    }
+    if out.is_empty() {
+        return Err(CliError::Failed("no ciphertexts".to_string()));
+    }
+
+    Ok(out)
+}
+
+fn parse_encoding(name: &str) -> Result<Encoding, CliError> {
+    match name {
+        "hex" => Ok(Encoding::Hex),
+        "base64" | "b64" => Ok(Encoding::Base64),
+        "base64url" => Ok(Encoding::Base64Url),
+        "base32" => Ok(Encoding::Base32),
+        "ascii85" => Ok(Encoding::Ascii85),
+        _ => Err(CliError::Usage(format!("unknown encoding {}", name))),
+    }
+}
+
+fn parse_block_size(arg: &str) -> Result<usize, CliError> {
+    match arg.parse::<usize>() {
+        Ok(n) if n >= 1 && n <= 255 => Ok(n),
+        _ => Err(CliError::Usage(format!("block size must be from 1 to 255, not {}", arg))),
+    }
+}
+
+fn parse_padding(name: &str) -> Result<Box<Padding>, CliError> {
+    match name {
+        "pkcs7" => Ok(Box::new(Pkcs7)),
+        "x923" => Ok(Box::new(AnsiX923)),
+        "iso10126" => Ok(Box::new(Iso10126)),
+        "iso7816" => Ok(Box::new(Iso7816)),
+        "zero" => Ok(Box::new(ZeroPadding)),
+        _ => Err(CliError::Usage(format!("unknown padding scheme {}", name))),
+    }
+}
+
+// Quoted, with anything unprintable escaped
+fn escaped(bytes: &[u8]) -> String {
+    bytes.iter().flat_map(|&b| ascii::escape_default(b)).map(|b| b as char).collect()
+}
+
+// Makes sure there are no more than max_args arguments after the command name
+fn check_args(args: &[String], max_args: usize) -> Result<(), CliError> {
+    if args.len() > max_args + 1 {
+        Err(CliError::Usage(format!("too many arguments to {}", args[0])))
+    } else {
+        Ok(())
+    }
+}
+
+fn crib<R: BufRead, W: Write>(input: &[u8], commands: R, out: &mut W) -> Result<(), CliError> {
+    let mut dragger = CribDragger::new(read_encoded_lines(input)?);
+    let scorer = NgramModel::english().marginal(1);
+    crib_repl(&mut dragger, &scorer, commands, out)?;
+
+    Ok(())
+}
+
+// Runs the command in args, reading from stdin and writing to out
+fn run<R: BufRead, W: Write>(args: &[String], mut stdin: R, out: &mut W) -> Result<(), CliError> {
+    let cmd = match args.first() {
+        Some(cmd) => cmd.as_str(),
+        None => return Err(CliError::Usage("no command given".to_string())),
+    };
+
+    let arg = |i: usize| args.get(i).map(|s| s.as_str());
+    match cmd {
+        "encode" | "decode" => {
+            check_args(args, 2)?;
+            let name = arg(1).ok_or(CliError::Usage(format!("{} needs an ENCODING", cmd)))?;
+            let encoding = parse_encoding(name)?;
+            let input = read_input(arg(2), &mut stdin)?;
+            if cmd == "encode" {
+                writeln!(out, "{}", encoding.encode(&input))?;
+            } else {
+                let decoded = encoding.decode(&input).map_err(|e| {
+                    CliError::Failed(format!("bad {}: {}", name, e))
+                })?;
+                out.write_all(&decoded)?;
+            }
+        }
+        "xor" => {
+            let fixed = arg(1) == Some("--fixed");
+            let rest = &args[if fixed { 1 } else { 0 }..];
+            check_args(rest, 2)?;
+            let key_hex = rest.get(1).ok_or(CliError::Usage("xor needs a KEY".to_string()))?;
+            let key = Encoding::Hex.decode(key_hex)
+                                   .map_err(|e| CliError::Usage(format!("bad KEY: {}", e)))?;
+            let path = rest.get(2).map(|s| s.as_str());
+            if fixed {
+                let mut input = read_input(path, &mut stdin)?;
+                xor_into(&mut input, &key).map_err(|e| CliError::Failed(e.to_string()))?;
+                out.write_all(&input)?;
+            } else {
+                // Streamed, so there's no limit on how big the input can be
+                let mut stream = XorStream::new(open_input(path, &mut stdin)?, &key)
+                                           .map_err(|e| CliError::Usage(e.to_string()))?;
+                io::copy(&mut stream, out)?;
+            }
+        }
+        "break-single" => {
+            check_args(args, 1)?;
+            let input = read_input(arg(1), &mut stdin)?;
+            if input.is_empty() {
+                return Err(CliError::Failed("nothing to break".to_string()));
+            }
+            // The runners-up are worth a look, since swapping spaces for newlines costs very little
+            let ranking = rank_keys(&input, &NgramModel::english().marginal(1), 3);
+            for c in ranking.candidates {
+                writeln!(out, "key 0x{:02x} (score {:.3}): \"{}\"", c.key, c.score,
+                         escaped(&c.plaintext))?;
+            }
+        }
+        "break-repeating" => {
+            check_args(args, 1)?;
+            let input = read_input(arg(1), &mut stdin)?;
+            let candidates = RepeatingXorBreaker::english().break_ciphertext(&input);
+            let best = candidates.first()
+                                 .ok_or(CliError::Failed("nothing to break".to_string()))?;
+            writeln!(out, "key: {} \"{}\"", Encoding::Hex.encode(&best.key), escaped(&best.key))?;
+            writeln!(out, "plaintext: \"{}\"", escaped(&best.plaintext))?;
+        }
+        "detect-ecb" => {
+            // The block size is optional, so a lone argument that's a number is taken to be it
+            check_args(args, 2)?;
+            let (sizes, path) = match (arg(1), arg(2)) {
+                (Some(a), p) if p.is_some() || a.parse::<usize>().is_ok() =>
+                    (vec![parse_block_size(a)?], p),
+                (p, _) => (DEFAULT_BLOCK_SIZES.to_vec(), p),
+            };
+            let ciphertexts = read_encoded_lines(&read_input(path, &mut stdin)?)?;
+            let ranked = rank_block_repeats(ciphertexts, &sizes);
+            if ranked.is_empty() {
+                writeln!(out, "no repeated blocks")?;
+            }
+            for (idx, repeats) in ranked {
+                writeln!(out, "line {}: {} repeated {}-byte blocks from offset {} \
+                               ({:.0} bits of evidence)", idx + 1, repeats.duplicates,
+                         repeats.block_size, repeats.offset, repeats.score)?;
+            }
+        }
+        "pad" | "unpad" => {
+            let (padding, skip) = match arg(1) {
+                Some("--scheme") => {
+                    let name = arg(2).ok_or(CliError::Usage("--scheme needs a NAME".to_string()))?;
+                    (parse_padding(name)?, 2)
+                }
+                _ => (Box::new(Pkcs7) as Box<Padding>, 0),
+            };
+            check_args(args, skip + 2)?;
+            let size = arg(skip + 1)
+                           .ok_or(CliError::Usage(format!("{} needs a BLOCK_SIZE", cmd)))?;
+            let block_size = parse_block_size(size)?;
+            let input = read_input(arg(skip + 2), &mut stdin)?;
+            if cmd == "pad" {
+                out.write_all(&padding.pad(&input, block_size))?;
+            } else {
+                let unpadded = padding.unpad(&input, block_size)
+                                      .map_err(|e| CliError::Failed(e.to_string()))?;
+                out.write_all(unpadded)?;
+            }
+        }
+        "triage" => {
+            check_args(args, 1)?;
+            write!(out, "{}", triage(&read_input(arg(1), &mut stdin)?))?;
+        }
+        "crib" => {
+            // stdin is where the commands come from, so the ciphertexts can't
+            check_args(args, 1)?;
+            match arg(1) {
+                None | Some("-") => return Err(CliError::Usage("crib needs a FILE".to_string())),
+                Some(path) => crib(&read_input(Some(path), &mut stdin)?, stdin, out)?,
+            }
+        }
+        "oracle-report" => {
+            check_args(args, 1)?;
+            let report = CostReport::read(BufReader::new(open_input(arg(1), &mut stdin)?))
+                                    .map_err(|e| CliError::Failed(e.to_string()))?;
+            write!(out, "{}", report)?;
+        }
+        "help" | "-h" | "--help" => writeln!(out, "{}", USAGE)?,
+        _ => return Err(CliError::Usage(format!("unknown command {}", cmd))),
+    }
+
+    Ok(())
+}
+
+fn exit_code(err: &CliError) -> i32 {
+    match *err {
+        CliError::Usage(_) => 2,
+        CliError::Failed(_) => 1,
+    }
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    if let Err(e) = run(&args, stdin.lock(), &mut stdout.lock()) {
+        let _ = writeln!(io::stderr(), "mcc: {}", e);
+        process::exit(exit_code(&e));
+    }
+}
+
+#[test]
+fn tst_mcc() {
+    // Runs mcc on the given stdin, and gives back stdout or the exit code
+    let mcc = |args: &[&str], stdin: &[u8]| -> Result<Vec<u8>, i32> {
+        let args = args.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+        let mut out = Vec::new();
+        run(&args, stdin, &mut out).map(|_| out).map_err(|e| exit_code(&e))
+    };
+
+    let plaintext = b"Cooking MC's like a pound of bacon";
+    for &encoding in &["hex", "base64", "base64url", "base32", "ascii85"] {
+        let encoded = mcc(&["encode", encoding], plaintext).unwrap();
+        assert_eq!(mcc(&["decode", encoding, "-"], &encoded).unwrap(), &plaintext[..]);
+    }
+    assert_eq!(mcc(&["encode", "hex"], b"YELLOW").unwrap(), b"59454c4c4f57\n");
+    assert_eq!(mcc(&["xor", "--fixed", "2020"], b"AB").unwrap(), b"ab");
+
+    // Bad arguments are 2, and everything else that goes wrong is 1
+    assert_eq!(mcc(&[], b""), Err(2));
+    assert_eq!(mcc(&["encode", "rot13"], b""), Err(2));
+    assert_eq!(mcc(&["pad", "0"], b""), Err(2));
+    assert_eq!(mcc(&["decode", "hex"], b"not hex"), Err(1));
+    assert_eq!(mcc(&["unpad", "16"], b"YELLOW SUBMARINE"), Err(1));
+
+    let ciphertext = plaintext.iter().map(|&b| b ^ 0x58).collect::<Vec<u8>>();
+    let out = String::from_utf8(mcc(&["break-single"], &ciphertext).unwrap()).unwrap();
+    let best = out.lines().next().unwrap();
+    assert!(best.starts_with("key 0x58 "));
+    // escape_default escapes the apostrophe
+    assert!(best.ends_with("\"Cooking MC\\'s like a pound of bacon\""));
+}
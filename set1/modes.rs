@@ -0,0 +1,438 @@
+use block_cipher::{BlockCipher, CipherError};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModeError {
+    // ECB, CBC and PCBC only take whole blocks. Pad first
+    Unaligned { len: usize, block_size: usize },
+    // Every mode but ECB needs exactly one block of IV, and ECB needs none
+    IvLength { expected: usize, found: usize },
+    // The cipher can't run in this mode
+    Cipher { err: CipherError },
+}
+
+impl fmt::Display for ModeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ModeError::Unaligned { len, block_size } =>
+                write!(f, "{} bytes isn't a whole number of {}-byte blocks", len, block_size),
+            ModeError::IvLength { expected, found } =>
+                write!(f, "IV is {} bytes, but this mode takes {}", found, expected),
+            ModeError::Cipher { err } => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for ModeError { }
+
+impl From<CipherError> for ModeError {
+    fn from(err: CipherError) -> ModeError {
+        ModeError::Cipher { err: err }
+    }
+}
+
+// How the counter block is laid out in CTR mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Counter {
+    // The whole block is one big-endian number. This is what NIST uses
+    BigEndian,
+    // Only the last 4 bytes count, big-endian, and wrap without carrying into the rest. This is
+    // GCM's inc32
+    BigEndian32,
+    // A nonce in the first half of the block and a little-endian u64 in the last 8 bytes, like
+    // challenge 18
+    LittleEndian64,
+}
+
+impl Counter {
+    // How many bytes of the block the counter needs
+    pub fn min_block_size(&self) -> usize {
+        match *self {
+            Counter::BigEndian => 1,
+            Counter::BigEndian32 => 4,
+            Counter::LittleEndian64 => 8,
+        }
+    }
+}
+
+// Pretty diagrams here: https://en.wikipedia.org/wiki/Block_cipher_mode_of_operation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Ecb,
+    Cbc,
+    Pcbc,
+    // CFB with 1-bit, 8-bit and full-block segments
+    Cfb1,
+    Cfb8,
+    Cfb,
+    Ofb,
+    Ctr(Counter),
+}
+
+impl Mode {
+    // Whether input has to come in whole blocks. The rest act like stream ciphers
+    pub fn is_block_aligned(&self) -> bool {
+        match *self {
+            Mode::Ecb | Mode::Cbc | Mode::Pcbc => true,
+            _ => false,
+        }
+    }
+
+    pub fn needs_iv(&self) -> bool {
+        *self != Mode::Ecb
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Encrypt,
+    Decrypt,
+}
+
+// A block cipher running in some mode, which can be fed a message a piece at a time. Each piece
+// carries on from where the last left off, so cutting a message up differently doesn't change the
+// output. The stream modes take pieces of any length; the others only take whole blocks
+pub struct ModeCipher<C: BlockCipher> {
+    cipher: C,
+    mode: Mode,
+    direction: Direction,
+    // What gets fed back into the cipher. This is the previous ciphertext block in CBC and CFB,
+    // plaintext XOR ciphertext in PCBC, the last output in OFB, and the next counter in CTR
+    register: C::Block,
+    // The stream modes can stop partway through a block of keystream. used is how much of it is
+    // gone
+    keystream: C::Block,
+    used: usize,
+}
+
+impl<C: BlockCipher> ModeCipher<C> {
+    pub fn encryptor(mode: Mode, cipher: C, iv: &[u8]) -> Result<ModeCipher<C>, ModeError> {
+        ModeCipher::new(mode, cipher, iv, Direction::Encrypt)
+    }
+
+    pub fn decryptor(mode: Mode, cipher: C, iv: &[u8]) -> Result<ModeCipher<C>, ModeError> {
+        ModeCipher::new(mode, cipher, iv, Direction::Decrypt)
+    }
+
+    fn new(mode: Mode, cipher: C, iv: &[u8],
+           direction: Direction) -> Result<ModeCipher<C>, ModeError> {
+        let block_size = cipher.block_size();
+        if let Mode::Ctr(counter) = mode {
+            if block_size < counter.min_block_size() {
+                let min = counter.min_block_size();
+                return Err(CipherError::BlockSize { found: block_size, min: min }.into());
+            }
+        }
+        let expected = if mode.needs_iv() { block_size } else { 0 };
+        if iv.len() != expected {
+            return Err(ModeError::IvLength { expected: expected, found: iv.len() });
+        }
+
+        let mut register = C::Block::default();
+        if mode.needs_iv() {
+            register.as_mut().copy_from_slice(iv);
+        }
+
+        Ok(ModeCipher {
+            cipher: cipher,
+            mode: mode,
+            direction: direction,
+            register: register,
+            keystream: C::Block::default(),
+            used: block_size,
+        })
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    // Encrypts or decrypts buf in place, depending on how this was made. Nothing is touched if
+    // this returns an error
+    pub fn update(&mut self, buf: &mut [u8]) -> Result<(), ModeError> {
+        let block_size = self.cipher.block_size();
+        if self.mode.is_block_aligned() && buf.len() % block_size != 0 {
+            return Err(ModeError::Unaligned { len: buf.len(), block_size: block_size });
+        }
+
+        match self.mode {
+            Mode::Ecb | Mode::Cbc | Mode::Pcbc => {
+                for chunk in buf.chunks_mut(block_size) {
+                    self.update_block(chunk);
+                }
+            },
+            Mode::Cfb1 => {
+                for byte in buf.iter_mut() {
+                    *byte = self.cfb1_byte(*byte);
+                }
+            },
+            Mode::Cfb8 => {
+                for byte in buf.iter_mut() {
+                    *byte = self.cfb8_byte(*byte);
+                }
+            },
+            Mode::Cfb | Mode::Ofb | Mode::Ctr(_) => {
+                for byte in buf.iter_mut() {
+                    *byte = self.stream_byte(*byte);
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    // One block of ECB, CBC or PCBC
+    fn update_block(&mut self, chunk: &mut [u8]) {
+        let input = C::block_from(chunk);
+        let mut block = input;
+        match (self.mode, self.direction) {
+            (Mode::Ecb, Direction::Encrypt) => self.cipher.encrypt_block(&mut block),
+            (Mode::Ecb, Direction::Decrypt) => self.cipher.decrypt_block(&mut block),
+            (_, Direction::Encrypt) => {
+                xor_in(block.as_mut(), self.register.as_ref());
+                self.cipher.encrypt_block(&mut block);
+            },
+            (_, Direction::Decrypt) => {
+                self.cipher.decrypt_block(&mut block);
+                xor_in(block.as_mut(), self.register.as_ref());
+            },
+        }
+
+        let (plaintext, ciphertext) = match self.direction {
+            Direction::Encrypt => (input, block),
+            Direction::Decrypt => (block, input),
+        };
+        if self.mode == Mode::Pcbc {
+            self.register = plaintext;
+            xor_in(self.register.as_mut(), ciphertext.as_ref());
+        } else {
+            self.register = ciphertext;
+        }
+        chunk.copy_from_slice(block.as_ref());
+    }
+
+    // CFB-1 does a whole block encryption per bit, most significant bit first, and shifts each
+    // ciphertext bit into the register
+    fn cfb1_byte(&mut self, byte: u8) -> u8 {
+        let mut out = 0u8;
+        for i in (0..8).rev() {
+            let mut keystream = self.register;
+            self.cipher.encrypt_block(&mut keystream);
+            let in_bit = (byte >> i) & 1;
+            let out_bit = in_bit ^ (keystream.as_ref()[0] >> 7);
+            out |= out_bit << i;
+
+            let ct_bit = match self.direction {
+                Direction::Encrypt => out_bit,
+                Direction::Decrypt => in_bit,
+            };
+            let reg = self.register.as_mut();
+            for j in 0..reg.len() {
+                let carry = reg.get(j+1).map(|&b| b >> 7).unwrap_or(ct_bit);
+                reg[j] = (reg[j] << 1) | carry;
+            }
+        }
+
+        out
+    }
+
+    // CFB-8 is the same with a byte at a time
+    fn cfb8_byte(&mut self, byte: u8) -> u8 {
+        let mut keystream = self.register;
+        self.cipher.encrypt_block(&mut keystream);
+        let out = byte ^ keystream.as_ref()[0];
+
+        let ct_byte = match self.direction {
+            Direction::Encrypt => out,
+            Direction::Decrypt => byte,
+        };
+        let reg = self.register.as_mut();
+        let len = reg.len();
+        for j in 1..len {
+            reg[j-1] = reg[j];
+        }
+        reg[len-1] = ct_byte;
+
+        out
+    }
+
+    // Full-block CFB, OFB and CTR all XOR with a block of keystream at a time, and only differ in
+    // what they feed back
+    fn stream_byte(&mut self, byte: u8) -> u8 {
+        let block_size = self.keystream.as_ref().len();
+        if self.used == block_size {
+            self.keystream = self.register;
+            self.cipher.encrypt_block(&mut self.keystream);
+            match self.mode {
+                Mode::Ofb => self.register = self.keystream,
+                Mode::Ctr(counter) => increment(self.register.as_mut(), counter),
+                _ => (),
+            }
+            self.used = 0;
+        }
+
+        let out = byte ^ self.keystream.as_ref()[self.used];
+        // CFB fills the register with ciphertext as it goes. It isn't encrypted again until the
+        // whole block has been used, so overwriting it in place is fine
+        if self.mode == Mode::Cfb {
+            self.register.as_mut()[self.used] = match self.direction {
+                Direction::Encrypt => out,
+                Direction::Decrypt => byte,
+            };
+        }
+        self.used += 1;
+
+        out
+    }
+}
+
+fn xor_in(a: &mut [u8], b: &[u8]) {
+    for (x, y) in a.iter_mut().zip(b) {
+        *x ^= *y;
+    }
+}
+
+fn increment(block: &mut [u8], counter: Counter) {
+    let len = block.len();
+    match counter {
+        Counter::BigEndian => {
+            for b in block.iter_mut().rev() {
+                *b = b.wrapping_add(1);
+                if *b != 0 {
+                    break;
+                }
+            }
+        },
+        Counter::BigEndian32 => {
+            for b in block[len-4..].iter_mut().rev() {
+                *b = b.wrapping_add(1);
+                if *b != 0 {
+                    break;
+                }
+            }
+        },
+        Counter::LittleEndian64 => {
+            for b in block[len-8..].iter_mut() {
+                *b = b.wrapping_add(1);
+                if *b != 0 {
+                    break;
+                }
+            }
+        },
+    }
+}
+
+// One-shot versions, for when the whole message is at hand
+pub fn encrypt_with_mode<C: BlockCipher>(mode: Mode, cipher: C, iv: &[u8],
+                                         plaintext: &[u8]) -> Result<Vec<u8>, ModeError> {
+    let mut buf = plaintext.to_vec();
+    ModeCipher::encryptor(mode, cipher, iv)?.update(&mut buf)?;
+    Ok(buf)
+}
+
+pub fn decrypt_with_mode<C: BlockCipher>(mode: Mode, cipher: C, iv: &[u8],
+                                         ciphertext: &[u8]) -> Result<Vec<u8>, ModeError> {
+    let mut buf = ciphertext.to_vec();
+    ModeCipher::decryptor(mode, cipher, iv)?.update(&mut buf)?;
+    Ok(buf)
+}
+
+#[test]
+fn tst_modes() {
+    use aes::Aes;
+    use c01::decode_hex;
+
+    // NIST SP 800-38A, appendix F
+    let aes = Aes::new(&decode_hex("2b7e151628aed2a6abf7158809cf4f3c")).unwrap();
+    let iv = decode_hex("000102030405060708090a0b0c0d0e0f");
+    let plaintext = decode_hex("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+                                30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710");
+    let vectors = [
+        (Mode::Ecb, "", 64, "3ad77bb40d7a3660a89ecaf32466ef97f5d3d58503b9699de785895a96fdbaaf\
+                             43b1cd7f598ece23881b00e3ed0306887b0c785e27e8ad3f8223207104725dd4"),
+        (Mode::Cbc, "iv", 64, "7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2\
+                               73bed6b8e3c1743b7116e69e222295163ff1caa1681fac09120eca307586e1a7"),
+        // Not from NIST, which has nothing on PCBC. Worked out separately from the definition,
+        // C_i = E(P_i ^ P_(i-1) ^ C_(i-1)) with P_0 ^ C_0 = IV, so the first block is CBC's
+        (Mode::Pcbc, "iv", 64, "7649abac8119b246cee98e9b12e9197d9e8baff12ad5270a0d1eef93d7037994\
+                                5700b39803779fa35a3c600a49a163c033ae199f27379f21be6dd57d295cc87d"),
+        (Mode::Cfb1, "iv", 2, "68b3"),
+        (Mode::Cfb8, "iv", 18, "3b79424c9c0dd436bace9e0ed4586a4f32b9"),
+        (Mode::Cfb, "iv", 64, "3b3fd92eb72dad20333449f8e83cfb4ac8a64537a0b3a93fcde3cdad9f1ce58b\
+                               26751f67a3cbb140b1808cf187a4f4dfc04b05357c5d1c0eeac4c66f9ff7f2e6"),
+        (Mode::Ofb, "iv", 64, "3b3fd92eb72dad20333449f8e83cfb4a7789508d16918f03f53c52dac54ed825\
+                               9740051e9c5fecf64344f7a82260edcc304c6528f659c77866a510d9c1d6ae5e"),
+        (Mode::Ctr(Counter::BigEndian), "f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff", 64,
+         "874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff\
+          5ae4df3edbd5d35e5b4f09020db03eab1e031dda2fbe03d1792170a0f3009cee"),
+    ];
+    for &(mode, iv_hex, len, ciphertext) in vectors.iter() {
+        let iv = match iv_hex {
+            "iv" => iv.clone(),
+            _ => decode_hex(iv_hex),
+        };
+        let ciphertext = decode_hex(ciphertext);
+        assert_eq!(encrypt_with_mode(mode, &aes, &iv, &plaintext[..len]).unwrap(), ciphertext);
+        assert_eq!(decrypt_with_mode(mode, &aes, &iv, &ciphertext).unwrap(), &plaintext[..len]);
+
+        // A block at a time, or a byte at a time for the stream modes, gives the same thing
+        let step = if mode.is_block_aligned() { 16 } else { 1 };
+        let mut enc = ModeCipher::encryptor(mode, &aes, &iv).unwrap();
+        let mut buf = plaintext[..len].to_vec();
+        for chunk in buf.chunks_mut(step) {
+            enc.update(chunk).unwrap();
+        }
+        assert_eq!(buf, ciphertext);
+    }
+
+    // AES-256 works the same way
+    let aes256 = Aes::new(&decode_hex("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a3\
+                                       0914dff4")).unwrap();
+    assert_eq!(encrypt_with_mode(Mode::Cbc, &aes256, &iv, &plaintext).unwrap(),
+               decode_hex("f58c4c04d6e5f1ba779eabfb5f7bfbd69cfc4e967edb808d679f777bc6702c7d\
+                           39f23369a9d9bacfa530e26304231461b2eb05e2c39be9fcda6c19078c6a9d1b"));
+
+    // The stream modes don't care how the message is cut up, even mid-block
+    for &mode in [Mode::Cfb, Mode::Ofb, Mode::Ctr(Counter::LittleEndian64)].iter() {
+        let whole = encrypt_with_mode(mode, &aes, &iv, &plaintext[..50]).unwrap();
+        let mut pieces = plaintext[..50].to_vec();
+        let mut enc = ModeCipher::encryptor(mode, &aes, &iv).unwrap();
+        let (a, rest) = pieces.split_at_mut(7);
+        let (b, c) = rest.split_at_mut(20);
+        for piece in [a, b, c].iter_mut() {
+            enc.update(piece).unwrap();
+        }
+        assert_eq!(pieces, whole);
+    }
+
+    // A flipped bit in PCBC should garble everything after it
+    let ciphertext = encrypt_with_mode(Mode::Pcbc, &aes, &iv, &plaintext).unwrap();
+    assert_eq!(decrypt_with_mode(Mode::Pcbc, &aes, &iv, &ciphertext).unwrap(), plaintext);
+    let mut flipped = ciphertext.clone();
+    flipped[20] ^= 1;
+    let garbled = decrypt_with_mode(Mode::Pcbc, &aes, &iv, &flipped).unwrap();
+    assert_eq!(&garbled[..16], &plaintext[..16]);
+    assert!(garbled.chunks(16).zip(plaintext.chunks(16)).skip(1).all(|(g, p)| g != p));
+
+    // Bad input is refused without touching anything
+    let mut odd = plaintext[..20].to_vec();
+    let mut cbc = ModeCipher::encryptor(Mode::Cbc, &aes, &iv).unwrap();
+    assert_eq!(cbc.update(&mut odd), Err(ModeError::Unaligned { len: 20, block_size: 16 }));
+    assert_eq!(odd, &plaintext[..20]);
+    assert_eq!(ModeCipher::encryptor(Mode::Ecb, &aes, &iv).err(),
+               Some(ModeError::IvLength { expected: 0, found: 16 }));
+    assert_eq!(ModeCipher::decryptor(Mode::Ofb, &aes, b"short").err(),
+               Some(ModeError::IvLength { expected: 16, found: 5 }));
+
+    // Counters too big for the block
+    struct Tiny;
+    impl BlockCipher for Tiny {
+        type Block = [u8; 4];
+        fn encrypt_block(&self, _: &mut [u8; 4]) { }
+        fn decrypt_block(&self, _: &mut [u8; 4]) { }
+    }
+    assert_eq!(ModeCipher::encryptor(Mode::Ctr(Counter::LittleEndian64), Tiny, &[0; 4]).err(),
+               Some(ModeError::Cipher { err: CipherError::BlockSize { found: 4, min: 8 } }));
+    assert!(ModeCipher::encryptor(Mode::Ctr(Counter::BigEndian32), Tiny, &[0; 4]).is_ok());
+}
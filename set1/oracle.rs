@@ -0,0 +1,460 @@
+use codec::Encoding;
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+// Where the challenge tests add their costs, if it's set
+pub const REPORT_VAR: &'static str = "MCC_ORACLE_REPORT";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OracleError {
+    // The budget was used up before this query
+    OverBudget { budget: usize },
+    // Reading or writing a transcript, report, or connection failed
+    Io(io::ErrorKind),
+    // The line isn't a tab-separated pair, or one of its halves doesn't parse
+    BadTranscript { line: usize },
+    // A replayed query isn't the one the transcript has in its place
+    Diverged { query: usize },
+    // Replaying asked for more queries than the transcript has
+    EndOfTranscript { queries: usize },
+    // A remote oracle's answer didn't parse as the type we asked for
+    BadReply,
+}
+
+impl fmt::Display for OracleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OracleError::OverBudget { budget } =>
+                write!(f, "the budget of {} queries is used up", budget),
+            OracleError::Io(kind) => write!(f, "I/O error: {:?}", kind),
+            OracleError::BadTranscript { line } => write!(f, "line {} is malformed", line),
+            OracleError::Diverged { query } =>
+                write!(f, "query {} isn't the one in the transcript", query),
+            OracleError::EndOfTranscript { queries } =>
+                write!(f, "the transcript only has {} queries", queries),
+            OracleError::BadReply => write!(f, "the remote oracle's reply didn't parse"),
+        }
+    }
+}
+
+impl Error for OracleError { }
+
+impl From<io::Error> for OracleError {
+    fn from(err: io::Error) -> OracleError {
+        OracleError::Io(err.kind())
+    }
+}
+
+// Anything an attack can send chosen inputs to. Every closure from input to output is one, so
+// the challenges' boxed closures (Encryptor, Checker, EvenOracle, ...) all work as they are
+pub trait Oracle<In: ?Sized, Out> {
+    fn query(&mut self, input: &In) -> Out;
+}
+
+impl<In: ?Sized, Out, F> Oracle<In, Out> for F where F: FnMut(&In) -> Out {
+    fn query(&mut self, input: &In) -> Out {
+        self(input)
+    }
+}
+
+// How queries and answers are written down in a transcript. It has to fit on one line with no
+// tabs in it, so bytes and strings are hex
+pub trait Transcribe {
+    fn transcribe(&self) -> String;
+}
+
+pub trait FromTranscript: Sized {
+    fn from_transcript(s: &str) -> Option<Self>;
+}
+
+impl Transcribe for [u8] {
+    fn transcribe(&self) -> String {
+        Encoding::Hex.encode(self)
+    }
+}
+
+impl Transcribe for Vec<u8> {
+    fn transcribe(&self) -> String {
+        Encoding::Hex.encode(self)
+    }
+}
+
+impl Transcribe for str {
+    fn transcribe(&self) -> String {
+        Encoding::Hex.encode(self.as_bytes())
+    }
+}
+
+impl Transcribe for String {
+    fn transcribe(&self) -> String {
+        Encoding::Hex.encode(self.as_bytes())
+    }
+}
+
+impl Transcribe for bool {
+    fn transcribe(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Transcribe for usize {
+    fn transcribe(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl FromTranscript for Vec<u8> {
+    fn from_transcript(s: &str) -> Option<Vec<u8>> {
+        Encoding::Hex.decode(s).ok()
+    }
+}
+
+impl FromTranscript for String {
+    fn from_transcript(s: &str) -> Option<String> {
+        Vec::from_transcript(s).and_then(|bytes| String::from_utf8(bytes).ok())
+    }
+}
+
+impl FromTranscript for bool {
+    fn from_transcript(s: &str) -> Option<bool> {
+        s.parse().ok()
+    }
+}
+
+impl FromTranscript for usize {
+    fn from_transcript(s: &str) -> Option<usize> {
+        s.parse().ok()
+    }
+}
+
+// Writes a line per query as it happens, so a transcript survives an attack that panics
+struct Recorder<In: ?Sized, Out> {
+    out: LineWriter<File>,
+    input: fn(&In) -> String,
+    output: fn(&Out) -> String,
+}
+
+// Reads the query count of a Metered that's been given away
+#[derive(Clone, Debug)]
+pub struct Meter {
+    queries: Rc<Cell<usize>>,
+}
+
+impl Meter {
+    pub fn queries(&self) -> usize {
+        self.queries.get()
+    }
+}
+
+// Wraps an oracle to count its queries, cut it off after a budget, and write a transcript. Past
+// the budget the inner oracle isn't called at all
+pub struct Metered<O, In: ?Sized, Out> {
+    oracle: O,
+    queries: Rc<Cell<usize>>,
+    budget: Option<usize>,
+    recorder: Option<Recorder<In, Out>>,
+}
+
+impl<O, In: ?Sized, Out> Metered<O, In, Out> where O: Oracle<In, Out> {
+    pub fn new(oracle: O) -> Metered<O, In, Out> {
+        Metered {
+            oracle: oracle,
+            queries: Rc::new(Cell::new(0)),
+            budget: None,
+            recorder: None,
+        }
+    }
+
+    pub fn with_budget(mut self, budget: usize) -> Metered<O, In, Out> {
+        self.budget = Some(budget);
+        self
+    }
+
+    // Writes every query from here on to path, replacing whatever was there
+    pub fn record<P: AsRef<Path>>(self, path: P) -> io::Result<Metered<O, In, Out>>
+            where In: Transcribe, Out: Transcribe {
+        self.record_with(path, In::transcribe, Out::transcribe)
+    }
+
+    // The same, for inputs and outputs that aren't Transcribe, like the RSA challenges' Ints
+    pub fn record_with<P: AsRef<Path>>(mut self, path: P, input: fn(&In) -> String,
+                                       output: fn(&Out) -> String)
+                                       -> io::Result<Metered<O, In, Out>> {
+        self.recorder = Some(Recorder {
+            out: LineWriter::new(File::create(path)?),
+            input: input,
+            output: output,
+        });
+        Ok(self)
+    }
+
+    pub fn queries(&self) -> usize {
+        self.queries.get()
+    }
+
+    pub fn remaining(&self) -> Option<usize> {
+        self.budget.map(|budget| budget - self.queries())
+    }
+
+    pub fn meter(&self) -> Meter {
+        Meter { queries: self.queries.clone() }
+    }
+
+    pub fn into_inner(self) -> O {
+        self.oracle
+    }
+
+    pub fn try_query(&mut self, input: &In) -> Result<Out, OracleError> {
+        if let Some(budget) = self.budget {
+            if self.queries() >= budget {
+                return Err(OracleError::OverBudget { budget: budget });
+            }
+        }
+        self.queries.set(self.queries() + 1);
+
+        let output = self.oracle.query(input);
+        if let Some(ref mut rec) = self.recorder {
+            writeln!(rec.out, "{}\t{}", (rec.input)(input), (rec.output)(&output))?;
+        }
+        Ok(output)
+    }
+
+    // Hands the oracle to code that wants one of the challenges' boxed closures, keeping a Meter
+    // to read the count from afterwards. The closure can't return an error, so it panics once
+    // the budget is used up or the transcript can't be written
+    pub fn boxed(self) -> (Box<Fn(&In) -> Out>, Meter)
+            where O: 'static, In: 'static, Out: 'static {
+        let meter = self.meter();
+        let metered = RefCell::new(self);
+        let f = move |input: &In| {
+            match metered.borrow_mut().try_query(input) {
+                Ok(output) => output,
+                Err(e) => panic!("oracle: {}", e),
+            }
+        };
+        (Box::new(f), meter)
+    }
+}
+
+impl<O, In: ?Sized, Out> Oracle<In, Result<Out, OracleError>> for Metered<O, In, Out>
+        where O: Oracle<In, Out> {
+    fn query(&mut self, input: &In) -> Result<Out, OracleError> {
+        self.try_query(input)
+    }
+}
+
+// The queries and answers a Metered wrote down, in order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transcript {
+    entries: Vec<(String, String)>,
+}
+
+impl Transcript {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Transcript, OracleError> {
+        let mut entries = Vec::new();
+        for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            let mut halves = line.splitn(2, '\t');
+            match (halves.next(), halves.next()) {
+                (Some(input), Some(output)) if !output.contains('\t') =>
+                    entries.push((input.to_string(), output.to_string())),
+                _ => return Err(OracleError::BadTranscript { line: i+1 }),
+            }
+        }
+
+        Ok(Transcript { entries: entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[(String, String)] {
+        &self.entries
+    }
+
+    // An oracle that answers from the transcript without the real one. It has to be asked the
+    // same queries in the same order, which a deterministic attack will do
+    pub fn replay<'a>(&'a self) -> Replay<'a> {
+        Replay { transcript: self, next: 0 }
+    }
+}
+
+pub struct Replay<'a> {
+    transcript: &'a Transcript,
+    next: usize,
+}
+
+impl<'a, In: ?Sized, Out> Oracle<In, Result<Out, OracleError>> for Replay<'a>
+        where In: Transcribe, Out: FromTranscript {
+    fn query(&mut self, input: &In) -> Result<Out, OracleError> {
+        let idx = self.next;
+        let &(ref recorded, ref output) = match self.transcript.entries.get(idx) {
+            Some(entry) => entry,
+            None => return Err(OracleError::EndOfTranscript { queries: self.transcript.len() }),
+        };
+        if *recorded != input.transcribe() {
+            return Err(OracleError::Diverged { query: idx });
+        }
+        self.next += 1;
+
+        Out::from_transcript(output).ok_or(OracleError::BadTranscript { line: idx+1 })
+    }
+}
+
+// What an attack cost: how many queries it made, and how many bytes (or whatever it recovers) it
+// got for them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cost {
+    pub attack: String,
+    pub queries: usize,
+    pub recovered: usize,
+}
+
+// Costs of attacks from any of the sets, for comparing them side by side. On disk it's a line per
+// attack: name, queries, and recovered, separated by tabs
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CostReport {
+    costs: Vec<Cost>,
+}
+
+impl CostReport {
+    pub fn new() -> CostReport {
+        CostReport { costs: Vec::new() }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<CostReport, OracleError> {
+        CostReport::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read<R: BufRead>(input: R) -> Result<CostReport, OracleError> {
+        let mut report = CostReport::new();
+        for (i, line) in input.lines().enumerate() {
+            let line = line?;
+            let fields = line.split('\t').collect::<Vec<&str>>();
+            let cost = match fields[..] {
+                [attack, queries, recovered] => queries.parse().ok().and_then(|q| {
+                    recovered.parse().ok().map(|r| Cost {
+                        attack: attack.to_string(),
+                        queries: q,
+                        recovered: r,
+                    })
+                }),
+                _ => None,
+            };
+            report.push(cost.ok_or(OracleError::BadTranscript { line: i+1 })?);
+        }
+
+        Ok(report)
+    }
+
+    pub fn push(&mut self, cost: Cost) {
+        self.costs.push(cost);
+    }
+
+    pub fn costs(&self) -> &[Cost] {
+        &self.costs
+    }
+
+    pub fn append_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = OpenOptions::new().create(true).append(true).open(path)?;
+        for c in self.costs.iter() {
+            writeln!(out, "{}\t{}\t{}", c.attack, c.queries, c.recovered)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CostReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.costs.iter().map(|c| c.attack.len()).max().unwrap_or(0).max(6);
+        writeln!(f, "{:<w$}  {:>10}  {:>9}  {:>10}", "attack", "queries", "recovered",
+                 "per unit", w = width)?;
+        for c in self.costs.iter() {
+            let per_unit = if c.recovered == 0 {
+                "-".to_string()
+            } else {
+                format!("{:.1}", c.queries as f64 / c.recovered as f64)
+            };
+            writeln!(f, "{:<w$}  {:>10}  {:>9}  {:>10}", c.attack, c.queries, c.recovered,
+                     per_unit, w = width)?;
+        }
+        Ok(())
+    }
+}
+
+// Adds a cost to the report file named by MCC_ORACLE_REPORT, if it's set. The challenge tests call
+// this, so running them with it set, then mcc oracle-report, compares them all
+pub fn log_cost(attack: &str, queries: usize, recovered: usize) {
+    if let Some(path) = env::var_os(REPORT_VAR) {
+        let mut report = CostReport::new();
+        report.push(Cost { attack: attack.to_string(), queries: queries, recovered: recovered });
+        if let Err(e) = report.append_to(&path) {
+            eprintln!("couldn't add to {}: {}", path.to_string_lossy(), e);
+        }
+    }
+}
+
+#[test]
+fn tst_oracle() {
+    use c02::xor_bytes;
+
+    let path = env::temp_dir().join(format!("tst_oracle_{}.txt", ::std::process::id()));
+    let xor_key = |input: &[u8]| xor_bytes(input, &vec![0x5a; input.len()]);
+
+    // Counting and budgets
+    let mut metered = Metered::new(xor_key).with_budget(3).record(&path).unwrap();
+    assert_eq!(metered.try_query(b"hi"), Ok(vec![0x32, 0x33]));
+    assert_eq!(metered.query(&b""[..]), Ok(vec![]));
+    assert_eq!(metered.remaining(), Some(1));
+    assert_eq!(metered.try_query(b"\t\n"), Ok(vec![0x53, 0x50]));
+    assert_eq!(metered.try_query(b"x"), Err(OracleError::OverBudget { budget: 3 }));
+    assert_eq!(metered.queries(), 3);
+    drop(metered);
+
+    // The transcript replays the same queries, and nothing else
+    let transcript = Transcript::load(&path).unwrap();
+    assert_eq!(transcript.len(), 3);
+    assert_eq!(transcript.entries()[0], ("6869".to_string(), "3233".to_string()));
+    let mut replay = transcript.replay();
+    let answer: Result<Vec<u8>, OracleError> = replay.query(&b"hi"[..]);
+    assert_eq!(answer, Ok(vec![0x32, 0x33]));
+    let answer: Result<Vec<u8>, OracleError> = replay.query(&b"no"[..]);
+    assert_eq!(answer, Err(OracleError::Diverged { query: 1 }));
+    let mut replay = transcript.replay();
+    for input in [&b"hi"[..], b"", b"\t\n"].iter() {
+        let expected = xor_key(input);
+        assert_eq!(replay.query(*input), Ok(expected));
+    }
+    let answer: Result<Vec<u8>, OracleError> = replay.query(&b"hi"[..]);
+    assert_eq!(answer, Err(OracleError::EndOfTranscript { queries: 3 }));
+
+    // The boxed adapter still counts after it's been given away
+    let checker: Box<Fn(&[u8]) -> bool> = Box::new(|input: &[u8]| input.len() % 2 == 0);
+    let (checker, meter) = Metered::new(checker).boxed();
+    assert!(checker(b"ab") && !checker(b"abc"));
+    assert_eq!(meter.queries(), 2);
+
+    // Costs go to disk and back
+    let _ = ::std::fs::remove_file(&path);
+    let mut report = CostReport::new();
+    report.push(Cost { attack: "c12".to_string(), queries: 139, recovered: 138 });
+    report.push(Cost { attack: "c51 stream".to_string(), queries: 5000, recovered: 0 });
+    report.append_to(&path).unwrap();
+    let loaded = CostReport::load(&path).unwrap();
+    assert_eq!(loaded, report);
+    let table = loaded.to_string();
+    assert!(table.lines().nth(1).unwrap().ends_with("1.0"));
+    assert!(table.lines().nth(2).unwrap().ends_with("-"));
+    let _ = ::std::fs::remove_file(&path);
+}
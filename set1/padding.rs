@@ -0,0 +1,332 @@
+use rand::{self, Rng};
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::u8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaddingError {
+    // Padded data always has at least one block
+    Empty,
+    // Padded data is always whole blocks
+    Unaligned { len: usize, block_size: usize },
+    // The length byte at the end is 0 or bigger than a block
+    BadLength { pad_len: usize },
+    // The length is fine, but the bytes before it aren't what the scheme puts there
+    BadBytes,
+    // ISO/IEC 7816-4 only: there's no 0x80 before the trailing zeros
+    NoMarker,
+}
+
+impl fmt::Display for PaddingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PaddingError::Empty => write!(f, "there's nothing to unpad"),
+            PaddingError::Unaligned { len, block_size } =>
+                write!(f, "{} bytes isn't a whole number of {}-byte blocks", len, block_size),
+            PaddingError::BadLength { pad_len } =>
+                write!(f, "padding length {} is invalid", pad_len),
+            PaddingError::BadBytes => write!(f, "padding bytes are wrong"),
+            PaddingError::NoMarker => write!(f, "no 0x80 marker before the padding"),
+        }
+    }
+}
+
+impl Error for PaddingError { }
+
+// A way of filling out the last block. unpad returns the unpadded data without copying it
+pub trait Padding {
+    fn pad(&self, bytes: &[u8], block_size: usize) -> Vec<u8>;
+    fn unpad<'a>(&self, bytes: &'a [u8], block_size: usize) -> Result<&'a [u8], PaddingError>;
+}
+
+// 1 to block_size copies of the number of padding bytes. Unpadding looks at the whole last block
+// no matter what, and doesn't branch on anything in it until the very end, so how long it takes
+// says nothing about where the padding went wrong
+#[derive(Clone, Copy, Debug)]
+pub struct Pkcs7;
+
+// Zeros, then the number of padding bytes
+#[derive(Clone, Copy, Debug)]
+pub struct AnsiX923;
+
+// Random bytes, then the number of padding bytes. Only the length is checked
+#[derive(Clone, Copy, Debug)]
+pub struct Iso10126;
+
+// 0x80, then zeros. Works for any block size, since there's no length byte
+#[derive(Clone, Copy, Debug)]
+pub struct Iso7816;
+
+// Zeros, and none at all if the data is already whole blocks. Unpadding strips trailing zeros, so
+// data that ends in zeros doesn't survive. Only for when the length is known some other way
+#[derive(Clone, Copy, Debug)]
+pub struct ZeroPadding;
+
+// Schemes that end in a length byte can't do blocks bigger than a byte can count
+fn check_block_size(block_size: usize) {
+    assert!(block_size >= 1 && block_size <= u8::MAX as usize,
+            "Block size has to be from 1 to {}", u8::MAX);
+}
+
+// The rest can do blocks of any size but 0
+fn check_nonzero_block_size(block_size: usize) {
+    assert!(block_size >= 1, "Block size can't be 0");
+}
+
+fn check_shape(bytes: &[u8], block_size: usize) -> Result<(), PaddingError> {
+    if bytes.is_empty() {
+        Err(PaddingError::Empty)
+    } else if bytes.len() % block_size != 0 {
+        Err(PaddingError::Unaligned { len: bytes.len(), block_size: block_size })
+    } else {
+        Ok(())
+    }
+}
+
+// How many bytes to add so there's at least one
+fn pad_len(len: usize, block_size: usize) -> usize {
+    block_size - len % block_size
+}
+
+// Reads the length byte and checks it's between 1 and block_size
+fn length_byte(bytes: &[u8], block_size: usize) -> Result<usize, PaddingError> {
+    check_shape(bytes, block_size)?;
+    let pad_len = bytes[bytes.len()-1] as usize;
+    if pad_len == 0 || pad_len > block_size {
+        return Err(PaddingError::BadLength { pad_len: pad_len });
+    }
+    Ok(pad_len)
+}
+
+// 0xff if a < b, otherwise 0, without a branch. Both have to fit in one bit less than a usize,
+// which lengths do
+fn ct_lt(a: usize, b: usize) -> u8 {
+    let borrow = a.wrapping_sub(b) >> (8 * mem::size_of::<usize>() - 1);
+    (borrow as u8).wrapping_mul(0xff)
+}
+
+impl Padding for Pkcs7 {
+    fn pad(&self, bytes: &[u8], block_size: usize) -> Vec<u8> {
+        check_block_size(block_size);
+        let n = pad_len(bytes.len(), block_size);
+        let mut out = bytes.to_vec();
+        out.extend(vec![n as u8; n]);
+        out
+    }
+
+    fn unpad<'a>(&self, bytes: &'a [u8], block_size: usize) -> Result<&'a [u8], PaddingError> {
+        check_block_size(block_size);
+        check_shape(bytes, block_size)?;
+        let last_block = &bytes[bytes.len()-block_size..];
+        let pad_len = last_block[block_size-1] as usize;
+
+        let bad_len = ct_lt(pad_len, 1) | ct_lt(block_size, pad_len);
+        let mut bad_bytes = 0u8;
+        for (i, &b) in last_block.iter().rev().enumerate() {
+            bad_bytes |= ct_lt(i, pad_len) & (b ^ pad_len as u8);
+        }
+
+        if bad_len != 0 {
+            Err(PaddingError::BadLength { pad_len: pad_len })
+        } else if bad_bytes != 0 {
+            Err(PaddingError::BadBytes)
+        } else {
+            Ok(&bytes[..bytes.len()-pad_len])
+        }
+    }
+}
+
+impl Padding for AnsiX923 {
+    fn pad(&self, bytes: &[u8], block_size: usize) -> Vec<u8> {
+        check_block_size(block_size);
+        let n = pad_len(bytes.len(), block_size);
+        let mut out = bytes.to_vec();
+        out.extend(vec![0u8; n-1]);
+        out.push(n as u8);
+        out
+    }
+
+    fn unpad<'a>(&self, bytes: &'a [u8], block_size: usize) -> Result<&'a [u8], PaddingError> {
+        check_block_size(block_size);
+        let pad_len = length_byte(bytes, block_size)?;
+        let body_len = bytes.len() - pad_len;
+        if bytes[body_len..bytes.len()-1].iter().any(|&b| b != 0) {
+            return Err(PaddingError::BadBytes);
+        }
+        Ok(&bytes[..body_len])
+    }
+}
+
+impl Padding for Iso10126 {
+    fn pad(&self, bytes: &[u8], block_size: usize) -> Vec<u8> {
+        check_block_size(block_size);
+        let n = pad_len(bytes.len(), block_size);
+        let mut out = bytes.to_vec();
+        out.extend(rand::thread_rng().gen_iter::<u8>().take(n-1));
+        out.push(n as u8);
+        out
+    }
+
+    fn unpad<'a>(&self, bytes: &'a [u8], block_size: usize) -> Result<&'a [u8], PaddingError> {
+        check_block_size(block_size);
+        let pad_len = length_byte(bytes, block_size)?;
+        Ok(&bytes[..bytes.len()-pad_len])
+    }
+}
+
+impl Padding for Iso7816 {
+    fn pad(&self, bytes: &[u8], block_size: usize) -> Vec<u8> {
+        check_nonzero_block_size(block_size);
+        let n = pad_len(bytes.len(), block_size);
+        let mut out = bytes.to_vec();
+        out.push(0x80);
+        out.extend(vec![0u8; n-1]);
+        out
+    }
+
+    fn unpad<'a>(&self, bytes: &'a [u8], block_size: usize) -> Result<&'a [u8], PaddingError> {
+        check_nonzero_block_size(block_size);
+        check_shape(bytes, block_size)?;
+        let last_block_start = bytes.len() - block_size;
+        let mut i = bytes.len() - 1;
+        while bytes[i] == 0 && i > last_block_start {
+            i -= 1;
+        }
+        if bytes[i] != 0x80 {
+            return Err(PaddingError::NoMarker);
+        }
+        Ok(&bytes[..i])
+    }
+}
+
+impl Padding for ZeroPadding {
+    fn pad(&self, bytes: &[u8], block_size: usize) -> Vec<u8> {
+        check_nonzero_block_size(block_size);
+        let mut out = bytes.to_vec();
+        if bytes.len() % block_size != 0 {
+            out.extend(vec![0u8; pad_len(bytes.len(), block_size)]);
+        }
+        out
+    }
+
+    fn unpad<'a>(&self, bytes: &'a [u8], block_size: usize) -> Result<&'a [u8], PaddingError> {
+        check_nonzero_block_size(block_size);
+        check_shape(bytes, block_size)?;
+        let last_block_start = bytes.len() - block_size;
+        let mut end = bytes.len();
+        while end > last_block_start + 1 && bytes[end-1] == 0 {
+            end -= 1;
+        }
+        Ok(&bytes[..end])
+    }
+}
+
+#[test]
+fn tst_padding() {
+    use std::panic;
+
+    let text = b"YELLOW SUBMARINE";
+    let schemes: Vec<(Box<Padding>, &[u8])> = vec![
+        (Box::new(Pkcs7), b"YELLOW SUBMARINE\x04\x04\x04\x04"),
+        (Box::new(AnsiX923), b"YELLOW SUBMARINE\x00\x00\x00\x04"),
+        (Box::new(Iso7816), b"YELLOW SUBMARINE\x80\x00\x00\x00"),
+        (Box::new(ZeroPadding), b"YELLOW SUBMARINE\x00\x00\x00\x00"),
+    ];
+    for &(ref scheme, padded) in schemes.iter() {
+        assert_eq!(scheme.pad(text, 20), padded);
+        assert_eq!(scheme.unpad(padded, 20), Ok(&text[..]));
+        assert_eq!(scheme.unpad(b"", 20), Err(PaddingError::Empty));
+        assert_eq!(scheme.unpad(&padded[1..], 20),
+                   Err(PaddingError::Unaligned { len: 19, block_size: 20 }));
+    }
+
+    // Everything but zero padding adds a whole block to aligned data, and takes it off again
+    for scheme in [&Pkcs7 as &Padding, &AnsiX923, &Iso10126, &Iso7816].iter() {
+        let padded = scheme.pad(text, 16);
+        assert_eq!(padded.len(), 32);
+        assert_eq!(scheme.unpad(&padded, 16), Ok(&text[..]));
+        assert_eq!(scheme.unpad(&scheme.pad(b"", 8), 8), Ok(&b""[..]));
+    }
+    assert_eq!(ZeroPadding.pad(text, 16), text);
+    let padded = Iso10126.pad(b"YELLOW", 8);
+    assert_eq!(padded[7], 2);
+    assert_eq!(Iso10126.unpad(&padded, 8), Ok(&b"YELLOW"[..]));
+
+    assert_eq!(Pkcs7.unpad(b"ICE ICE BABY\x05\x05\x05\x05", 16), Err(PaddingError::BadBytes));
+    assert_eq!(Pkcs7.unpad(b"ICE ICE BABY\x01\x02\x03\x04", 16), Err(PaddingError::BadBytes));
+    assert_eq!(Pkcs7.unpad(b"ICE ICE BABY\x04\x04\x04\x00", 16),
+               Err(PaddingError::BadLength { pad_len: 0 }));
+    assert_eq!(Pkcs7.unpad(b"ICE ICE BABY\x04\x04\x04\x11", 16),
+               Err(PaddingError::BadLength { pad_len: 17 }));
+    assert_eq!(AnsiX923.unpad(b"ICE ICE BABY\x00\x01\x00\x04", 16), Err(PaddingError::BadBytes));
+    assert_eq!(Iso7816.unpad(b"ICE ICE BABY\x00\x00\x00\x00", 16), Err(PaddingError::NoMarker));
+
+    // A block size of 0 is a mistake, whatever the scheme
+    let schemes: [&Padding; 5] = [&Pkcs7, &AnsiX923, &Iso10126, &Iso7816, &ZeroPadding];
+    for &scheme in schemes.iter() {
+        let scheme = panic::AssertUnwindSafe(scheme);
+        assert!(panic::catch_unwind(|| scheme.pad(text, 0)).is_err());
+        assert!(panic::catch_unwind(|| scheme.unpad(text, 0).is_ok()).is_err());
+    }
+
+    // Unpadding doesn't take wildly longer on some inputs than others. A leaky check would be
+    // off by the whole pad, 255 times over; see tst_padding_timing for the tight version
+    let best = pkcs7_unpad_timings(20, 100);
+    let fastest = *best.iter().min().unwrap() as f64;
+    let slowest = *best.iter().max().unwrap() as f64;
+    assert!(slowest / fastest < 4.0, "Timings vary too much: {:?}", best);
+}
+
+// How long Pkcs7.unpad takes on a 1-byte pad, a 255-byte pad, a bad length byte, and a mismatch
+// in the first padding byte, in that order. Each case is timed reps times in a row, and the best
+// of the rounds is kept, interleaved so noise hits every case alike
+#[cfg(test)]
+fn pkcs7_unpad_timings(rounds: usize, reps: usize) -> [u64; 4] {
+    use std::hint::black_box;
+    use std::time::Instant;
+    use std::u64;
+
+    let block_size = 255;
+    let body = vec![b'A'; 2*block_size];
+    let short_pad = Pkcs7.pad(&body[1..], block_size);
+    let long_pad = Pkcs7.pad(&body, block_size);
+    let mut bad_len = long_pad.clone();
+    *bad_len.last_mut().unwrap() = 0;
+    let mut bad_first = long_pad.clone();
+    bad_first[2*block_size] ^= 1;
+    let cases = [&short_pad, &long_pad, &bad_len, &bad_first];
+
+    let mut best = [u64::MAX; 4];
+    let mut n_ok = 0;
+    for _ in 0..rounds {
+        for (i, case) in cases.iter().enumerate() {
+            let start = Instant::now();
+            for _ in 0..reps {
+                // Otherwise the optimizer sees the same input every time and only unpads it once
+                if Pkcs7.unpad(black_box(case), black_box(block_size)).is_ok() {
+                    n_ok += 1;
+                }
+            }
+            let elapsed = start.elapsed();
+            let nanos = elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64;
+            best[i] = best[i].min(nanos);
+        }
+    }
+    assert_eq!(n_ok, 2 * rounds * reps);
+    best
+}
+
+// PKCS#7 takes as long on a 1-byte pad as a 255-byte one, and as long to reject a bad length byte
+// as a mismatch in the first padding byte. tst_padding checks the same thing loosely enough for a
+// debug build on a busy machine; this is the tight version, which depends on the machine and
+// whatever else it's doing, so it only runs when asked for, with
+// cargo test --release -- --ignored
+#[ignore]
+#[test]
+fn tst_padding_timing() {
+    let best = pkcs7_unpad_timings(30, 300);
+    let fastest = *best.iter().min().unwrap() as f64;
+    let slowest = *best.iter().max().unwrap() as f64;
+    assert!(slowest / fastest < 1.5, "Timings vary too much: {:?}", best);
+}
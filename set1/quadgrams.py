@@ -0,0 +1,112 @@
+#!/usr/bin/env python3
+# Regenerates quadgrams.txt, the English model behind NgramModel::english().
+#
+# The corpus is the prose that ships with CPython 3.11: the language reference topics in
+# pydoc_data, and the docstrings of the pure-Python standard library. Both are covered by the
+# Python Software Foundation License Version 2 (https://docs.python.org/3/license.html), which
+# allows redistributing derived works like these counts. Code samples, tables, and markup are
+# dropped, so what's left is mostly sentences.
+#
+# Symbols and output match scoring.rs: letters are case-folded, runs of whitespace collapse to
+# one space (_), other printable ASCII is #, and an n-gram touching anything else isn't counted.
+# The output is sorted like NgramModel::save, most common first.
+#
+# usage: python3 quadgrams.py > quadgrams.txt
+
+import ast
+import os
+import re
+import sys
+import sysconfig
+from collections import Counter
+
+from pydoc_data.topics import topics
+
+N = 4
+
+
+def is_prose(line):
+    stripped = line.strip()
+    if not stripped:
+        return True
+    # Indented lines are code samples or grammar rules in the topics, and doctests or argument
+    # tables in docstrings
+    if line.startswith('   ') or stripped.startswith(('>>>', '...', '|', '+-', '..')):
+        return False
+    letters = sum(c.isalpha() for c in stripped)
+    return letters >= 0.7 * len(stripped.replace(' ', ''))
+
+
+def prose(text):
+    text = re.sub(r'[*=\-~^]{4,}', ' ', text)
+    return '\n'.join(line for line in text.splitlines() if is_prose(line))
+
+
+def docstrings():
+    stdlib = sysconfig.get_paths()['stdlib']
+    for root, dirs, files in os.walk(stdlib):
+        dirs[:] = sorted(d for d in dirs
+                         if d not in ('test', 'tests', 'idle_test', 'site-packages')
+                         and not d.startswith('.'))
+        for name in sorted(files):
+            if not name.endswith('.py'):
+                continue
+            try:
+                with open(os.path.join(root, name), encoding='utf-8') as f:
+                    tree = ast.parse(f.read())
+            except (SyntaxError, UnicodeDecodeError, ValueError):
+                continue
+            for node in ast.walk(tree):
+                if isinstance(node, (ast.Module, ast.ClassDef, ast.FunctionDef,
+                                     ast.AsyncFunctionDef)):
+                    doc = ast.get_docstring(node)
+                    if doc:
+                        yield doc
+
+
+def symbols(text):
+    out = []
+    for b in text.encode('utf-8'):
+        if ord('a') <= b <= ord('z'):
+            s = b - ord('a')
+        elif ord('A') <= b <= ord('Z'):
+            s = b - ord('A')
+        elif b in b' \t\n\r':
+            s = 26
+            if out and out[-1] == 26:
+                continue
+        elif ord('!') <= b <= ord('~'):
+            s = 27
+        else:
+            s = None
+        out.append(s)
+    return out
+
+
+def main():
+    if sys.version_info[:2] != (3, 11):
+        sys.stderr.write('warning: quadgrams.txt was made with Python 3.11; other versions '
+                         'ship different docs\n')
+
+    corpus = [prose(topics[k]) for k in sorted(topics)]
+    corpus.extend(prose(doc) for doc in docstrings())
+    syms = symbols('\n'.join(corpus))
+
+    counts = Counter()
+    for i in range(len(syms) - N + 1):
+        window = syms[i:i+N]
+        if None not in window:
+            counts[tuple(window)] += 1
+
+    chars = 'abcdefghijklmnopqrstuvwxyz_#'
+    def index(gram):
+        idx = 0
+        for s in gram:
+            idx = idx * 28 + s
+        return idx
+    for gram, count in sorted(counts.items(), key=lambda gc: (-gc[1], index(gc[0]))):
+        print('%s %d' % (''.join(chars[s] for s in gram), count))
+
+
+if __name__ == '__main__':
+    main()
@@ -0,0 +1,292 @@
+use oracle::{FromTranscript, Oracle, OracleError, Transcribe};
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Nothing in the challenges comes close; it's only there so a bad length can't eat all the memory
+const MAX_FRAME: usize = 1 << 24;
+
+// How long the listener waits for a connection before it checks whether it's been stopped
+const ACCEPT_POLL: Duration = Duration::from_millis(5);
+
+// Frames are a 4-byte big-endian length, then that many bytes. It goes out in one write, or
+// Nagle holds the payload back until the length gets acked
+fn write_frame<W: Write>(out: &mut W, payload: &[u8]) -> io::Result<()> {
+    if payload.len() > MAX_FRAME {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "frame is too long"));
+    }
+    let len = payload.len() as u32;
+    let mut frame = vec![(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+    frame.extend_from_slice(payload);
+    out.write_all(&frame)?;
+    out.flush()
+}
+
+// None on a clean end of stream, before any of the length
+fn read_frame<R: Read>(input: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len_bytes = [0u8; 4];
+    let mut got = 0;
+    while got < 4 {
+        match input.read(&mut len_bytes[got..])? {
+            0 if got == 0 => return Ok(None),
+            0 => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "cut off in a length")),
+            n => got += n,
+        }
+    }
+    let len = len_bytes.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+    if len > MAX_FRAME {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame is too long"));
+    }
+    let mut payload = vec![0u8; len];
+    input.read_exact(&mut payload)?;
+    Ok(Some(payload))
+}
+
+// Serves an oracle on localhost. Each query is a frame holding the input, and each answer is a
+// frame holding the output, both written the way a transcript writes them. The oracle runs on the
+// server's threads, so it has to be Send; a boxed one needs + Send in its type. Connections take
+// turns with it, so it doesn't have to be Sync. The server stops when it's dropped: the port is
+// closed, and clients that are still connected get hung up on
+pub struct OracleServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    listener: Option<JoinHandle<()>>,
+    connections: Arc<Mutex<Vec<Connection>>>,
+}
+
+// A client's stream, so it can be shut down from outside, and the thread answering it
+struct Connection {
+    stream: TcpStream,
+    handle: JoinHandle<()>,
+}
+
+impl OracleServer {
+    // Port 0 gets whatever port is free
+    pub fn spawn<F, Out>(mut oracle: F, port: u16) -> io::Result<OracleServer>
+            where F: FnMut(&[u8]) -> Out + Send + 'static, Out: Transcribe + 'static {
+        OracleServer::spawn_with(move |input: &Vec<u8>| oracle(input), port,
+                                 Vec::from_transcript, Out::transcribe)
+    }
+
+    // The same, for oracles that take something other than bytes. input parses a query, and
+    // output writes an answer; In::from_transcript and Out::transcribe do for anything that has
+    // them, and the RSA challenges' Ints can bring their own
+    pub fn spawn_with<F, In, Out>(oracle: F, port: u16, input: fn(&str) -> Option<In>,
+                                  output: fn(&Out) -> String) -> io::Result<OracleServer>
+            where F: FnMut(&In) -> Out + Send + 'static, In: 'static, Out: 'static {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let addr = listener.local_addr()?;
+        // A blocking accept would never see the stop
+        listener.set_nonblocking(true)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let connections = Arc::new(Mutex::new(Vec::new()));
+        let oracle = Arc::new(Mutex::new(oracle));
+
+        let (stopped, tracked) = (stop.clone(), connections.clone());
+        let handle = thread::spawn(move || {
+            while !stopped.load(Ordering::SeqCst) {
+                let mut stream = match listener.accept() {
+                    Ok((s, _)) => s,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_POLL);
+                        continue;
+                    }
+                    Err(_) => continue,
+                };
+                let watched = match stream.set_nonblocking(false)
+                                          .and_then(|_| stream.try_clone()) {
+                    Ok(s) => s,
+                    Err(_) => continue,
+                };
+                let _ = stream.set_nodelay(true);
+                let oracle = oracle.clone();
+                let handle = thread::spawn(move || {
+                    // A client that hangs up or sends junk only loses its own connection
+                    while let Ok(Some(frame)) = read_frame(&mut stream) {
+                        let query = match String::from_utf8(frame).ok().and_then(|s| input(&s)) {
+                            Some(query) => query,
+                            None => break,
+                        };
+                        let answer = output(&(&mut *oracle.lock().unwrap())(&query));
+                        if write_frame(&mut stream, answer.as_bytes()).is_err() {
+                            break;
+                        }
+                    }
+                });
+                let mut tracked = tracked.lock().unwrap();
+                tracked.retain(|c: &Connection| !c.handle.is_finished());
+                tracked.push(Connection { stream: watched, handle: handle });
+            }
+        });
+
+        Ok(OracleServer {
+            addr: addr,
+            stop: stop,
+            listener: Some(handle),
+            connections: connections,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+}
+
+impl Drop for OracleServer {
+    // Doesn't return until the port is closed and every connection's thread is done, so nothing
+    // can connect after, and the oracle isn't called again
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.listener.take() {
+            let _ = handle.join();
+        }
+        // The listener's gone, so nothing else gets added
+        let connections = self.connections.lock().unwrap().drain(..).collect::<Vec<_>>();
+        for connection in connections {
+            let _ = connection.stream.shutdown(Shutdown::Both);
+            let _ = connection.handle.join();
+        }
+    }
+}
+
+// One connection to an OracleServer, kept open across queries
+pub struct OracleClient {
+    stream: TcpStream,
+}
+
+impl OracleClient {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<OracleClient> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(OracleClient { stream: stream })
+    }
+
+    pub fn try_query<Out: FromTranscript>(&mut self, input: &[u8]) -> Result<Out, OracleError> {
+        self.query_with(input, <[u8]>::transcribe, Out::from_transcript)
+    }
+
+    // The other side of OracleServer::spawn_with
+    pub fn query_with<In: ?Sized, Out>(&mut self, query: &In, input: fn(&In) -> String,
+                                       output: fn(&str) -> Option<Out>)
+                                       -> Result<Out, OracleError> {
+        write_frame(&mut self.stream, input(query).as_bytes())?;
+        let reply = read_frame(&mut self.stream)?;
+        let reply = reply.ok_or(OracleError::Io(io::ErrorKind::UnexpectedEof))?;
+        String::from_utf8(reply).ok()
+                                .and_then(|s| output(&s))
+                                .ok_or(OracleError::BadReply)
+    }
+
+    // The same closure type the challenges use, so an attack runs unchanged against a target in
+    // another process. The closure can't return an error, so it panics if the connection does
+    pub fn boxed<Out: FromTranscript + 'static>(self) -> Box<Fn(&[u8]) -> Out> {
+        let client = RefCell::new(self);
+        Box::new(move |input: &[u8]| {
+            match client.borrow_mut().try_query(input) {
+                Ok(output) => output,
+                Err(e) => panic!("remote oracle: {}", e),
+            }
+        })
+    }
+
+    // For attacks that query from more than one thread, like challenge 17's. They take turns
+    // with the connection
+    pub fn boxed_sync<Out>(self) -> Box<Fn(&[u8]) -> Out + Send + Sync>
+            where Out: FromTranscript + 'static {
+        self.boxed_with(<[u8]>::transcribe, Out::from_transcript)
+    }
+
+    // The thread-safe closure for any input and output, written and parsed with the given
+    // functions
+    pub fn boxed_with<In: ?Sized, Out>(self, input: fn(&In) -> String,
+                                       output: fn(&str) -> Option<Out>)
+                                       -> Box<Fn(&In) -> Out + Send + Sync>
+            where In: 'static, Out: 'static {
+        let client = Mutex::new(self);
+        Box::new(move |query: &In| {
+            match client.lock().unwrap().query_with(query, input, output) {
+                Ok(answer) => answer,
+                Err(e) => panic!("remote oracle: {}", e),
+            }
+        })
+    }
+}
+
+impl Drop for OracleClient {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+impl<In, Out> Oracle<In, Result<Out, OracleError>> for OracleClient
+        where In: Transcribe + ?Sized, Out: FromTranscript {
+    fn query(&mut self, input: &In) -> Result<Out, OracleError> {
+        self.query_with(input, In::transcribe, Out::from_transcript)
+    }
+}
+
+#[test]
+fn tst_remote() {
+    use c02::xor_bytes;
+
+    // Bytes, bools, and counts all make it across
+    let server = OracleServer::spawn(|input: &[u8]| xor_bytes(input, &vec![0x20; input.len()]), 0)
+                             .unwrap();
+    assert!(server.port() != 0);
+    let shout = OracleClient::connect(server.addr()).unwrap().boxed::<Vec<u8>>();
+    assert_eq!(shout(b"hello"), b"HELLO");
+    assert_eq!(shout(b""), b"");
+
+    let even = OracleServer::spawn(|input: &[u8]| input.len() % 2 == 0, 0).unwrap();
+    let mut client = OracleClient::connect(even.addr()).unwrap();
+    assert_eq!(client.query(&b"ab"[..]), Ok(true));
+    assert_eq!(client.try_query::<bool>(b"abc"), Ok(false));
+    // Clients can be connected at the same time, and a reply of the wrong type is an error
+    let mut other = OracleClient::connect(even.addr()).unwrap();
+    assert_eq!(other.try_query::<bool>(&[0; 10]), Ok(true));
+    assert_eq!(client.try_query::<Vec<u8>>(b""), Err(OracleError::BadReply));
+
+    let counter = OracleServer::spawn(|input: &[u8]| input.len(), 0).unwrap();
+    let mut client = OracleClient::connect(counter.addr()).unwrap();
+    assert_eq!(client.try_query::<usize>(&[7; 100000]), Ok(100000));
+
+    // Inputs other than bytes go across with their own format, and the Send + Sync closures can
+    // be shared between threads
+    let parse = |s: &str| s.parse::<u64>().ok();
+    let halver = OracleServer::spawn_with(|n: &u64| n / 2, 0, parse, u64::to_string).unwrap();
+    let halve = OracleClient::connect(halver.addr()).unwrap().boxed_with(u64::to_string, parse);
+    assert_eq!(halve(&1000000000001), 500000000000);
+    let halve = Arc::new(halve);
+    let shared = halve.clone();
+    assert_eq!(thread::spawn(move || shared(&7)).join().unwrap(), 3);
+    assert_eq!(halve(&8), 4);
+
+    let count = Arc::new(OracleClient::connect(counter.addr()).unwrap().boxed_sync::<usize>());
+    let threads = (0..4).map(|i| {
+        let count = count.clone();
+        thread::spawn(move || (0..50).all(|_| count(&vec![0; i]) == i))
+    }).collect::<Vec<_>>();
+    assert!(threads.into_iter().all(|t| t.join().unwrap()));
+
+    // A dropped server has stopped taking connections by the time drop returns, and has hung up
+    // on the clients it had
+    let addr = counter.addr();
+    drop(counter);
+    let refused = OracleClient::connect(addr).and_then(|mut c| {
+        c.try_query::<usize>(b"x").map_err(|_| io::Error::new(io::ErrorKind::Other, "closed"))
+    });
+    assert!(refused.is_err());
+    assert!(client.try_query::<usize>(b"x").is_err());
+
+    // Frames too long to read are refused on the way out too
+    let too_long = write_frame(&mut Vec::new(), &vec![0; MAX_FRAME + 1]);
+    assert_eq!(too_long.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
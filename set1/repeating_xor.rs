@@ -0,0 +1,298 @@
+use c03::{cmp_scores, rank_keys};
+use c06::hamming_dist;
+use scoring::{NgramModel, Scorer};
+use std::collections::HashMap;
+
+// The Hamming estimator compares every pair of chunks, so it only looks at this many of them
+const MAX_HAMMING_CHUNKS: usize = 128;
+// Length of the repeated substrings the Kasiski estimator looks for
+const KASISKI_LEN: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeySizeEstimator {
+    // Average bitwise Hamming distance per byte between every pair of key-sized chunks. Bytes
+    // XORed with the same key byte are closer together than random ones
+    Hamming,
+    // Average index of coincidence of the byte values in each column. Columns XORed with a single
+    // byte keep the plaintext's skewed distribution; anything else looks closer to uniform
+    Coincidence,
+    // How much more often than chance the distances between repeated trigrams are multiples of
+    // the key size
+    Kasiski,
+}
+
+impl KeySizeEstimator {
+    // Scores every key size in [min_size, max_size] that the ciphertext has room for and returns
+    // (key_size, score), best first. Lower scores are better for every estimator
+    pub fn rank_sizes(&self, ciphertext: &[u8], min_size: usize,
+                      max_size: usize) -> Vec<(usize, f64)> {
+        // Kasiski distances are shared by every size, so only find them once
+        let distances = match *self {
+            KeySizeEstimator::Kasiski => repeat_distances(ciphertext),
+            _ => Vec::new(),
+        };
+
+        let mut out = Vec::new();
+        for size in min_size..(max_size+1) {
+            let score = match *self {
+                KeySizeEstimator::Hamming => hamming_score(ciphertext, size),
+                KeySizeEstimator::Coincidence => coincidence_score(ciphertext, size),
+                KeySizeEstimator::Kasiski => kasiski_score(&distances, size),
+            };
+            if let Some(s) = score {
+                out.push((size, s));
+            }
+        }
+        // Stable, so ties go to the smaller size
+        out.sort_by(|&(_, a), &(_, b)| cmp_scores(a, b));
+
+        out
+    }
+}
+
+// Needs at least two full chunks
+fn hamming_score(ciphertext: &[u8], size: usize) -> Option<f64> {
+    let chunks = ciphertext.chunks(size).filter(|c| c.len() == size)
+                           .take(MAX_HAMMING_CHUNKS).collect::<Vec<&[u8]>>();
+    if chunks.len() < 2 {
+        return None;
+    }
+
+    let mut total = 0u64;
+    let mut n_pairs = 0u64;
+    for i in 0..chunks.len() {
+        for j in (i+1)..chunks.len() {
+            total += hamming_dist(chunks[i], chunks[j]) as u64;
+            n_pairs += 1;
+        }
+    }
+
+    Some(total as f64 / (n_pairs * size as u64) as f64)
+}
+
+// Needs at least two bytes in every column. The index is scaled by 256 so uniformly random bytes
+// come out near 1, then negated so that lower is better
+fn coincidence_score(ciphertext: &[u8], size: usize) -> Option<f64> {
+    if ciphertext.len() < 2*size {
+        return None;
+    }
+
+    let mut total = 0f64;
+    for col in 0..size {
+        let mut counts = [0u64; 256];
+        let mut n = 0u64;
+        for &b in ciphertext[col..].iter().step_by(size) {
+            counts[b as usize] += 1;
+            n += 1;
+        }
+        let pairs = counts.iter().fold(0u64, |acc, &c| acc + c*c.saturating_sub(1));
+        total += 256f64 * (pairs as f64) / ((n * (n-1)) as f64);
+    }
+
+    Some(-total / size as f64)
+}
+
+// Distances between consecutive occurrences of every repeated KASISKI_LEN-byte substring
+fn repeat_distances(ciphertext: &[u8]) -> Vec<usize> {
+    let mut last_seen: HashMap<&[u8], usize> = HashMap::new();
+    let mut out = Vec::new();
+    for (i, window) in ciphertext.windows(KASISKI_LEN).enumerate() {
+        if let Some(prev) = last_seen.insert(window, i) {
+            out.push(i - prev);
+        }
+    }
+
+    out
+}
+
+// A key of size k divides about 1/k of random distances, so the fraction that it divides is
+// scaled by k. No repeats means no information, and every size gets the same score
+fn kasiski_score(distances: &[usize], size: usize) -> Option<f64> {
+    if distances.is_empty() {
+        return Some(0f64);
+    }
+    let n_divisible = distances.iter().filter(|&&d| d % size == 0).count();
+
+    Some(-(n_divisible as f64) * (size as f64) / (distances.len() as f64))
+}
+
+// The shortest key that repeats to make this one, e.g., "ICE" for "ICEICE"
+fn shortest_period(key: &[u8]) -> &[u8] {
+    for p in 1..key.len() {
+        if key.len() % p == 0 && (p..key.len()).all(|i| key[i] == key[i % p]) {
+            return &key[..p];
+        }
+    }
+
+    key
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RepeatingXorCandidate {
+    pub key: Vec<u8>,
+    pub plaintext: Vec<u8>,
+    pub score: f64,
+}
+
+// Breaks repeating-key XOR: the estimators propose key sizes, each proposed size is broken one
+// column at a time, and the resulting plaintexts are ranked with the scorer. Nothing assumes the
+// plaintext is text; pass a scorer that knows what the plaintext should look like
+pub struct RepeatingXorBreaker<S: Scorer> {
+    scorer: S,
+    min_key_size: usize,
+    max_key_size: usize,
+    estimators: Vec<KeySizeEstimator>,
+    // How many of each estimator's best sizes get broken
+    sizes_per_estimator: usize,
+}
+
+impl RepeatingXorBreaker<NgramModel> {
+    // Scores plaintexts by their letter frequencies, which is what English needs. An n-gram model
+    // of higher order won't work here, since a column isn't contiguous text
+    pub fn english() -> RepeatingXorBreaker<NgramModel> {
+        RepeatingXorBreaker::new(NgramModel::english().marginal(1))
+    }
+}
+
+impl<S: Scorer> RepeatingXorBreaker<S> {
+    pub fn new(scorer: S) -> RepeatingXorBreaker<S> {
+        RepeatingXorBreaker {
+            scorer: scorer,
+            min_key_size: 1,
+            max_key_size: 40,
+            estimators: vec![KeySizeEstimator::Hamming, KeySizeEstimator::Coincidence,
+                             KeySizeEstimator::Kasiski],
+            sizes_per_estimator: 5,
+        }
+    }
+
+    // Both bounds are inclusive
+    pub fn key_sizes(mut self, min_size: usize, max_size: usize) -> RepeatingXorBreaker<S> {
+        assert!(min_size >= 1 && min_size <= max_size, "Bad key size range!");
+        self.min_key_size = min_size;
+        self.max_key_size = max_size;
+        self
+    }
+
+    pub fn estimators(mut self, estimators: &[KeySizeEstimator]) -> RepeatingXorBreaker<S> {
+        assert!(!estimators.is_empty(), "Need at least one key size estimator!");
+        self.estimators = estimators.to_vec();
+        self
+    }
+
+    pub fn sizes_per_estimator(mut self, n: usize) -> RepeatingXorBreaker<S> {
+        assert!(n >= 1, "Need to try at least one key size per estimator!");
+        self.sizes_per_estimator = n;
+        self
+    }
+
+    // The union of every estimator's best sizes, in no particular order
+    pub fn candidate_key_sizes(&self, ciphertext: &[u8]) -> Vec<usize> {
+        let max_size = self.max_key_size.min(ciphertext.len());
+        let mut sizes: Vec<usize> = Vec::new();
+        for estimator in &self.estimators {
+            let ranked = estimator.rank_sizes(ciphertext, self.min_key_size, max_size);
+            for &(size, _) in ranked.iter().take(self.sizes_per_estimator) {
+                if !sizes.contains(&size) {
+                    sizes.push(size);
+                }
+            }
+        }
+
+        sizes
+    }
+
+    // Picks the best byte for every column independently. A size of 0, or one longer than the
+    // ciphertext, leaves columns with nothing to go on, so it gives an empty candidate that scores
+    // worse than anything else
+    pub fn break_with_key_size(&self, ciphertext: &[u8], size: usize) -> RepeatingXorCandidate {
+        if size == 0 || size > ciphertext.len() {
+            return RepeatingXorCandidate {
+                key: Vec::new(),
+                plaintext: Vec::new(),
+                score: f64::INFINITY,
+            };
+        }
+
+        let mut key = Vec::new();
+        for col in 0..size {
+            let column = ciphertext[col..].iter().step_by(size).cloned().collect::<Vec<u8>>();
+            key.push(rank_keys(&column, &self.scorer, 1).candidates[0].key);
+        }
+        let key = shortest_period(&key).to_vec();
+        let plaintext = ciphertext.iter().zip(key.iter().cycle()).map(|(&c, &k)| c ^ k)
+                                  .collect::<Vec<u8>>();
+        let score = self.scorer.score(&plaintext);
+
+        RepeatingXorCandidate {
+            key: key,
+            plaintext: plaintext,
+            score: score,
+        }
+    }
+
+    // Every distinct key found, best first
+    pub fn break_ciphertext(&self, ciphertext: &[u8]) -> Vec<RepeatingXorCandidate> {
+        let mut out: Vec<RepeatingXorCandidate> = Vec::new();
+        for size in self.candidate_key_sizes(ciphertext) {
+            let candidate = self.break_with_key_size(ciphertext, size);
+            // A multiple of the real key size finds the same key again
+            if !out.iter().any(|c| c.key == candidate.key) {
+                out.push(candidate);
+            }
+        }
+        out.sort_by(|a, b| cmp_scores(a.score, b.score));
+
+        out
+    }
+}
+
+#[test]
+fn tst_repeating_xor() {
+    use c06::{decode_b64, dump_file};
+
+    let b64: String = dump_file("c06.txt").split_whitespace().collect();
+    let ciphertext = decode_b64(&b64);
+    let key = b"Terminator X: Bring the noise";
+
+    // Each estimator should get there on its own
+    for &estimator in &[KeySizeEstimator::Hamming, KeySizeEstimator::Coincidence,
+                        KeySizeEstimator::Kasiski] {
+        let breaker = RepeatingXorBreaker::english().estimators(&[estimator]);
+        let candidates = breaker.break_ciphertext(&ciphertext);
+        assert_eq!(candidates[0].key, key);
+        assert!(candidates[0].plaintext.starts_with(b"I'm back and I'm ringin' the bell"));
+    }
+
+    // Short and long keys outside the old 2..40 window
+    let plaintext = ciphertext.iter().zip(key.iter().cycle()).map(|(&c, &k)| c ^ k)
+                              .collect::<Vec<u8>>();
+    let keys: [&[u8]; 2] = [b"\xa7", b"Vanilla Ice Ice Baby is too cold, too cold. Vanilla Ice!"];
+    for key in keys.iter() {
+        let ct = plaintext.iter().zip(key.iter().cycle()).map(|(&p, &k)| p ^ k)
+                          .collect::<Vec<u8>>();
+        let breaker = RepeatingXorBreaker::english().key_sizes(1, 64);
+        assert_eq!(breaker.break_ciphertext(&ct)[0].key, *key);
+    }
+
+    // Binary records that are mostly zeros, scored by how many zeros they decrypt to
+    let mut records = Vec::new();
+    for i in 0u32..200 {
+        records.extend_from_slice(&[i as u8, (i >> 8) as u8, 0, 0]);
+        records.extend_from_slice(&[0u8; 8]);
+        records.extend_from_slice(b"\x7fELF");
+    }
+    let key = b"\x13\x37\xc0\xff\xee\x00\x42\x99\xde\xad\xbe\xef\x01";
+    let ct = records.iter().zip(key.iter().cycle()).map(|(&p, &k)| p ^ k).collect::<Vec<u8>>();
+    let zeros = |pt: &[u8]| -(pt.iter().filter(|&&b| b == 0).count() as f64);
+    let candidates = RepeatingXorBreaker::new(zeros).break_ciphertext(&ct);
+    assert_eq!(candidates[0].key, key);
+    assert_eq!(candidates[0].plaintext, records);
+
+    // Key sizes with no room for a key
+    let breaker = RepeatingXorBreaker::english();
+    for &size in &[0, 4] {
+        let candidate = breaker.break_with_key_size(b"abc", size);
+        assert!(candidate.key.is_empty() && candidate.plaintext.is_empty());
+    }
+}
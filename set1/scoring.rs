@@ -0,0 +1,367 @@
+use std::f64;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+// Measures how far a candidate plaintext is from English; lower is better. Anything callable as
+// Fn(&[u8]) -> f64 is a Scorer, so plain error functions like braindead_err can be passed as-is
+pub trait Scorer {
+    fn score(&self, text: &[u8]) -> f64;
+}
+
+impl<F: Fn(&[u8]) -> f64> Scorer for F {
+    fn score(&self, text: &[u8]) -> f64 {
+        self(text)
+    }
+}
+
+// Quadgram counts over ~1.7MB of English prose from the documentation and docstrings that ship
+// with CPython 3.11, which are under the PSF License. quadgrams.py regenerates it, in the format
+// written by NgramModel::save
+const ENGLISH_QUADGRAMS: &'static str = include_str!("quadgrams.txt");
+
+// Letters are case-folded, runs of whitespace collapse to one space, and the remaining printable
+// ASCII shares a single symbol. Anything else is unprintable and never appears in an n-gram
+const N_SYMBOLS: usize = 28;
+const SPACE: usize = 26;
+const OTHER: usize = 27;
+const MAX_ORDER: usize = 4;
+
+// Number of printable characters that share the OTHER symbol. Each one in a text costs an extra
+// log10 of this, so that trading letters for punctuation isn't free
+const N_OTHER_CHARS: f64 = 42.0;
+
+// An n-gram containing an unprintable byte costs this many times as much as an unseen one
+const UNPRINTABLE_FACTOR: f64 = 2.0;
+
+fn to_symbol(b: u8) -> Option<usize> {
+    match b {
+        b'a'...b'z' => Some((b - b'a') as usize),
+        b'A'...b'Z' => Some((b - b'A') as usize),
+        b' ' | b'\t' | b'\n' | b'\r' => Some(SPACE),
+        b'!'...b'~' => Some(OTHER),
+                  _ => None
+    }
+}
+
+fn symbol_char(s: usize) -> char {
+    match s {
+        SPACE => '_',
+        OTHER => '#',
+            _ => (b'a' + s as u8) as char
+    }
+}
+
+fn char_symbol(c: char) -> Option<usize> {
+    match c {
+        'a'...'z' => Some((c as u8 - b'a') as usize),
+              '_' => Some(SPACE),
+              '#' => Some(OTHER),
+                _ => None
+    }
+}
+
+fn symbolize(text: &[u8]) -> Vec<Option<usize>> {
+    let mut out: Vec<Option<usize>> = Vec::new();
+    for &b in text {
+        let s = to_symbol(b);
+        if s == Some(SPACE) && out.last() == Some(&Some(SPACE)) {
+            continue;
+        }
+        out.push(s);
+    }
+
+    out
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// A character n-gram language model. The score of a text is the average negative log10
+// likelihood of its n-grams
+pub struct NgramModel {
+    n: usize,
+    counts: Vec<u32>,
+    total: u64,
+    // -log10(p) for every n-gram, indexed like counts
+    costs: Vec<f32>,
+    // Same thing for single symbols, used on texts shorter than n
+    unigram_costs: [f64; N_SYMBOLS],
+    unseen_cost: f64,
+}
+
+impl NgramModel {
+    // The embedded quadgram model
+    pub fn english() -> NgramModel {
+        NgramModel::load(ENGLISH_QUADGRAMS.as_bytes()).unwrap()
+    }
+
+    // Counts every n-gram in the corpus. n can be 1 through 4
+    pub fn train<R: Read>(mut corpus: R, n: usize) -> io::Result<NgramModel> {
+        if n < 1 || n > MAX_ORDER {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "n-gram order must be between 1 and 4"));
+        }
+
+        let mut text = Vec::new();
+        corpus.read_to_end(&mut text)?;
+
+        let mut counts = vec![0u32; N_SYMBOLS.pow(n as u32)];
+        for window in symbolize(&text).windows(n) {
+            if let Some(idx) = NgramModel::index(window) {
+                counts[idx] += 1;
+            }
+        }
+
+        Ok(NgramModel::from_counts(n, counts))
+    }
+
+    pub fn train_from_file(filename: &str, n: usize) -> io::Result<NgramModel> {
+        let file = File::open(filename)?;
+        NgramModel::train(BufReader::new(file), n)
+    }
+
+    // Reads "gram count" lines, as written by save()
+    pub fn load<R: Read>(input: R) -> io::Result<NgramModel> {
+        let mut n = 0usize;
+        let mut counts = Vec::new();
+        for line in BufReader::new(input).lines() {
+            let line = line?;
+            let mut it = line.split(' ');
+            let (gram, count) = match (it.next(), it.next()) {
+                (Some(g), Some(c)) => (g, c),
+                _ => return Err(invalid_data("expected a gram and a count")),
+            };
+            let count = match count.parse::<u32>() {
+                Ok(c) => c,
+                Err(_) => return Err(invalid_data("bad n-gram count")),
+            };
+
+            if n == 0 {
+                n = gram.len();
+                if n < 1 || n > MAX_ORDER {
+                    return Err(invalid_data("n-gram order must be between 1 and 4"));
+                }
+                counts = vec![0u32; N_SYMBOLS.pow(n as u32)];
+            }
+            let symbols = gram.chars().map(char_symbol).collect::<Vec<_>>();
+            match NgramModel::index(&symbols) {
+                Some(idx) if symbols.len() == n => counts[idx] = count,
+                _ => return Err(invalid_data("bad n-gram")),
+            }
+        }
+        if n == 0 {
+            return Err(invalid_data("no n-grams"));
+        }
+
+        Ok(NgramModel::from_counts(n, counts))
+    }
+
+    pub fn save<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let mut grams = self.counts.iter().enumerate().filter(|&(_, &c)| c > 0)
+                            .collect::<Vec<_>>();
+        // Most common first
+        grams.sort_by(|&(i, a), &(j, b)| b.cmp(a).then(i.cmp(&j)));
+        for (idx, &count) in grams {
+            let mut gram = String::new();
+            let mut rest = idx;
+            for _ in 0..self.n {
+                gram.insert(0, symbol_char(rest % N_SYMBOLS));
+                rest /= N_SYMBOLS;
+            }
+            writeln!(out, "{} {}", gram, count)?;
+        }
+
+        Ok(())
+    }
+
+    // The model of order n < self.order() implied by this one, i.e., the counts of every n-gram
+    // prefix. english().marginal(1) is a letter frequency model, which is what you want when
+    // scoring text that isn't contiguous, like the columns of a repeating-key XOR
+    pub fn marginal(&self, n: usize) -> NgramModel {
+        assert!(n >= 1 && n <= self.n, "marginal order must be between 1 and {}", self.n);
+        let stride = N_SYMBOLS.pow((self.n - n) as u32);
+        let mut counts = vec![0u32; N_SYMBOLS.pow(n as u32)];
+        for (idx, &c) in self.counts.iter().enumerate() {
+            counts[idx / stride] += c;
+        }
+
+        NgramModel::from_counts(n, counts)
+    }
+
+    pub fn order(&self) -> usize {
+        self.n
+    }
+
+    fn index(symbols: &[Option<usize>]) -> Option<usize> {
+        symbols.iter().fold(Some(0usize), |acc, s| match (acc, *s) {
+            (Some(a), Some(s)) => Some(a*N_SYMBOLS + s),
+            _ => None
+        })
+    }
+
+    fn from_counts(n: usize, counts: Vec<u32>) -> NgramModel {
+        let total = counts.iter().fold(0u64, |acc, &c| acc + c as u64);
+        // Same floor as an n-gram seen a hundredth of a time
+        let unseen_cost = -(0.01 / (total.max(1) as f64)).log10();
+
+        let costs = counts.iter().map(|&c| {
+            if c == 0 { unseen_cost as f32 }
+            else { -(c as f64 / total as f64).log10() as f32 }
+        }).collect::<Vec<f32>>();
+
+        // Marginal distribution of the first symbol
+        let mut firsts = [0u64; N_SYMBOLS];
+        let stride = N_SYMBOLS.pow((n - 1) as u32);
+        for (idx, &c) in counts.iter().enumerate() {
+            firsts[idx / stride] += c as u64;
+        }
+        let mut unigram_costs = [unseen_cost; N_SYMBOLS];
+        for (s, &c) in firsts.iter().enumerate() {
+            if c > 0 {
+                unigram_costs[s] = -(c as f64 / total as f64).log10();
+            }
+        }
+
+        NgramModel {
+            n: n,
+            counts: counts,
+            total: total,
+            costs: costs,
+            unigram_costs: unigram_costs,
+            unseen_cost: unseen_cost,
+        }
+    }
+}
+
+impl Scorer for NgramModel {
+    fn score(&self, text: &[u8]) -> f64 {
+        let symbols = symbolize(text);
+        if symbols.is_empty() {
+            return 0f64;
+        }
+        let unprintable_cost = UNPRINTABLE_FACTOR * self.unseen_cost;
+        let n_other = symbols.iter().filter(|&&s| s == Some(OTHER)).count();
+        let other_cost = (n_other as f64) * N_OTHER_CHARS.log10();
+
+        // Too short for a single n-gram; fall back to single symbols
+        if symbols.len() < self.n {
+            let sum = symbols.iter().fold(other_cost, |acc, s| match *s {
+                Some(s) => acc + self.unigram_costs[s],
+                None => acc + unprintable_cost,
+            });
+            return sum / (symbols.len() as f64);
+        }
+
+        let windows = symbols.windows(self.n);
+        let n_windows = windows.len() as f64;
+        let sum = windows.fold(other_cost, |acc, w| match NgramModel::index(w) {
+            Some(idx) => acc + self.costs[idx] as f64,
+            None => acc + unprintable_cost,
+        });
+
+        sum / n_windows
+    }
+}
+
+// The lines of c06's plaintext, each XORed with the same made-up keystream, like challenge 19's
+// fixed-nonce CTR. Returns the plaintexts, the ciphertexts, and the keystream
+#[cfg(test)]
+pub fn many_time_pad() -> (Vec<Vec<u8>>, Vec<Vec<u8>>, Vec<u8>) {
+    use c02::xor_bytes;
+    use c03::make_key_vec;
+    use c06::{decode_b64, dump_file};
+
+    let c06_b64: String = dump_file("c06.txt").split_whitespace().collect();
+    let c06_ct = decode_b64(&c06_b64);
+    let c06_key = make_key_vec(b"Terminator X: Bring the noise", c06_ct.len());
+    let c06_pt = xor_bytes(&c06_ct, &c06_key);
+    let pts = c06_pt.split(|&b| b == b'\n').filter(|line| !line.is_empty())
+                    .map(|line| line.to_vec()).collect::<Vec<Vec<u8>>>();
+    let mut x = 0x2545f491u32;
+    let keystream = (0..64).map(|_| {
+        x ^= x << 13; x ^= x >> 17; x ^= x << 5;
+        x as u8
+    }).collect::<Vec<u8>>();
+    let cts = pts.iter().map(|pt| pt.iter().zip(&keystream).map(|(&p, &k)| p ^ k).collect())
+                 .collect::<Vec<Vec<u8>>>();
+    (pts, cts, keystream)
+}
+
+// Runs challenges 3, 4, and 6, and a many-time pad made from the lines of c06's plaintext, with
+// every scorer and prints how each one did
+#[test]
+fn tst_scorers() {
+    use c01::decode_hex;
+    use c02::xor_bytes;
+    use c03::{coincidence_err, make_key_vec, test_all_keys};
+    use c04::{braindead_err, chi_sq_bigram, chi_sq_monogram, extra_braindead_err, get_lines,
+              test_all};
+    use c06::{break_with_key_size, decode_b64, dump_file, sorted_key_sizes};
+
+    let scorers: Vec<(&str, Box<Scorer>)> = vec![
+        ("coincidence_err", Box::new(coincidence_err)),
+        ("chi_sq_monogram", Box::new(chi_sq_monogram)),
+        ("chi_sq_bigram", Box::new(chi_sq_bigram)),
+        ("braindead_err", Box::new(braindead_err)),
+        ("extra_braindead_err", Box::new(extra_braindead_err)),
+        ("quadgrams", Box::new(NgramModel::english())),
+        ("unigrams", Box::new(NgramModel::english().marginal(1))),
+    ];
+
+    let c03_ct = decode_hex("1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736");
+
+    let c04_lines = get_lines("c04.txt").iter().map(|s| decode_hex(s)).collect::<Vec<_>>();
+    let c04_cts = c04_lines.iter().map(|l| &**l).collect::<Vec<&[u8]>>();
+
+    let c06_b64: String = dump_file("c06.txt").split_whitespace().collect();
+    let c06_ct = decode_b64(&c06_b64);
+    let mut c06_sizes = sorted_key_sizes(&c06_ct);
+    c06_sizes.truncate(5);
+
+    let (_, pad_cts, keystream) = many_time_pad();
+    let min_len = pad_cts.iter().map(|ct| ct.len()).min().unwrap();
+
+    println!("{:>20} {:>5} {:>5} {:>5} {:>8}", "scorer", "c03", "c04", "c06", "pad");
+    let mut results = Vec::new();
+    for &(name, ref scorer) in &scorers {
+        let (key, _) = test_all_keys(&c03_ct, &**scorer);
+        let pt = xor_bytes(&c03_ct, &make_key_vec(&[key], c03_ct.len()));
+        let c03_ok = pt == b"Cooking MC's like a pound of bacon";
+
+        let (key, idx, _) = test_all(&c04_cts, &**scorer);
+        let pt = xor_bytes(c04_cts[idx], &make_key_vec(&[key], c04_cts[idx].len()));
+        let c04_ok = pt == b"Now that the party is jumping\n";
+
+        let mut candidates = c06_sizes.iter().map(|&(size, _)| {
+            break_with_key_size(&c06_ct, size, &**scorer)
+        }).collect::<Vec<_>>();
+        candidates.sort_by(|&(_, a), &(_, b)| a.partial_cmp(&b).unwrap());
+        let c06_ok = candidates[0].0 == b"Terminator X: Bring the noise";
+
+        // Fraction of the recoverable keystream bytes that came out right
+        let n_correct = (0..min_len).filter(|&col| {
+            let column = pad_cts.iter().map(|ct| ct[col]).collect::<Vec<u8>>();
+            test_all_keys(&column, &**scorer).0 == keystream[col]
+        }).count();
+        let pad_acc = n_correct as f64 / min_len as f64;
+
+        println!("{:>20} {:>5} {:>5} {:>5} {:>8.3}", name, c03_ok, c04_ok, c06_ok, pad_acc);
+        results.push((c03_ok, c04_ok, c06_ok, pad_acc));
+    }
+
+    // What each scorer is expected to get right, in the order above. The bigram and quadgram
+    // scorers only make sense on contiguous text, so they're no good on the columns in c06 and
+    // the many-time pad. The index of coincidence only looks at letters, so it's fooled by line
+    // noise
+    let expected = [(true,  false, false, false),
+                    (true,  true,  true,  true),
+                    (true,  true,  false, false),
+                    (false, true,  true,  false),
+                    (true,  true,  false, true),
+                    (true,  true,  false, false),
+                    (true,  true,  true,  true)];
+    for (&(c03_ok, c04_ok, c06_ok, pad_acc), &exp) in results.iter().zip(expected.iter()) {
+        assert_eq!((c03_ok, c04_ok, c06_ok, pad_acc > 0.9), exp);
+    }
+}
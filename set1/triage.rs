@@ -0,0 +1,381 @@
+use block_repeats::{find_block_repeats, DEFAULT_BLOCK_SIZES};
+use c03::rank_keys;
+use codec::Encoding;
+use repeating_xor::RepeatingXorBreaker;
+use scoring::NgramModel;
+use std::ascii;
+use std::cmp::min;
+use std::fmt;
+
+// Wrappings are peeled off this many times at most, e.g., base64 of hex of base64
+const MAX_LAYERS: usize = 4;
+// The XOR breakers and the block repetition scan only look at this much of the blob, so
+// triaging a huge capture stays quick
+const MAX_BREAK_LEN: usize = 1 << 16;
+// Repeating-key XOR columns shorter than this are too easy to overfit to trust
+const MIN_COLUMN_LEN: usize = 16;
+// How many of the most common bytes the report lists
+const TOP_BYTES: usize = 5;
+
+// Byte-level statistics of a blob
+#[derive(Clone, Debug, PartialEq)]
+pub struct ByteStats {
+    pub len: usize,
+    // Shannon entropy in bits per byte, from 0 to 8
+    pub entropy: f64,
+    // Index of coincidence, scaled so that uniformly random bytes come out near 1. ASCII text is
+    // somewhere around 15 to 20
+    pub ioc: f64,
+    pub distinct: usize,
+    // Fraction of bytes that are printable ASCII or whitespace
+    pub printable: f64,
+    // Count of every byte value, indexed by byte
+    pub histogram: Vec<usize>,
+}
+
+impl ByteStats {
+    pub fn new(bytes: &[u8]) -> ByteStats {
+        let mut histogram = vec![0usize; 256];
+        for &b in bytes {
+            histogram[b as usize] += 1;
+        }
+
+        let n = bytes.len() as f64;
+        let mut entropy = 0f64;
+        let mut pairs = 0f64;
+        for &c in histogram.iter().filter(|&&c| c > 0) {
+            let p = c as f64 / n;
+            entropy -= p * p.log2();
+            pairs += (c * (c-1)) as f64;
+        }
+        let ioc = if bytes.len() < 2 { 0f64 } else { 256f64 * pairs / (n * (n-1f64)) };
+
+        ByteStats {
+            len: bytes.len(),
+            entropy: entropy,
+            ioc: ioc,
+            distinct: histogram.iter().filter(|&&c| c > 0).count(),
+            printable: printable_fraction(bytes),
+            histogram: histogram,
+        }
+    }
+
+    // The n most common bytes with their counts, most common first. Ties go to the smaller byte
+    pub fn most_common(&self, n: usize) -> Vec<(u8, usize)> {
+        let mut counts = self.histogram.iter().enumerate().filter(|&(_, &c)| c > 0)
+                             .map(|(b, &c)| (b as u8, c)).collect::<Vec<(u8, usize)>>();
+        counts.sort_by(|&(a, c), &(b, d)| d.cmp(&c).then(a.cmp(&b)));
+        counts.truncate(n);
+
+        counts
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Hypothesis {
+    // Starts with a gzip or zlib header; format is which one
+    Compressed { format: &'static str },
+    // Already readable, nothing to undo
+    Plaintext,
+    SingleByteXor { key: u8, plaintext: Vec<u8> },
+    RepeatingXor { key: Vec<u8>, plaintext: Vec<u8> },
+    // Some block-sized chunks repeat, which a good cipher in any other mode wouldn't do. Blocks
+    // are counted from offset
+    Ecb { block_size: usize, offset: usize, repeats: usize },
+    // Nothing stands out and the bytes look uniform: a stream cipher, CBC, CTR, or compressed
+    // data without a header
+    Random,
+}
+
+// A hypothesis with a confidence from 0 to 1. Confidences are rough heuristics meant for
+// ranking, not probabilities, and the hypotheses aren't mutually exclusive
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub hypothesis: Hypothesis,
+    pub confidence: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TriageReport {
+    // Encodings peeled off the blob, outermost first
+    pub layers: Vec<Encoding>,
+    // The blob with those encodings undone
+    pub bytes: Vec<u8>,
+    pub stats: ByteStats,
+    // Most likely first
+    pub findings: Vec<Finding>,
+}
+
+fn printable_fraction(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 0f64;
+    }
+    let n = bytes.iter().filter(|&&b| (b >= 0x20 && b < 0x7f) || b == b'\t' || b == b'\n'
+                                      || b == b'\r').count();
+
+    n as f64 / bytes.len() as f64
+}
+
+// Fraction of bytes that are letters or spaces. This is what separates text from random bytes
+// that a breaker has massaged into being printable
+fn letter_fraction(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 0f64;
+    }
+    let n = bytes.iter().filter(|&&b| (b as char).is_ascii_alphabetic() || b == b' ').count();
+
+    n as f64 / bytes.len() as f64
+}
+
+// Only newlines are allowed as whitespace, or else any English sentence without punctuation
+// would pass for base64
+fn looks_encoded(text: &[u8], encoding: Encoding) -> Option<Vec<u8>> {
+    let body = text.iter().filter(|&&b| b != b'\n' && b != b'\r').collect::<Vec<&u8>>();
+    let alphabet_ok = |b: u8| match encoding {
+        Encoding::Hex => (b as char).is_digit(16),
+        Encoding::Base64 => (b as char).is_ascii_alphanumeric() || b == b'+' || b == b'/'
+                            || b == b'=',
+        Encoding::Base64Url => (b as char).is_ascii_alphanumeric() || b == b'-' || b == b'_'
+                               || b == b'=',
+        _ => false,
+    };
+    if body.len() < 4 || !body.iter().all(|&&b| alphabet_ok(b)) {
+        return None;
+    }
+
+    encoding.decode(text).ok()
+}
+
+// Peels off hex and base64 wrappings for as long as they keep decoding
+pub fn unwrap_encodings(blob: &[u8]) -> (Vec<Encoding>, Vec<u8>) {
+    let mut layers = Vec::new();
+    let mut bytes = blob.to_vec();
+    while layers.len() < MAX_LAYERS {
+        let trimmed = {
+            let start = bytes.iter().position(|b| !(*b as char).is_whitespace())
+                             .unwrap_or(bytes.len());
+            let end = bytes.iter().rposition(|b| !(*b as char).is_whitespace()).map_or(0, |i| i+1);
+            if start < end { bytes[start..end].to_vec() } else { Vec::new() }
+        };
+
+        // Hex goes first, since every hex string is also valid base64
+        let decoded = [Encoding::Hex, Encoding::Base64, Encoding::Base64Url].iter()
+                          .filter_map(|&e| looks_encoded(&trimmed, e).map(|d| (e, d))).next();
+        match decoded {
+            Some((encoding, inner)) => {
+                layers.push(encoding);
+                bytes = inner;
+            }
+            None => break,
+        }
+    }
+
+    (layers, bytes)
+}
+
+fn compression_format(bytes: &[u8]) -> Option<(&'static str, f64)> {
+    if bytes.len() >= 3 && bytes[0] == 0x1f && bytes[1] == 0x8b && bytes[2] == 0x08 {
+        Some(("gzip", 0.95))
+    } else if bytes.len() >= 2 && bytes[0] & 0x0f == 8 && bytes[0] >> 4 <= 7
+              && ((bytes[0] as u16) << 8 | bytes[1] as u16) % 31 == 0 {
+        // A two-byte zlib header has a checksum, but one in a few hundred random pairs pass it
+        Some(("zlib", 0.8))
+    } else {
+        None
+    }
+}
+
+// Runs every check on the blob and ranks what it finds
+pub fn triage(blob: &[u8]) -> TriageReport {
+    let (layers, bytes) = unwrap_encodings(blob);
+    let stats = ByteStats::new(&bytes);
+    let sample = &bytes[..min(bytes.len(), MAX_BREAK_LEN)];
+    let mut findings = Vec::new();
+
+    if let Some((format, confidence)) = compression_format(&bytes) {
+        findings.push(Finding {
+            hypothesis: Hypothesis::Compressed { format: format },
+            confidence: confidence,
+        });
+    }
+
+    // Any repeat is a strong sign, as long as the blob isn't just repetitive text
+    if let Some(repeats) = find_block_repeats(sample, &DEFAULT_BLOCK_SIZES) {
+        if repeats.score > 0f64 {
+            let not_text = (2f64 * (1f64 - stats.printable)).min(1f64);
+            let hypothesis = Hypothesis::Ecb {
+                block_size: repeats.block_size,
+                offset: repeats.offset,
+                repeats: repeats.duplicates,
+            };
+            findings.push(Finding {
+                hypothesis: hypothesis,
+                confidence: (1f64 - 0.5f64.powi(repeats.duplicates as i32)) * not_text,
+            });
+        }
+    }
+
+    let unigrams = NgramModel::english().marginal(1);
+    if !sample.is_empty() {
+        // Key 0 is the blob itself, so this doubles as the plaintext check
+        let best = rank_keys(sample, &unigrams, 1).candidates.remove(0);
+        let hypothesis = if best.key == 0 {
+            Hypothesis::Plaintext
+        } else {
+            Hypothesis::SingleByteXor { key: best.key, plaintext: best.plaintext.clone() }
+        };
+        findings.push(Finding {
+            hypothesis: hypothesis,
+            confidence: letter_fraction(&best.plaintext) * printable_fraction(&best.plaintext),
+        });
+    }
+
+    // Single-byte keys are covered above
+    let max_key_size = sample.len() / MIN_COLUMN_LEN;
+    if max_key_size >= 2 {
+        let breaker = RepeatingXorBreaker::new(unigrams).key_sizes(2, min(40, max_key_size));
+        if let Some(best) = breaker.break_ciphertext(sample).into_iter()
+                                   .find(|c| c.key.len() > 1) {
+            // Discounted a little, since a longer key can always fit at least as well
+            let confidence = 0.95 * letter_fraction(&best.plaintext)
+                                  * printable_fraction(&best.plaintext);
+            findings.push(Finding {
+                hypothesis: Hypothesis::RepeatingXor { key: best.key, plaintext: best.plaintext },
+                confidence: confidence,
+            });
+        }
+    }
+
+    // A catch-all, so it never gets more than half confidence
+    if bytes.len() >= 2 {
+        findings.push(Finding {
+            hypothesis: Hypothesis::Random,
+            confidence: 0.5 * (1f64 / stats.ioc).min(1f64),
+        });
+    }
+
+    // Stable, so ties keep the order above
+    findings.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap());
+
+    TriageReport {
+        layers: layers,
+        bytes: bytes,
+        stats: stats,
+        findings: findings,
+    }
+}
+
+// At most 60 bytes of plaintext, with anything unprintable escaped
+fn preview(bytes: &[u8]) -> String {
+    let mut out = bytes.iter().take(60).flat_map(|&b| ascii::escape_default(b))
+                       .map(|b| b as char).collect::<String>();
+    if bytes.len() > 60 {
+        out.push_str("...");
+    }
+
+    out
+}
+
+impl fmt::Display for Hypothesis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Hypothesis::Compressed { format } => write!(f, "{} compressed data", format),
+            Hypothesis::Plaintext => write!(f, "plaintext"),
+            Hypothesis::SingleByteXor { key, ref plaintext } =>
+                write!(f, "single-byte XOR, key 0x{:02x}: \"{}\"", key, preview(plaintext)),
+            Hypothesis::RepeatingXor { ref key, ref plaintext } =>
+                write!(f, "repeating-key XOR, {}-byte key \"{}\": \"{}\"", key.len(), preview(key),
+                       preview(plaintext)),
+            Hypothesis::Ecb { block_size, offset, repeats } =>
+                write!(f, "ECB mode, {} repeated {}-byte blocks from offset {}", repeats,
+                       block_size, offset),
+            Hypothesis::Random =>
+                write!(f, "random-looking data (stream cipher, CBC, CTR, or compressed)"),
+        }
+    }
+}
+
+impl fmt::Display for TriageReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let layers = self.layers.iter().map(|e| format!("{:?}", e)).collect::<Vec<String>>();
+        writeln!(f, "encoding: {}", if layers.is_empty() { "none".to_string() }
+                                    else { layers.join(" -> ") })?;
+        writeln!(f, "length: {} bytes", self.stats.len)?;
+        writeln!(f, "entropy: {:.3} bits/byte", self.stats.entropy)?;
+        writeln!(f, "index of coincidence: {:.3} (random = 1)", self.stats.ioc)?;
+        writeln!(f, "distinct bytes: {}", self.stats.distinct)?;
+        writeln!(f, "printable: {:.1}%", 100f64 * self.stats.printable)?;
+        let common = self.stats.most_common(TOP_BYTES).iter()
+                         .map(|&(b, c)| format!("0x{:02x} ({:.1}%)", b,
+                                                100f64 * c as f64 / self.stats.len as f64))
+                         .collect::<Vec<String>>();
+        writeln!(f, "most common: {}", common.join(", "))?;
+        writeln!(f, "hypotheses:")?;
+        for (i, finding) in self.findings.iter().enumerate() {
+            writeln!(f, "{:>3}. [{:.2}] {}", i+1, finding.confidence, finding.hypothesis)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn tst_triage() {
+    use c04::get_lines;
+    use c06::dump_file;
+
+    // c06 is base64-wrapped repeating-key XOR
+    let report = triage(dump_file("c06.txt").as_bytes());
+    assert_eq!(report.layers, vec![Encoding::Base64]);
+    match report.findings[0].hypothesis {
+        Hypothesis::RepeatingXor { ref key, .. } =>
+            assert_eq!(key, b"Terminator X: Bring the noise"),
+        ref h => panic!("Expected repeating-key XOR, got {}", h),
+    }
+
+    // c03 is hex-wrapped single-byte XOR
+    let report = triage(b"1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736");
+    assert_eq!(report.layers, vec![Encoding::Hex]);
+    match report.findings[0].hypothesis {
+        Hypothesis::SingleByteXor { key, ref plaintext } => {
+            assert_eq!(key, 88);
+            assert_eq!(plaintext, b"Cooking MC's like a pound of bacon");
+        }
+        ref h => panic!("Expected single-byte XOR, got {}", h),
+    }
+
+    // Line 132 of c08 is the ECB one
+    let line = &get_lines("c08.txt")[132];
+    let report = triage(line.as_bytes());
+    assert_eq!(report.findings[0].hypothesis,
+               Hypothesis::Ecb { block_size: 16, offset: 0, repeats: 3 });
+
+    // Plain text, which the newline and letters alone shouldn't make look like base64
+    let text = b"Now that the party is jumping\nWith the bass kicked in and the Vegas are pumpin";
+    let report = triage(text);
+    assert!(report.layers.is_empty());
+    assert_eq!(report.findings[0].hypothesis, Hypothesis::Plaintext);
+
+    // Base64 of hex of bytes from a keystream
+    let mut state = 0x2545f4914f6cdd1du64;
+    let random = (0..4096).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 32) as u8
+    }).collect::<Vec<u8>>();
+    let wrapped = Encoding::Base64.encode(Encoding::Hex.encode(&random).as_bytes());
+    let report = triage(wrapped.as_bytes());
+    assert_eq!(report.layers, vec![Encoding::Base64, Encoding::Hex]);
+    assert_eq!(report.bytes, random);
+    assert!(report.stats.entropy > 7.9);
+    assert!((report.stats.ioc - 1f64).abs() < 0.1);
+    assert_eq!(report.findings[0].hypothesis, Hypothesis::Random);
+
+    let mut gzip = vec![0x1f, 0x8b, 0x08, 0x00];
+    gzip.extend_from_slice(&random[..100]);
+    assert_eq!(triage(&gzip).findings[0].hypothesis, Hypothesis::Compressed { format: "gzip" });
+    let mut zlib = vec![0x78, 0x9c];
+    zlib.extend_from_slice(&random[..100]);
+    assert_eq!(triage(&zlib).findings[0].hypothesis, Hypothesis::Compressed { format: "zlib" });
+}
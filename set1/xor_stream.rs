@@ -0,0 +1,187 @@
+use std::cmp::min;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+// How many bytes an XorStream writer XORs into its scratch buffer before handing them on
+const WRITE_CHUNK_SIZE: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XorError {
+    // The two operands of an elementwise XOR weren't the same length
+    LengthMismatch { expected: usize, found: usize },
+    // A repeating key has to have at least one byte in it
+    EmptyKey,
+}
+
+impl fmt::Display for XorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            XorError::LengthMismatch { expected, found } =>
+                write!(f, "expected {} bytes to XOR with, got {}", expected, found),
+            XorError::EmptyKey => write!(f, "repeating key is empty"),
+        }
+    }
+}
+
+impl Error for XorError { }
+
+impl From<XorError> for io::Error {
+    fn from(err: XorError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
+
+// buf ^= other, without allocating. Unlike xor_bytes, a short second operand is an error rather
+// than a panic
+pub fn xor_into(buf: &mut [u8], other: &[u8]) -> Result<(), XorError> {
+    if buf.len() != other.len() {
+        return Err(XorError::LengthMismatch { expected: buf.len(), found: other.len() });
+    }
+    for (b, &o) in buf.iter_mut().zip(other) {
+        *b ^= o;
+    }
+
+    Ok(())
+}
+
+// buf ^= key repeated out to buf's length, without building the repeated key
+pub fn xor_repeating_into(buf: &mut [u8], key: &[u8]) -> Result<(), XorError> {
+    if key.is_empty() {
+        return Err(XorError::EmptyKey);
+    }
+    apply_keystream(buf, key, 0);
+
+    Ok(())
+}
+
+// XORs buf with the key starting from key[offset], and returns the offset to pick up from next
+fn apply_keystream(buf: &mut [u8], key: &[u8], offset: usize) -> usize {
+    for (b, &k) in buf.iter_mut().zip(key[offset..].iter().chain(key.iter().cycle())) {
+        *b ^= k;
+    }
+
+    (offset + buf.len()) % key.len()
+}
+
+// Wraps a reader or a writer and XORs everything that passes through with a repeating key. Only
+// the key and the current offset into it are kept around, so this works on inputs of any size.
+// Encryption and decryption are the same operation, so there's only one type for both
+pub struct XorStream<T> {
+    inner: T,
+    key: Vec<u8>,
+    // Index into the key of the next byte to be XORed
+    pos: usize,
+}
+
+impl<T> XorStream<T> {
+    pub fn new(inner: T, key: &[u8]) -> Result<XorStream<T>, XorError> {
+        if key.is_empty() {
+            return Err(XorError::EmptyKey);
+        }
+
+        Ok(XorStream {
+            inner: inner,
+            key: key.to_vec(),
+            pos: 0,
+        })
+    }
+
+    // XORs buf in place with the next buf.len() bytes of keystream, as if it had been read
+    // through this stream. Handy for mmapped files and other data that's already in memory
+    pub fn apply(&mut self, buf: &mut [u8]) {
+        self.pos = apply_keystream(buf, &self.key, self.pos);
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    // Reading or writing through this directly will knock the keystream out of step
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for XorStream<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n_read = self.inner.read(out)?;
+        self.apply(&mut out[..n_read]);
+
+        Ok(n_read)
+    }
+}
+
+impl<W: Write> Write for XorStream<W> {
+    // Only the bytes the inner writer actually accepts use up keystream, so partial writes stay
+    // in step
+    fn write(&mut self, input: &[u8]) -> io::Result<usize> {
+        let mut chunk = [0u8; WRITE_CHUNK_SIZE];
+        let len = min(input.len(), WRITE_CHUNK_SIZE);
+        chunk[..len].copy_from_slice(&input[..len]);
+        apply_keystream(&mut chunk[..len], &self.key, self.pos);
+
+        let n_written = self.inner.write(&chunk[..len])?;
+        self.pos = (self.pos + n_written) % self.key.len();
+
+        Ok(n_written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn tst_xor_stream() {
+    use c02::encode_hex;
+
+    let plaintext = b"Burning 'em, if you ain't quick and nimble\n\
+                      I go crazy when I hear a cymbal";
+    let expected = "0b3637272a2b2e63622c2e69692a23693a2a3c6324202d623d63343c2a26226324272765272\
+                    a282b2f20430a652e2c652a3124333a653e2b2027630c692b20283165286326302e27282f";
+
+    // Reading in odd-sized pieces has to keep the key in step across calls
+    let mut reader = XorStream::new(&plaintext[..], b"ICE").unwrap();
+    let mut ciphertext = Vec::new();
+    let mut piece = [0u8; 7];
+    loop {
+        let n = reader.read(&mut piece).unwrap();
+        if n == 0 {
+            break;
+        }
+        ciphertext.extend_from_slice(&piece[..n]);
+    }
+    assert_eq!(encode_hex(&ciphertext), expected);
+
+    // Writing more than a chunk at a time, and decrypting by going through again
+    let big = (0..3*WRITE_CHUNK_SIZE+5).map(|i| (i * 31) as u8).collect::<Vec<u8>>();
+    let mut writer = XorStream::new(Vec::new(), b"ICE").unwrap();
+    writer.write_all(&big).unwrap();
+    let mut decrypted = Vec::new();
+    XorStream::new(&writer.into_inner()[..], b"ICE").unwrap()
+              .read_to_end(&mut decrypted).unwrap();
+    assert_eq!(decrypted, big);
+
+    // apply() and the in-place helpers agree with the stream
+    let mut buf = plaintext.to_vec();
+    let mut stream = XorStream::new(io::sink(), b"ICE").unwrap();
+    let (front, back) = buf.split_at_mut(10);
+    stream.apply(front);
+    stream.apply(back);
+    assert_eq!(buf, ciphertext);
+    xor_repeating_into(&mut buf, b"ICE").unwrap();
+    assert_eq!(&buf[..], &plaintext[..]);
+    xor_into(&mut buf, &ciphertext).unwrap();
+    xor_into(&mut buf, &plaintext[..]).unwrap();
+    assert_eq!(buf, ciphertext);
+
+    assert_eq!(xor_into(&mut buf, b"short"),
+               Err(XorError::LengthMismatch { expected: plaintext.len(), found: 5 }));
+    assert_eq!(xor_repeating_into(&mut buf, b""), Err(XorError::EmptyKey));
+    assert!(XorStream::new(io::sink(), b"").is_err());
+}
@@ -0,0 +1,297 @@
+use set1::xor_bytes;
+use c11::make_vec;
+use ecb_suffix::MAX_BLOCK_SIZE;
+use std::error::Error;
+use std::fmt;
+
+// One forgery per sacrificial block of 'a' through 'z'. Each scrambles differently, and the odds
+// of 26 scrambles all hitting a character the tester chokes on are tiny
+const FORGE_ATTEMPTS: usize = 26;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlipError {
+    // The ciphertext never got any longer
+    NoBlockSize,
+    // Changing our input didn't behave like CBC or a stream cipher: it stayed in one block (ECB),
+    // or the same input encrypted differently every time (random IV or nonce)
+    NotMalleable,
+    // The tester turned down every forgery
+    Rejected { attempts: usize },
+}
+
+impl fmt::Display for FlipError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FlipError::NoBlockSize => write!(f, "couldn't find the block size"),
+            FlipError::NotMalleable => write!(f, "the oracle isn't deterministic CBC or CTR"),
+            FlipError::Rejected { attempts } =>
+                write!(f, "the tester rejected all {} forgeries", attempts),
+        }
+    }
+}
+
+impl Error for FlipError { }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlipMode {
+    // Flipping a ciphertext bit scrambles its own block and flips the same bit in the next one
+    Cbc { block_size: usize },
+    // Flipping a ciphertext bit flips the same plaintext bit and nothing else, like CTR or OFB
+    Stream,
+}
+
+// Where our input ends up in the plaintext, and how the ciphertext around it can be changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Injection {
+    pub mode: FlipMode,
+    pub offset: usize,
+}
+
+// Bit-flipping forgeries against an oracle that encrypts something with our input in the middle,
+// and a tester that says whether a ciphertext got us what we wanted. The target never goes
+// through the oracle, so it doesn't matter what the oracle sanitizes out of our input
+pub struct BitFlipper<E, T> {
+    oracle: E,
+    tester: T,
+    queries: usize,
+    injection: Option<Injection>,
+}
+
+impl<E, T> BitFlipper<E, T> where E: FnMut(&[u8]) -> Vec<u8>, T: FnMut(&[u8]) -> bool {
+    pub fn new(oracle: E, tester: T) -> BitFlipper<E, T> {
+        BitFlipper {
+            oracle: oracle,
+            tester: tester,
+            queries: 0,
+            injection: None,
+        }
+    }
+
+    // Calls made to the oracle and tester so far
+    pub fn queries(&self) -> usize {
+        self.queries
+    }
+
+    fn query(&mut self, input: &[u8]) -> Vec<u8> {
+        self.queries += 1;
+        (self.oracle)(input)
+    }
+
+    fn test(&mut self, ciphertext: &[u8]) -> bool {
+        self.queries += 1;
+        (self.tester)(ciphertext)
+    }
+
+    pub fn probe(&mut self) -> Result<Injection, FlipError> {
+        if let Some(injection) = self.injection {
+            return Ok(injection);
+        }
+
+        let mode = self.find_mode()?;
+        let injection = Injection {
+            mode: mode,
+            offset: self.find_offset(mode)?,
+        };
+
+        self.injection = Some(injection);
+        Ok(injection)
+    }
+
+    // A stream cipher's ciphertext grows a byte at a time; CBC's grows a block at a time. ECB
+    // grows the same way as CBC, but a change to our input doesn't reach the last block
+    fn find_mode(&mut self) -> Result<FlipMode, FlipError> {
+        let base = self.query(b"");
+        if self.query(b"") != base {
+            return Err(FlipError::NotMalleable);
+        }
+
+        for n in 1..MAX_BLOCK_SIZE+1 {
+            let len = self.query(&make_vec(b'A', n)).len();
+            if len == base.len() {
+                continue;
+            }
+            if n == 1 && len == base.len() + 1 {
+                return Ok(FlipMode::Stream);
+            }
+
+            let block_size = len - base.len();
+            let filler = make_vec(b'A', 3*block_size);
+            let mut changed = filler.clone();
+            changed[0] = b'B';
+            let a = self.query(&filler);
+            let b = self.query(&changed);
+            return if a[a.len()-block_size..] != b[b.len()-block_size..] {
+                Ok(FlipMode::Cbc { block_size: block_size })
+            } else {
+                Err(FlipError::NotMalleable)
+            };
+        }
+
+        Err(FlipError::NoBlockSize)
+    }
+
+    // Two inputs differing only in their last byte first differ in the ciphertext at that byte,
+    // or at its block under CBC. Adding filler until that block moves on tells us how far into
+    // its block our input started
+    fn find_offset(&mut self, mode: FlipMode) -> Result<usize, FlipError> {
+        let unit = match mode {
+            FlipMode::Cbc { block_size } => block_size,
+            FlipMode::Stream => 1,
+        };
+        let start = self.first_difference(0)?;
+        if unit == 1 {
+            return Ok(start);
+        }
+        for filler_len in 1..unit+1 {
+            let diff = self.first_difference(filler_len)?;
+            if diff / unit > start / unit {
+                return Ok(diff / unit * unit - filler_len);
+            }
+        }
+
+        Err(FlipError::NotMalleable)
+    }
+
+    fn first_difference(&mut self, filler_len: usize) -> Result<usize, FlipError> {
+        let mut input = make_vec(b'A', filler_len + 1);
+        let a = self.query(&input);
+        input[filler_len] = b'B';
+        let b = self.query(&input);
+        a.iter().zip(b.iter()).position(|(x, y)| x != y).ok_or(FlipError::NotMalleable)
+    }
+
+    // Returns a ciphertext that decrypts with target where our input went, once the tester has
+    // said yes to it. CBC leaves a block of garbage in front of every block of target, inside our
+    // input. The target is lined up so it ends at the end of a block, and a target longer than a
+    // block is split into blocks from there, so it comes out in pieces with garbage in between.
+    // Formats with separators can put one at each end of a piece
+    pub fn forge(&mut self, target: &[u8]) -> Result<Vec<u8>, FlipError> {
+        let injection = self.probe()?;
+        match injection.mode {
+            FlipMode::Stream => {
+                let filler = make_vec(b'A', target.len());
+                let mut ciphertext = self.query(&filler);
+                let mask = xor_bytes(&filler, target);
+                apply_mask(&mut ciphertext[injection.offset..], &mask);
+                if self.test(&ciphertext) {
+                    Ok(ciphertext)
+                } else {
+                    Err(FlipError::Rejected { attempts: 1 })
+                }
+            },
+            FlipMode::Cbc { block_size } => {
+                let align = (block_size - injection.offset % block_size) % block_size;
+                let start = injection.offset + align;
+                let filler = make_vec(b'A', block_size);
+                let n_blocks = (target.len() + block_size - 1) / block_size;
+                let mut wanted = make_vec(b'A', n_blocks*block_size - target.len());
+                wanted.extend_from_slice(target);
+
+                for attempt in 0..FORGE_ATTEMPTS {
+                    // A sacrificial block, then a block of filler to flip, for each target block
+                    let mut input = make_vec(b'A', align);
+                    for _ in 0..n_blocks {
+                        input.extend(make_vec(b'a' + attempt as u8, block_size));
+                        input.extend_from_slice(&filler);
+                    }
+                    let mut ciphertext = self.query(&input);
+                    for (i, block) in wanted.chunks(block_size).enumerate() {
+                        let sacrificial = start + 2*i*block_size;
+                        apply_mask(&mut ciphertext[sacrificial..sacrificial+block_size],
+                                   &xor_bytes(&filler, block));
+                    }
+                    if self.test(&ciphertext) {
+                        return Ok(ciphertext);
+                    }
+                }
+
+                Err(FlipError::Rejected { attempts: FORGE_ATTEMPTS })
+            },
+        }
+    }
+}
+
+fn apply_mask(bytes: &mut [u8], mask: &[u8]) {
+    for (b, m) in bytes.iter_mut().zip(mask.iter()) {
+        *b ^= *m;
+    }
+}
+
+#[test]
+fn tst_bit_flip() {
+    use set1::{decrypt_with_mode, encrypt_with_mode, Aes, Counter, Mode, Padding, Pkcs7};
+
+    let aes = Aes::new(b"YELLOW SUBMARINE").unwrap();
+    let iv = [7u8; 16];
+    let suffix = b";comment2=%20like%20a%20pound%20of%20bacon";
+    let is_admin = |plaintext: &[u8]| {
+        String::from_utf8_lossy(plaintext).split(';').any(|field| field == "admin=true")
+    };
+    let sanitize = |input: &[u8]| {
+        input.iter().map(|&b| if b == b';' || b == b'=' { b'%' } else { b }).collect::<Vec<u8>>()
+    };
+
+    for &mode in [Mode::Cbc, Mode::Ctr(Counter::BigEndian)].iter() {
+        for &prefix_len in [0, 5, 16, 37].iter() {
+            let prefix = make_vec(b'P', prefix_len);
+            let oracle = |input: &[u8]| {
+                let plaintext = [&prefix[..], &sanitize(input), &suffix[..]].concat();
+                let plaintext = if mode == Mode::Cbc {
+                    Pkcs7.pad(&plaintext, 16)
+                } else {
+                    plaintext
+                };
+                encrypt_with_mode(mode, &aes, &iv, &plaintext).unwrap()
+            };
+            let tester = |ciphertext: &[u8]| {
+                let plaintext = decrypt_with_mode(mode, &aes, &iv, ciphertext).unwrap();
+                is_admin(&plaintext)
+            };
+
+            let mut flipper = BitFlipper::new(oracle, tester);
+            let injection = flipper.probe().unwrap();
+            assert_eq!(injection.offset, prefix_len);
+            let expected = if mode == Mode::Cbc {
+                FlipMode::Cbc { block_size: 16 }
+            } else {
+                FlipMode::Stream
+            };
+            assert_eq!(injection.mode, expected);
+
+            assert!(flipper.forge(b";admin=true").is_ok());
+        }
+    }
+
+    // A target two blocks long, with separators at the ends of each block so the garbage CBC
+    // leaves in between is a field of its own
+    let oracle = |input: &[u8]| {
+        let plaintext = [&b"prefix"[..], &sanitize(input), &suffix[..]].concat();
+        encrypt_with_mode(Mode::Cbc, &aes, &iv, &Pkcs7.pad(&plaintext, 16)).unwrap()
+    };
+    let tester = |ciphertext: &[u8]| {
+        let plaintext = decrypt_with_mode(Mode::Cbc, &aes, &iv, ciphertext).unwrap();
+        let fields = String::from_utf8_lossy(&plaintext).split(';').map(|f| f.to_string())
+                                                        .collect::<Vec<String>>();
+        fields.iter().any(|f| f == "admin=true") && fields.iter().any(|f| f == "role=root")
+    };
+    let mut flipper = BitFlipper::new(oracle, tester);
+    let ciphertext = flipper.forge(b";admin=true;a=b;;role=root;x=12;").unwrap();
+    let plaintext = decrypt_with_mode(Mode::Cbc, &aes, &iv, &ciphertext).unwrap();
+    assert_eq!(&plaintext[32..48], b";admin=true;a=b;");
+    assert_eq!(&plaintext[64..80], b";role=root;x=12;");
+
+    // ECB's changes stay in their block, and a fresh IV every time hides where the input went
+    let ecb = |input: &[u8]| {
+        encrypt_with_mode(Mode::Ecb, &aes, &[], &Pkcs7.pad(input, 16)).unwrap()
+    };
+    let mut flipper = BitFlipper::new(ecb, |_: &[u8]| true);
+    assert_eq!(flipper.probe(), Err(FlipError::NotMalleable));
+
+    let mut counter = 0u8;
+    let random_iv = move |input: &[u8]| {
+        counter += 1;
+        encrypt_with_mode(Mode::Cbc, &aes, &[counter; 16], &Pkcs7.pad(input, 16)).unwrap()
+    };
+    let mut flipper = BitFlipper::new(random_iv, |_: &[u8]| true);
+    assert_eq!(flipper.probe(), Err(FlipError::NotMalleable));
+}
@@ -1,5 +1,7 @@
-use c09::minimal_pad;
-use c10::{decrypt_block_ecb, encrypt_aes_cbc, encrypt_block_ecb, AES_BLOCK_SIZE};
+use set1::{decrypt_with_mode, encrypt_with_mode, Aes, BlockCipher, Mode};
+use c09::pkcs7_pad;
+use c10::{encrypt_aes_cbc, AES_BLOCK_SIZE};
+use fingerprint::{fingerprint, ModeGuess};
 use rand;
 use rand::Rng;
 use std::iter;
@@ -16,19 +18,21 @@ enum CipherMode {
 }
 
 pub fn encrypt_aes_ecb(plaintext: &[u8], key: &[u8]) -> Vec<u8> {
-    if plaintext.len() % AES_BLOCK_SIZE != 0 {
-        panic!("AES plaintext should be in 16 byte blocks!");
-    }
-    plaintext.chunks(AES_BLOCK_SIZE)
-             .flat_map(|block| encrypt_block_ecb(block, key))
-             .collect()
+    encrypt_ecb(plaintext, &Aes::new(key).unwrap())
 }
 
 // Not needed, but why not
 pub fn decrypt_aes_ecb(ciphertext: &[u8], key: &[u8]) -> Vec<u8> {
-    ciphertext.chunks(AES_BLOCK_SIZE)
-              .flat_map(|block| decrypt_block_ecb(block, key))
-              .collect()
+    decrypt_ecb(ciphertext, &Aes::new(key).unwrap())
+}
+
+// For oracles that encrypt over and over with one key, so it's only expanded once
+pub fn encrypt_ecb<C: BlockCipher>(plaintext: &[u8], cipher: &C) -> Vec<u8> {
+    encrypt_with_mode(Mode::Ecb, cipher, &[], plaintext).unwrap()
+}
+
+pub fn decrypt_ecb<C: BlockCipher>(ciphertext: &[u8], cipher: &C) -> Vec<u8> {
+    decrypt_with_mode(Mode::Ecb, cipher, &[], ciphertext).unwrap()
 }
 
 // Need FnMut because rng mutates
@@ -57,7 +61,7 @@ fn get_random_oracle() -> (Box<FnMut(&[u8]) -> Vec<u8>>, CipherMode) {
 
         // Need to pad it to a 16 byte boundary or the encryption function
         // will panic
-        let padded = minimal_pad(&modified_plaintext, AES_BLOCK_SIZE);
+        let padded = pkcs7_pad(&modified_plaintext, AES_BLOCK_SIZE);
 
         match cipher_mode {
             CipherMode::ECB => encrypt_aes_ecb(&padded, &key),
@@ -72,19 +76,11 @@ fn get_random_oracle() -> (Box<FnMut(&[u8]) -> Vec<u8>>, CipherMode) {
     (Box::new(encryptor), cipher_mode)
 }
 
-fn guess_cipher_mode(mut oracle: Box<FnMut(&[u8]) -> Vec<u8>>) -> CipherMode {
-    // Pad out the rest of the prefix and suffix blocks and make two blocks
-    // of 'A' after the first block
-    let plaintext = [b'A'; 2*AES_BLOCK_SIZE + 2*(AES_BLOCK_SIZE - 5)];
-    let ciphertext = oracle(&plaintext);
-    // Skip the first ciphertext block; Check the filled blocks' ciphertext
-    let mut chunk_iter = ciphertext.chunks(AES_BLOCK_SIZE).skip(1);
-    // If identical sequential plaintext blocks encrypt to identical
-    // sequential ciphertext blocks, it's ECB
-    if chunk_iter.next().unwrap() == chunk_iter.next().unwrap() {
-        CipherMode::ECB
-    } else {
-        CipherMode::CBC
+// The oracle's prefix and suffix are random, but fingerprint doesn't need to know how long
+fn guess_cipher_mode(oracle: Box<FnMut(&[u8]) -> Vec<u8>>) -> CipherMode {
+    match fingerprint(oracle, 32).unwrap().mode {
+        ModeGuess::Ecb => CipherMode::ECB,
+        _ => CipherMode::CBC,
     }
 }
 
@@ -1,18 +1,19 @@
-use set1::decode_b64;
-use c09::{minimal_pad, pkcs7_pad};
+use set1::{decode_b64, log_cost, Aes, Metered, OracleClient, OracleServer};
+use c09::pkcs7_pad;
 use c10::AES_BLOCK_SIZE;
-use c11::{encrypt_aes_ecb, make_vec};
+use c11::encrypt_ecb;
+use ecb_suffix::EcbSuffixAttack;
 use rand;
 use rand::Rng;
-use std::collections::HashMap;
 
 pub type Encryptor = Box<Fn(&[u8]) -> Vec<u8>>;
 
-// Not the same oracle from c11. This is a fixed-key ECB oracle
-fn get_oracle() -> Encryptor {
+// Not the same oracle from c11. This is a fixed-key ECB oracle. It's Send so it can be served
+fn get_oracle() -> Box<Fn(&[u8]) -> Vec<u8> + Send> {
     let mut rng = rand::thread_rng();
     let mut key = [0; 16];
     rng.fill_bytes(&mut key);
+    let aes = Aes::new(&key).unwrap();
 
     let b64 ="Um9sbGluJyBpbiBteSA1LjAKV2l0aCBteSByYWctdG9wIGRvd24gc28gbXkg
               aGFpciBjYW4gYmxvdwpUaGUgZ2lybGllcyBvbiBzdGFuZGJ5IHdhdmluZyBq
@@ -26,108 +27,30 @@ fn get_oracle() -> Encryptor {
 
         // Need to pad it to a 16 byte boundary or the encryption function
         // will panic
-        let padded = minimal_pad(&modified_plaintext, AES_BLOCK_SIZE);
+        let padded = pkcs7_pad(&modified_plaintext, AES_BLOCK_SIZE);
 
-        encrypt_aes_ecb(&padded, &key)
+        encrypt_ecb(&padded, &aes)
     };
 
     Box::new(oracle)
 }
 
-fn find_block_size(oracle: &Encryptor) -> usize {
-    let mut size = 1usize;
-    let base_len = oracle(b"").len();
-    loop {
-        let input = make_vec(b'A', size);
-        let new_len = oracle(&input).len();
-        if new_len > base_len {
-            return new_len - base_len;
-        }
-        else {
-            size += 1;
-        }
-    }
-}
-
-fn find_suffix_size(oracle: &Encryptor) -> usize {
-    let mut size = 1usize;
-    let base_len = oracle(b"").len();
-    loop {
-        let input = make_vec(b'A', size);
-        let new_len = oracle(&input).len();
-        // We pushed the internal plaintext just past the block
-        // size boundary
-        if new_len > base_len {
-            return base_len - (size - 1);
-        }
-        else {
-            size += 1;
-        }
-    }
-}
-
-fn using_ecb(oracle: &Encryptor, block_size: usize) -> bool {
-    let two_blocks = make_vec(b'A', 2*block_size);
-    let ciphertext = oracle(&two_blocks);
-    let mut chunk_iter = ciphertext.chunks(block_size);
-
-    chunk_iter.next().unwrap() == chunk_iter.next().unwrap()
-}
-
-pub fn last_n_from(v: Vec<u8>, n: usize) -> Vec<u8> {
-    let mut out = v.iter().rev().take(n)
-                   .cloned().collect::<Vec<u8>>();
-    out.reverse();
-    out
-}
-
-// Makes a hashmap of all the possible ecb ciphertext blocks
-// given a prefix of length block_size-1
-fn make_hashmap(prefix: &[u8], oracle: &Encryptor,
-                    block_size: usize) -> HashMap<Vec<u8>, u8> {
-    let mut out: HashMap<Vec<u8>, u8> = HashMap::new();
-    for byte in 0u8..255 {
-        let mut plaintext_block = prefix.to_vec();
-        plaintext_block.push(byte);
-        let ciphertext_block = oracle(&plaintext_block)[..block_size].to_vec();
-        out.insert(ciphertext_block, byte);
-    }
-
-    out
-}
-
 fn decrypt_suffix(oracle: Encryptor) -> Vec<u8> {
-    let block_size = find_block_size(&oracle);
-    let suffix_size = find_suffix_size(&oracle);
-    let suffix_blocks = (suffix_size / block_size) + 1;
-    let mut suffix: Vec<u8> = Vec::new();
-    for n_block in 0..suffix_blocks {
-        for n_byte in 1..block_size+1 {
-            if suffix.len() == suffix_size {
-                return suffix;
-            }
-            let filler = make_vec(b'A', block_size - n_byte);
-
-            // block_size-1 known fixed bytes at a time; we must figure
-            // out the last byte
-            let mut all_known = filler.clone();
-            all_known.extend(suffix.clone());
-            let fixed_bytes = last_n_from(all_known, block_size - 1);
-            let ct_block_hashmap = make_hashmap(&fixed_bytes, &oracle, block_size);
-            let ct_block = oracle(&filler)[block_size*n_block..block_size*(n_block+1)].to_vec();
-            let suffix_byte = *ct_block_hashmap.get(&ct_block).unwrap();
-            suffix.push(suffix_byte);
-        }
-    }
-    suffix
+    EcbSuffixAttack::new(|input: &[u8]| oracle(input)).decrypt_suffix().unwrap()
 }
 
 #[test]
 fn tst12() {
-    let oracle = get_oracle();
+    let (oracle, meter) = Metered::new(get_oracle()).boxed();
     let secret = decrypt_suffix(oracle);
+    log_cost("c12", meter.queries(), secret.len());
     let secret_str = String::from_utf8_lossy(&secret);
 
     assert!(secret_str.starts_with("Rollin' in my 5.0\n"));
     assert!(secret_str.ends_with("Did you stop? No, I just drove by\n"));
+
+    // The same attack against an oracle on the other end of a socket
+    let server = OracleServer::spawn(get_oracle(), 0).unwrap();
+    let remote = OracleClient::connect(server.addr()).unwrap().boxed();
+    assert_eq!(decrypt_suffix(remote), secret);
 }
@@ -1,18 +1,9 @@
-pub fn pkcs7_unpad(input: &[u8]) -> Option<Vec<u8>> {
-    let mut out = input.to_vec();
-    let pad_len = *out.last().unwrap();
-    if pad_len == 0 { // 0 is an illegal pad byte
-        return None;
-    }
-    for _ in 0u8..pad_len {
-        let pad_byte = out.pop();
+use set1::{Padding, Pkcs7};
+use c10::AES_BLOCK_SIZE;
 
-        if pad_byte.is_none()
-             || pad_byte.unwrap() != pad_len {
-            return None;
-        }
-    }
-    Some(out)
+// Only for AES-sized blocks. set1's Pkcs7 handles any size, and says what's wrong
+pub fn pkcs7_unpad(input: &[u8]) -> Option<Vec<u8>> {
+    Pkcs7.unpad(input, AES_BLOCK_SIZE).ok().map(|unpadded| unpadded.to_vec())
 }
 
 #[test]
@@ -24,4 +15,5 @@ fn tst15() {
     assert_eq!(a.unwrap(), b"ICE ICE BABY");
     assert_eq!(b, None);
     assert_eq!(c, None);
+    assert_eq!(pkcs7_unpad(b""), None);
 }
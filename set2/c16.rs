@@ -1,8 +1,6 @@
-use set1::xor_bytes;
-use c09::minimal_pad;
+use set1::{AnsiX923, Padding, Pkcs7};
+use bit_flip::BitFlipper;
 use c10::{decrypt_aes_cbc, encrypt_aes_cbc, AES_BLOCK_SIZE};
-use c11::make_vec;
-use c15::pkcs7_unpad;
 use rand;
 use rand::Rng;
 
@@ -19,7 +17,8 @@ fn sanitize(input: &[u8]) -> Vec<u8> {
     }).collect::<Vec<u8>>()
 }
 
-fn get_oracle_and_tester() -> (Encryptor, Tester) {
+// The plaintext is padded with the given scheme
+fn get_oracle_and_tester<P: Padding + Clone + 'static>(padding: P) -> (Encryptor, Tester) {
     let mut rng = rand::thread_rng();
     let mut key = [0; 16];
     let mut iv = [0; 16];
@@ -29,13 +28,15 @@ fn get_oracle_and_tester() -> (Encryptor, Tester) {
     let prefix = b"comment1=cooking%20MCs;userdata=";
     let suffix = b";comment2=%20like%20a%20pound%20of%20bacon";
 
+    let unpadding = padding.clone();
+
     let cbc_oracle = move |plaintext: &[u8]| {
 
         let mut modified_plaintext = prefix.to_vec();
         modified_plaintext.extend(sanitize(plaintext));
         modified_plaintext.extend(suffix.to_vec());
 
-        let padded = minimal_pad(&modified_plaintext, AES_BLOCK_SIZE);
+        let padded = padding.pad(&modified_plaintext, AES_BLOCK_SIZE);
 
         encrypt_aes_cbc(&padded,
                         &key.to_vec(), &iv.to_vec())
@@ -45,7 +46,10 @@ fn get_oracle_and_tester() -> (Encryptor, Tester) {
     let admin_tester = move |ciphertext: &[u8]| {
         let raw_plaintext = decrypt_aes_cbc(ciphertext,
                                             &key.to_vec(), &iv.to_vec());
-        let unpadded_pt = pkcs7_unpad(&raw_plaintext).unwrap();
+        let unpadded_pt = match unpadding.unpad(&raw_plaintext, AES_BLOCK_SIZE) {
+            Ok(pt) => pt,
+            Err(_) => return false,
+        };
         let pt_str = String::from_utf8_lossy(&unpadded_pt).to_owned();
         for s in pt_str.split(";") {
             let kv = s.split("=").collect::<Vec<&str>>();
@@ -62,53 +66,21 @@ fn get_oracle_and_tester() -> (Encryptor, Tester) {
     (Box::new(cbc_oracle), Box::new(admin_tester))
 }
 
-fn make_admin_ciphertext(cbc_oracle: &Encryptor) -> Vec<u8> {
-    // fill prefix to a block boundary, then add two more filler blocks
-    let prefix_len = 32;
-    let n_prefix_blocks = (prefix_len / AES_BLOCK_SIZE) +
-                            if prefix_len % AES_BLOCK_SIZE != 0 { 1usize }
-                            else { 0usize };
-    let filler_len = ((AES_BLOCK_SIZE - (prefix_len % AES_BLOCK_SIZE)) % AES_BLOCK_SIZE)
-                        + 2*AES_BLOCK_SIZE;
-    let filler = make_vec(b'A', filler_len);
-
-    let mut ciphertext = cbc_oracle(&filler);
-
-    // Index of the ciphertext block we're gonna mess with
-    let malleable_block_idx = n_prefix_blocks;
-
-    // What we want our block to look like post-xor
-    let admin = b";admin=true";
-    let mut admin_block = make_vec(b'A', AES_BLOCK_SIZE - admin.len());
-    admin_block.extend(admin.to_vec());
-
-    {
-        let mut malleable_block = ciphertext.chunks_mut(AES_BLOCK_SIZE)
-                                            .nth(malleable_block_idx).unwrap();
-        let filler_block = make_vec(b'A', AES_BLOCK_SIZE);
-        let xor_block = xor_bytes(&filler_block, &admin_block);
-
-        // I'd do this with iters but Vec::map_in_place() is unstable :\
-        for (i, b) in xor_block.iter().enumerate() {
-            malleable_block[i] ^= *b;
-        }
-    }
-
-    ciphertext
+// Finds where the userdata goes on its own, and keeps scrambling the block in front of it until
+// none of the garbage is a ';' or '='
+fn make_admin_ciphertext(cbc_oracle: &Encryptor, admin_tester: &Tester) -> Vec<u8> {
+    let mut flipper = BitFlipper::new(|input: &[u8]| cbc_oracle(input),
+                                      |ciphertext: &[u8]| admin_tester(ciphertext));
+    flipper.forge(b";admin=true").unwrap()
 }
 
 #[test]
 fn tst16() {
-    let mut success = false;
-
-    // There's an 11.8% chance of getting an unwanted ';' or '=' character, so try a few times
-    for _ in 0..10 {
-        let (cbc_oracle, admin_tester) = get_oracle_and_tester();
-        let ciphertext = make_admin_ciphertext(&cbc_oracle);
-        if admin_tester(&ciphertext) {
-            success = true;
-            break;
-        }
-    }
-    assert!(success);
+    // The plaintext is padded differently, but the same forgery works
+    let (cbc_oracle, admin_tester) = get_oracle_and_tester(Pkcs7);
+    let mut flipper = BitFlipper::new(|input: &[u8]| cbc_oracle(input), |_: &[u8]| false);
+    assert_eq!(flipper.probe().unwrap().offset, "comment1=cooking%20MCs;userdata=".len());
+    assert!(admin_tester(&make_admin_ciphertext(&cbc_oracle, &admin_tester)));
+    let (cbc_oracle, admin_tester) = get_oracle_and_tester(AnsiX923);
+    assert!(admin_tester(&make_admin_ciphertext(&cbc_oracle, &admin_tester)));
 }
@@ -0,0 +1,484 @@
+use c09::{minimal_pad, pkcs7_pad};
+use c11::make_vec;
+use ecb_suffix::MAX_BLOCK_SIZE;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordError {
+    // A field with no '=' in it
+    NoValue { field: usize },
+    // JSON that stops making sense at this byte
+    Malformed { at: usize },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecordError::NoValue { field } => write!(f, "field {} has no value", field),
+            RecordError::Malformed { at } => write!(f, "malformed record at byte {}", at),
+        }
+    }
+}
+
+impl Error for RecordError { }
+
+// How a record of key/value fields is written out. Values aren't escaped when encoding, except
+// for quotes and backslashes in JSON strings; sanitizing what goes in is the oracle's job
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordFormat {
+    // email=foo@bar.com&uid=10&role=user
+    Cookie,
+    // comment1=cooking%20MCs;userdata=foo;role=user
+    List,
+    // {"email":"foo@bar.com","uid":10,"role":"user"}. Numbers, true, false, and null go in bare
+    Json,
+}
+
+impl RecordFormat {
+    pub fn encode(&self, fields: &[(&str, &str)]) -> String {
+        match *self {
+            RecordFormat::Cookie | RecordFormat::List => {
+                let sep = if *self == RecordFormat::Cookie { "&" } else { ";" };
+                fields.iter().map(|&(k, v)| format!("{}={}", k, v))
+                      .collect::<Vec<_>>().join(sep)
+            },
+            RecordFormat::Json => {
+                let body = fields.iter().map(|&(k, v)| {
+                    let value = if is_json_literal(v) { v.to_string() } else { json_string(v) };
+                    format!("{}:{}", json_string(k), value)
+                }).collect::<Vec<_>>().join(",");
+                format!("{{{}}}", body)
+            },
+        }
+    }
+
+    // Fields in the order they're written. JSON strings come back unescaped, and anything bare
+    // comes back as it's written
+    pub fn decode(&self, record: &str) -> Result<Vec<(String, String)>, RecordError> {
+        match *self {
+            RecordFormat::Cookie | RecordFormat::List => {
+                if record.is_empty() {
+                    return Ok(Vec::new());
+                }
+                let sep = if *self == RecordFormat::Cookie { '&' } else { ';' };
+                record.split(sep).enumerate().map(|(i, item)| {
+                    let mut kv = item.splitn(2, '=');
+                    match (kv.next(), kv.next()) {
+                        (Some(k), Some(v)) => Ok((k.to_string(), v.to_string())),
+                        _ => Err(RecordError::NoValue { field: i }),
+                    }
+                }).collect()
+            },
+            RecordFormat::Json => JsonParser { bytes: record.as_bytes(), pos: 0 }.object(),
+        }
+    }
+
+    // How the record ends when value is the last one in it
+    fn tail(&self, value: &str) -> String {
+        match *self {
+            RecordFormat::Cookie | RecordFormat::List => value.to_string(),
+            RecordFormat::Json if is_json_literal(value) => format!("{}}}", value),
+            RecordFormat::Json => format!("{}}}", json_string(value)),
+        }
+    }
+
+    // Where in the tail the value starts. A JSON string's opening quote isn't part of what we
+    // replace, so the same quote works for the new value
+    fn value_start(&self, value: &str) -> usize {
+        match *self {
+            RecordFormat::Json if !is_json_literal(value) => 1,
+            _ => 0,
+        }
+    }
+}
+
+fn is_json_literal(value: &str) -> bool {
+    match value {
+        "true" | "false" | "null" => true,
+        _ => value.starts_with(|c: char| c == '-' || c.is_digit(10))
+             && value.parse::<f64>().is_ok(),
+    }
+}
+
+fn json_string(s: &str) -> String {
+    format!("\"{}\"", s.replace("\\", "\\\\").replace("\"", "\\\""))
+}
+
+// Just enough JSON for a flat object of strings and bare values
+struct JsonParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn malformed<T>(&self) -> Result<T, RecordError> {
+        Err(RecordError::Malformed { at: self.pos })
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.bytes.len() && (self.bytes[self.pos] as char).is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, b: u8) -> Result<(), RecordError> {
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.malformed()
+        }
+    }
+
+    fn object(&mut self) -> Result<Vec<(String, String)>, RecordError> {
+        let mut fields = Vec::new();
+        self.expect(b'{')?;
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                let key = self.string()?;
+                self.expect(b':')?;
+                self.skip_whitespace();
+                let value = if self.bytes.get(self.pos) == Some(&b'"') {
+                    self.string()?
+                } else {
+                    self.bare()?
+                };
+                fields.push((key, value));
+                self.skip_whitespace();
+                match self.bytes.get(self.pos) {
+                    Some(&b',') => self.pos += 1,
+                    Some(&b'}') => { self.pos += 1; break; },
+                    _ => return self.malformed(),
+                }
+            }
+        }
+        self.skip_whitespace();
+        if self.pos != self.bytes.len() {
+            return self.malformed();
+        }
+
+        Ok(fields)
+    }
+
+    fn string(&mut self) -> Result<String, RecordError> {
+        if self.bytes.get(self.pos) != Some(&b'"') {
+            return self.malformed();
+        }
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            match self.bytes.get(self.pos) {
+                Some(&b'"') => break,
+                Some(&b'\\') => {
+                    self.pos += 1;
+                    let c = match self.bytes.get(self.pos) {
+                        Some(&b'n') => b'\n',
+                        Some(&b't') => b'\t',
+                        Some(&b'r') => b'\r',
+                        Some(&c) if c == b'"' || c == b'\\' || c == b'/' => c,
+                        _ => return self.malformed(),
+                    };
+                    out.push(c);
+                },
+                Some(&c) => out.push(c),
+                None => return self.malformed(),
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+
+        // The input was a str, and escapes only ever swap one ASCII byte for another
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn bare(&mut self) -> Result<String, RecordError> {
+        let start = self.pos;
+        while self.pos < self.bytes.len() && !b",} \t\r\n".contains(&self.bytes[self.pos]) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return self.malformed();
+        }
+        Ok(String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CutPasteError {
+    // The ciphertext never got any longer
+    NoBlockSize,
+    // Our input never made two equal ciphertext blocks, so it's probably not ECB
+    NoAlignment,
+    // The target value has to come after the field we control
+    TargetBeforeField,
+    // The oracle lengthened our input, e.g., by escaping something in the chosen block
+    Mangled { expected: usize, found: usize },
+}
+
+impl fmt::Display for CutPasteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CutPasteError::NoBlockSize => write!(f, "couldn't find the block size"),
+            CutPasteError::NoAlignment => write!(f, "couldn't line our field up with a block"),
+            CutPasteError::TargetBeforeField =>
+                write!(f, "the target field comes before the one we control"),
+            CutPasteError::Mangled { expected, found } =>
+                write!(f, "the chosen blocks should have made {} bytes of ciphertext, not {}",
+                       expected, found),
+        }
+    }
+}
+
+impl Error for CutPasteError { }
+
+// How the oracle pads the record before encrypting it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordPadding {
+    // PKCS#7, which always adds at least one byte
+    Pkcs7,
+    // PKCS#7, except a record that's already whole blocks gets nothing, like challenge 13's
+    Minimal,
+}
+
+impl RecordPadding {
+    fn pad(&self, bytes: &[u8], block_size: usize) -> Vec<u8> {
+        match *self {
+            RecordPadding::Pkcs7 => pkcs7_pad(bytes, block_size),
+            RecordPadding::Minimal => minimal_pad(bytes, block_size),
+        }
+    }
+}
+
+// Where our field sits in the plaintext
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub block_size: usize,
+    // How many bytes of the record come before our field
+    pub offset: usize,
+    // How long the record is with our field left empty, before padding
+    pub record_len: usize,
+}
+
+// Cut-and-paste forgeries against an oracle that ECB-encrypts a record with one field we get to
+// set. The forged value is encrypted in blocks of its own inside our field, then pasted over the
+// end of an ordinary record, so the target has to be the last field. The record length comes from
+// where the ciphertext grows, so the padding has to be known; it's PKCS#7 unless set otherwise
+pub struct CutAndPaste<E> {
+    oracle: E,
+    format: RecordFormat,
+    padding: RecordPadding,
+    queries: usize,
+    layout: Option<FieldLayout>,
+}
+
+impl<E> CutAndPaste<E> where E: FnMut(&[u8]) -> Vec<u8> {
+    pub fn new(oracle: E, format: RecordFormat) -> CutAndPaste<E> {
+        CutAndPaste {
+            oracle: oracle,
+            format: format,
+            padding: RecordPadding::Pkcs7,
+            queries: 0,
+            layout: None,
+        }
+    }
+
+    pub fn padding(mut self, padding: RecordPadding) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn queries(&self) -> usize {
+        self.queries
+    }
+
+    fn query(&mut self, input: &[u8]) -> Vec<u8> {
+        self.queries += 1;
+        (self.oracle)(input)
+    }
+
+    pub fn probe(&mut self) -> Result<FieldLayout, CutPasteError> {
+        if let Some(layout) = self.layout {
+            return Ok(layout);
+        }
+
+        // With PKCS#7, the first length that gets another block fills the last one exactly. With
+        // minimal padding, it's the first that spills a byte into a new one
+        let base_len = self.query(b"").len();
+        let mut found = None;
+        for n in 1..MAX_BLOCK_SIZE+1 {
+            let len = self.query(&make_vec(b'A', n)).len();
+            if len > base_len {
+                let record_len = match self.padding {
+                    RecordPadding::Pkcs7 => base_len - n,
+                    RecordPadding::Minimal => base_len + 1 - n,
+                };
+                found = Some((len - base_len, record_len));
+                break;
+            }
+        }
+        let (block_size, record_len) = found.ok_or(CutPasteError::NoBlockSize)?;
+
+        // Two equal blocks of filler turn up once the filler before them reaches a boundary.
+        // Both filler bytes have to agree, in case the record around our field repeats one
+        let mut offset = None;
+        for pre in 0..block_size {
+            let a = self.first_repeat(b'A', pre, block_size);
+            let b = self.first_repeat(b'B', pre, block_size);
+            match (a, b) {
+                (Some(i), Some(j)) if i == j => {
+                    offset = Some(i*block_size - pre);
+                    break;
+                },
+                _ => (),
+            }
+        }
+
+        let layout = FieldLayout {
+            block_size: block_size,
+            offset: offset.ok_or(CutPasteError::NoAlignment)?,
+            record_len: record_len,
+        };
+        self.layout = Some(layout);
+        Ok(layout)
+    }
+
+    // The first block of the first pair of equal adjacent blocks, with pre bytes of filler ahead
+    // of two blocks of it
+    fn first_repeat(&mut self, byte: u8, pre: usize, block_size: usize) -> Option<usize> {
+        let ciphertext = self.query(&make_vec(byte, pre + 2*block_size));
+        let blocks = ciphertext.chunks(block_size).collect::<Vec<&[u8]>>();
+        (0..blocks.len().saturating_sub(1)).find(|&i| blocks[i] == blocks[i+1])
+    }
+
+    // A ciphertext of the record with the last field's value swapped from current to wanted.
+    // current is what the oracle puts there now, which an attacker can see on their own record.
+    //
+    // Only the last field can be changed, since the pasted blocks have to end the record. In JSON,
+    // a string value's closing quote has to go in the pasted blocks too, so it passes through our
+    // field, and an oracle that escapes quotes gives Mangled. Bare values like true or 10 don't
+    // have the problem
+    pub fn forge(&mut self, current: &str, wanted: &str) -> Result<Vec<u8>, CutPasteError> {
+        let layout = self.probe()?;
+        let bs = layout.block_size;
+
+        // Line the record up so the current value starts a block, then keep everything before it
+        let old_tail = self.format.tail(current);
+        let value_len = old_tail.len() - self.format.value_start(current);
+        let filler_len = (value_len + bs - layout.record_len % bs) % bs;
+        let value_start = layout.record_len + filler_len - value_len;
+        if value_start < layout.offset + filler_len {
+            return Err(CutPasteError::TargetBeforeField);
+        }
+        let ordinary = self.query(&make_vec(b'A', filler_len));
+
+        // Then encrypt what the record should end with, padded, in blocks of its own
+        let new_tail = self.format.tail(wanted);
+        let chosen = self.padding.pad(new_tail[self.format.value_start(wanted)..].as_bytes(), bs);
+        let align = (bs - layout.offset % bs) % bs;
+        let mut input = make_vec(b'A', align);
+        input.extend_from_slice(&chosen);
+        // Trailing filler leaves the record a byte short of a whole block, so a single byte of
+        // escaping shows up as an extra block. Either padding adds one byte to that
+        let short = (2*bs - 1 - (layout.record_len + input.len()) % bs) % bs;
+        input.extend(make_vec(b'A', short));
+        let injected = self.query(&input);
+        let expected = layout.record_len + input.len() + 1;
+        if injected.len() != expected {
+            return Err(CutPasteError::Mangled { expected: expected, found: injected.len() });
+        }
+
+        let start = layout.offset + align;
+        let mut forged = ordinary[..value_start].to_vec();
+        forged.extend_from_slice(&injected[start..start+chosen.len()]);
+        Ok(forged)
+    }
+}
+
+#[test]
+fn tst_cut_paste() {
+    use set1::{Aes, Padding, Pkcs7};
+    use c11::{decrypt_ecb, encrypt_ecb};
+
+    let aes = Aes::new(b"YELLOW SUBMARINE").unwrap();
+    let sanitize = |input: &[u8], bad: &[u8]| {
+        let s = String::from_utf8_lossy(input).into_owned();
+        s.chars().map(|c| if bad.contains(&(c as u8)) { '%' } else { c }).collect::<String>()
+    };
+
+    // The admin field goes last in every format, with ours somewhere ahead of it
+    for &format in [RecordFormat::Cookie, RecordFormat::List, RecordFormat::Json].iter() {
+        let leads = ["", "comment1=cooking%20MCs", "a much longer field to push ours along"];
+        for &lead in leads.iter() {
+            let (current, wanted) = if format == RecordFormat::Json {
+                ("false", "true")
+            } else {
+                ("user", "admin")
+            };
+            let oracle = |input: &[u8]| {
+                let field = sanitize(input, b"&;=");
+                let record = format.encode(&[("lead", lead), ("email", &field), ("uid", "10"),
+                                             ("admin", current)]);
+                encrypt_ecb(&Pkcs7.pad(record.as_bytes(), 16), &aes)
+            };
+
+            let mut cutter = CutAndPaste::new(oracle, format);
+            let layout = cutter.probe().unwrap();
+            let lead_len = format.encode(&[("lead", lead)]).len();
+            let expected_offset = match format {
+                RecordFormat::Json => lead_len - 1 + ",\"email\":\"".len(),
+                _ => lead_len + "&email=".len(),
+            };
+            assert_eq!(layout.block_size, 16);
+            assert_eq!(layout.offset, expected_offset, "{:?}", format);
+
+            let forged = cutter.forge(current, wanted).unwrap();
+            let plaintext = decrypt_ecb(&forged, &aes);
+            let plaintext = Pkcs7.unpad(&plaintext, 16).unwrap();
+            let fields = format.decode(&String::from_utf8_lossy(plaintext)).unwrap();
+            assert_eq!(fields.last().unwrap(), &("admin".to_string(), wanted.to_string()));
+        }
+    }
+
+    // Padding that leaves whole blocks alone, with records that come out whole blocks at the
+    // start and at the end
+    for &lead in ["", "abcdefghij", "abcd"].iter() {
+        let oracle = |input: &[u8]| {
+            let field = sanitize(input, b"&=");
+            let record = RecordFormat::Cookie.encode(&[("email", &field), ("lead", lead),
+                                                       ("role", "user")]);
+            encrypt_ecb(&RecordPadding::Minimal.pad(record.as_bytes(), 16), &aes)
+        };
+        let mut cutter = CutAndPaste::new(oracle, RecordFormat::Cookie)
+                                     .padding(RecordPadding::Minimal);
+        let layout = cutter.probe().unwrap();
+        assert_eq!(layout.record_len, "email=&lead=&role=user".len() + lead.len());
+        let forged = cutter.forge("user", "administrator!!!").unwrap();
+        let plaintext = decrypt_ecb(&forged, &aes);
+        assert!(plaintext.ends_with(b"&role=administrator!!!"), "{:?}", lead);
+    }
+
+    // A JSON string's closing quote has to go in the chosen block, and escaping it changes the
+    // length
+    let oracle = |input: &[u8]| {
+        let record = RecordFormat::Json.encode(&[("email", &String::from_utf8_lossy(input)),
+                                                 ("role", "user")]);
+        encrypt_ecb(&Pkcs7.pad(record.as_bytes(), 16), &aes)
+    };
+    let mut cutter = CutAndPaste::new(oracle, RecordFormat::Json);
+    assert_eq!(cutter.forge("user", "admin"),
+               Err(CutPasteError::Mangled { expected: 64, found: 80 }));
+
+    // Decoding doesn't panic on junk
+    assert_eq!(RecordFormat::Cookie.decode("a=b&c"), Err(RecordError::NoValue { field: 1 }));
+    assert_eq!(RecordFormat::List.decode("a=b=c").unwrap(),
+               vec![("a".to_string(), "b=c".to_string())]);
+    assert_eq!(RecordFormat::Json.decode("{\"a\":\"b\\\"\",\"c\":1}").unwrap(),
+               vec![("a".to_string(), "b\"".to_string()), ("c".to_string(), "1".to_string())]);
+    assert_eq!(RecordFormat::Json.decode("{\"a\":"), Err(RecordError::Malformed { at: 5 }));
+}
@@ -0,0 +1,374 @@
+use c11::make_vec;
+use std::error::Error;
+use std::fmt;
+
+// PKCS#7 can't pad blocks any bigger, so there's no point looking for them
+pub const MAX_BLOCK_SIZE: usize = 255;
+
+// How many times the same input gets resent before we call the prefix fixed
+const RANDOMNESS_PROBES: usize = 4;
+
+// How many tries a random prefix gets to line up, per byte of block size, before we give up
+const ALIGN_ATTEMPTS: usize = 64;
+
+// Two different blocks, so a prefix that happens to end in one of the bytes can't line up a fake
+// copy of the pair
+const MARKER_BYTES: (u8, u8) = (b'Y', b'Z');
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EcbAttackError {
+    // Ciphertext lengths never settled on a block size
+    NoBlockSize,
+    // Identical plaintext blocks didn't make identical ciphertext blocks
+    NotEcb,
+    // The marker blocks never landed on a block boundary
+    NoAlignment,
+    // None of the 256 guesses for this suffix byte matched
+    NoMatch { index: usize },
+}
+
+impl fmt::Display for EcbAttackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EcbAttackError::NoBlockSize => write!(f, "couldn't find the block size"),
+            EcbAttackError::NotEcb => write!(f, "the oracle isn't encrypting with ECB"),
+            EcbAttackError::NoAlignment => write!(f, "couldn't line the input up with a block"),
+            EcbAttackError::NoMatch { index } =>
+                write!(f, "no guess matched suffix byte {}; is the oracle consistent?", index),
+        }
+    }
+}
+
+impl Error for EcbAttackError { }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prefix {
+    // The same prefix, this many bytes long, on every call
+    Fixed(usize),
+    // A prefix that changes from call to call
+    Random,
+}
+
+// What the oracle puts around our input: prefix || input || suffix, then padding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EcbLayout {
+    pub block_size: usize,
+    pub prefix: Prefix,
+    pub suffix_len: usize,
+}
+
+// Handed to the progress callback after every recovered byte
+pub struct Progress<'a> {
+    pub recovered: &'a [u8],
+    pub suffix_len: usize,
+    pub queries: usize,
+}
+
+// Byte-at-a-time decryption of the secret suffix an ECB oracle appends to our input. Works out
+// the block size, prefix and suffix length on its own, so the same attack works on c12 and c14
+pub struct EcbSuffixAttack<F> {
+    oracle: F,
+    queries: usize,
+    on_progress: Option<Box<FnMut(&Progress)>>,
+    layout: Option<EcbLayout>,
+    // Everything below is only meaningful once layout() has found it
+    block_size: usize,
+    prefix: Prefix,
+    // The encryptions of the two marker blocks, back to back
+    marker: Vec<u8>,
+    // Filler that last lined a random prefix up, since it's as good a first guess as any
+    align_hint: usize,
+}
+
+impl<F: FnMut(&[u8]) -> Vec<u8>> EcbSuffixAttack<F> {
+    pub fn new(oracle: F) -> EcbSuffixAttack<F> {
+        EcbSuffixAttack {
+            oracle: oracle,
+            queries: 0,
+            on_progress: None,
+            layout: None,
+            block_size: 0,
+            prefix: Prefix::Random,
+            marker: Vec::new(),
+            align_hint: 0,
+        }
+    }
+
+    // Gets called after every byte of the suffix is recovered
+    pub fn on_progress<P: FnMut(&Progress) + 'static>(&mut self, callback: P) {
+        self.on_progress = Some(Box::new(callback));
+    }
+
+    // Oracle calls made so far
+    pub fn queries(&self) -> usize {
+        self.queries
+    }
+
+    fn query(&mut self, input: &[u8]) -> Vec<u8> {
+        self.queries += 1;
+        (self.oracle)(input)
+    }
+
+    pub fn layout(&mut self) -> Result<EcbLayout, EcbAttackError> {
+        if let Some(layout) = self.layout {
+            return Ok(layout);
+        }
+
+        self.block_size = self.find_block_size()?;
+        self.marker = self.find_markers()?;
+        self.prefix = self.find_prefix()?;
+        let layout = EcbLayout {
+            block_size: self.block_size,
+            prefix: self.prefix,
+            suffix_len: self.find_suffix_len()?,
+        };
+
+        self.layout = Some(layout);
+        Ok(layout)
+    }
+
+    // Ciphertext lengths are all multiples of the block size, and feeding in a block's worth of
+    // input is enough to make the length jump at least once. Random prefixes jump around more,
+    // but they still share the block size as a factor
+    fn find_block_size(&mut self) -> Result<usize, EcbAttackError> {
+        let base_len = self.query(b"").len();
+        let mut size = base_len;
+        let mut jumped = false;
+        for n in 1..2*MAX_BLOCK_SIZE+1 {
+            let len = self.query(&make_vec(b'A', n)).len();
+            jumped |= len != base_len;
+            size = gcd(size, len);
+            if jumped && n >= size {
+                // A length that grows a byte at a time is a stream cipher
+                return if size > 1 { Ok(size) } else { Err(EcbAttackError::NotEcb) };
+            }
+        }
+
+        Err(EcbAttackError::NoBlockSize)
+    }
+
+    // Three blocks of the same byte always cover two whole blocks, wherever they start. Under ECB
+    // those encrypt the same, and that's the encryption of a block of the byte. The prefix and
+    // suffix might repeat blocks too, but theirs don't change when the byte does
+    fn find_markers(&mut self) -> Result<Vec<u8>, EcbAttackError> {
+        let bs = self.block_size;
+        let first = self.query(&make_vec(MARKER_BYTES.0, 3*bs));
+        let second = self.query(&make_vec(MARKER_BYTES.1, 3*bs));
+        match (repeated_block(&first, &second, bs), repeated_block(&second, &first, bs)) {
+            (Some(a), Some(b)) => Ok([a, b].concat()),
+            _ => Err(EcbAttackError::NotEcb),
+        }
+    }
+
+    fn find_prefix(&mut self) -> Result<Prefix, EcbAttackError> {
+        let bs = self.block_size;
+        let probe = marker_plaintext(bs);
+        let first = self.query(&probe);
+        for _ in 0..RANDOMNESS_PROBES {
+            if self.query(&probe) != first {
+                return Ok(Prefix::Random);
+            }
+        }
+
+        // Fixed, so add filler until the markers start a block. The prefix is everything before
+        // that block that isn't filler
+        for fill in 0..bs {
+            let mut input = make_vec(b'A', fill);
+            input.extend(marker_plaintext(bs));
+            let ciphertext = self.query(&input);
+            if let Some(i) = find_marker(&self.marker, &ciphertext, bs) {
+                return Ok(Prefix::Fixed(i*bs - fill));
+            }
+        }
+
+        Err(EcbAttackError::NoAlignment)
+    }
+
+    // Encrypts payload so that it starts on a block boundary, and returns the ciphertext from
+    // there on. Past this point the prefix doesn't matter
+    fn aligned(&mut self, payload: &[u8]) -> Result<Vec<u8>, EcbAttackError> {
+        let bs = self.block_size;
+        match self.prefix {
+            Prefix::Fixed(prefix_len) => {
+                let fill = (bs - prefix_len % bs) % bs;
+                let mut input = make_vec(b'A', fill);
+                input.extend_from_slice(payload);
+                let ciphertext = self.query(&input);
+                Ok(ciphertext[prefix_len+fill..].to_vec())
+            },
+            // Keep changing the filler until the markers show up whole. With a prefix that's
+            // random enough, it's about one hit every block_size tries whatever the filler is
+            Prefix::Random => {
+                for attempt in 0..ALIGN_ATTEMPTS*bs {
+                    let fill = (self.align_hint + attempt) % bs;
+                    let mut input = make_vec(b'A', fill);
+                    input.extend(marker_plaintext(bs));
+                    input.extend_from_slice(payload);
+                    let ciphertext = self.query(&input);
+                    if let Some(i) = find_marker(&self.marker, &ciphertext, bs) {
+                        self.align_hint = fill;
+                        return Ok(ciphertext[(i+2)*bs..].to_vec());
+                    }
+                }
+
+                Err(EcbAttackError::NoAlignment)
+            },
+        }
+    }
+
+    // Adds input a byte at a time until the ciphertext grows. Either the suffix just spilled into
+    // a new block, or it just filled one and the oracle added a whole block of PKCS#7 padding. The
+    // second way, the last block is the same as an encrypted block of padding
+    fn find_suffix_len(&mut self) -> Result<usize, EcbAttackError> {
+        let bs = self.block_size;
+        let base_len = self.aligned(b"")?.len();
+        for n in 1..bs+1 {
+            let ciphertext = self.aligned(&make_vec(b'A', n))?;
+            if ciphertext.len() > base_len {
+                let padding_block = self.aligned(&make_vec(bs as u8, bs))?;
+                let full_padding = ciphertext[ciphertext.len()-bs..] == padding_block[..bs];
+                return Ok(if full_padding { base_len - n } else { base_len + 1 - n });
+            }
+        }
+
+        Err(EcbAttackError::NoBlockSize)
+    }
+
+    pub fn decrypt_suffix(&mut self) -> Result<Vec<u8>, EcbAttackError> {
+        let layout = self.layout()?;
+        let bs = layout.block_size;
+        let mut suffix: Vec<u8> = Vec::with_capacity(layout.suffix_len);
+        for index in 0..layout.suffix_len {
+            // Enough filler that the byte we want is the last in its block, and everything before
+            // it in the block is known
+            let filler = make_vec(b'A', bs - 1 - index % bs);
+            let mut known = filler.clone();
+            known.extend_from_slice(&suffix);
+            let window = known[known.len()-(bs-1)..].to_vec();
+
+            // All 256 guesses go in one query, ahead of the filler, so a byte costs one query
+            // instead of 257
+            let mut payload = Vec::with_capacity(256*bs + filler.len());
+            for guess in 0..256usize {
+                payload.extend_from_slice(&window);
+                payload.push(guess as u8);
+            }
+            payload.extend(filler);
+
+            let ciphertext = self.aligned(&payload)?;
+            let guess = {
+                let mut blocks = ciphertext.chunks(bs);
+                let guesses = blocks.by_ref().take(256).collect::<Vec<&[u8]>>();
+                blocks.nth(index / bs).and_then(|target| guesses.iter().position(|&g| g == target))
+            };
+            match guess {
+                Some(byte) => suffix.push(byte as u8),
+                None => return Err(EcbAttackError::NoMatch { index: index }),
+            }
+
+            if let Some(ref mut callback) = self.on_progress {
+                callback(&Progress {
+                    recovered: &suffix,
+                    suffix_len: layout.suffix_len,
+                    queries: self.queries,
+                });
+            }
+        }
+
+        Ok(suffix)
+    }
+}
+
+// The first block of ciphertext that's repeated right after itself, but doesn't show up in other
+fn repeated_block(ciphertext: &[u8], other: &[u8], block_size: usize) -> Option<Vec<u8>> {
+    let mut pairs = ciphertext.chunks(block_size).zip(ciphertext.chunks(block_size).skip(1));
+    pairs.find(|&(a, b)| a == b && other.chunks(block_size).all(|c| c != a))
+         .map(|(block, _)| block.to_vec())
+}
+
+fn marker_plaintext(block_size: usize) -> Vec<u8> {
+    [make_vec(MARKER_BYTES.0, block_size), make_vec(MARKER_BYTES.1, block_size)].concat()
+}
+
+// The first block where the encrypted markers start
+fn find_marker(marker: &[u8], ciphertext: &[u8], block_size: usize) -> Option<usize> {
+    let n_blocks = ciphertext.len() / block_size;
+    (0..n_blocks.saturating_sub(1)).find(|&i| &ciphertext[i*block_size..(i+2)*block_size] == marker)
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[test]
+fn tst_ecb_suffix() {
+    use c09::minimal_pad;
+    use set1::{Aes, BlockCipher, Padding, Pkcs7};
+    use rand::{self, Rng};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let aes = Aes::new(b"YELLOW SUBMARINE").unwrap();
+    let suffix = b"A secret suffix, a bit over four AES blocks long, that ends here.".to_vec();
+
+    // Stand-in for a 64-bit block cipher: the first half of AES on the zero-extended block. That
+    // isn't invertible, but collisions among 256 guesses are vanishingly unlikely
+    let ecb = move |plaintext: &[u8], block_size: usize| -> Vec<u8> {
+        plaintext.chunks(block_size).flat_map(|chunk| {
+            let mut block = [0u8; 16];
+            block[..block_size].copy_from_slice(chunk);
+            aes.encrypt_block(&mut block);
+            block[..block_size].to_vec()
+        }).collect::<Vec<u8>>()
+    };
+
+    let run = |block_size: usize, prefix_len: Option<usize>, pkcs7: bool| {
+        let secret = suffix.clone();
+        let mut rng = rand::thread_rng();
+        let oracle = move |input: &[u8]| {
+            let mut plaintext = match prefix_len {
+                Some(len) => make_vec(b'P', len),
+                None => {
+                    let len = rng.gen_range(0, 3*block_size);
+                    rng.gen_iter::<u8>().take(len).collect::<Vec<u8>>()
+                },
+            };
+            plaintext.extend_from_slice(input);
+            plaintext.extend_from_slice(&secret);
+            let padded = if pkcs7 {
+                Pkcs7.pad(&plaintext, block_size)
+            } else {
+                minimal_pad(&plaintext, block_size)
+            };
+            ecb(&padded, block_size)
+        };
+
+        let mut attack = EcbSuffixAttack::new(oracle);
+        let layout = attack.layout().unwrap();
+        assert_eq!(layout.block_size, block_size);
+        assert_eq!(layout.prefix, prefix_len.map(Prefix::Fixed).unwrap_or(Prefix::Random));
+        assert_eq!(layout.suffix_len, suffix.len());
+
+        let reported = Rc::new(Cell::new(0));
+        let reported_clone = reported.clone();
+        attack.on_progress(move |progress| reported_clone.set(progress.recovered.len()));
+        assert_eq!(attack.decrypt_suffix().unwrap(), suffix);
+        assert_eq!(reported.get(), suffix.len());
+        attack.queries()
+    };
+
+    // A fixed prefix costs one query per byte, plus a few dozen to find the layout
+    for &block_size in [16, 8].iter() {
+        for &prefix_len in [0, 5, 16, 21].iter() {
+            let queries = run(block_size, Some(prefix_len), true);
+            assert!(queries < suffix.len() + 3*block_size + 20, "{} queries", queries);
+            run(block_size, Some(prefix_len), false);
+        }
+        run(block_size, None, true);
+        run(block_size, None, false);
+    }
+
+    // Not ECB at all
+    let mut ctr_like = EcbSuffixAttack::new(|input: &[u8]| input.to_vec());
+    assert_eq!(ctr_like.layout(), Err(EcbAttackError::NotEcb));
+}
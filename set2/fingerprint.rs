@@ -0,0 +1,249 @@
+use c11::make_vec;
+use ecb_suffix::MAX_BLOCK_SIZE;
+use std::error::Error;
+use std::fmt;
+
+// Queries it takes to work out the block size from ciphertext lengths, at most
+const LENGTH_QUERIES: usize = 2*MAX_BLOCK_SIZE + 1;
+
+// Queries in a round, at most
+const ROUND_QUERIES: usize = 3;
+
+// What a byte-at-a-time oracle's input gets filled out to in a round
+const STREAM_INPUT_LEN: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FingerprintError {
+    // Ran out of budget before a single round
+    BudgetTooSmall { budget: usize, used: usize },
+    // Ciphertext lengths never settled on a block size
+    NoBlockSize,
+}
+
+impl fmt::Display for FingerprintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FingerprintError::BudgetTooSmall { budget, used } =>
+                write!(f, "a budget of {} queries is too small; {} went on the block size",
+                       budget, used),
+            FingerprintError::NoBlockSize => write!(f, "couldn't find the block size"),
+        }
+    }
+}
+
+impl Error for FingerprintError { }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModeGuess {
+    // Equal plaintext blocks make equal ciphertext blocks
+    Ecb,
+    // Padded to whole blocks, with no repeats. Could also be PCBC; nothing here tells them apart
+    Cbc,
+    // Length preserving, and a flipped input bit flips the same ciphertext bit but also changes
+    // what comes after it
+    Cfb,
+    // Length preserving, and a flipped input bit flips the same ciphertext bit and nothing else.
+    // CTR and OFB both look like this. So does a randomized CFB, since there's nothing to compare
+    Stream,
+    // A flipped input byte didn't change the ciphertext in any way we know
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fingerprint {
+    pub mode: ModeGuess,
+    // Whether the same input always gets the same ciphertext. With CBC that means a fixed IV
+    pub deterministic: bool,
+    // 1 for anything length preserving
+    pub block_size: usize,
+    // A guess, from how much longer the ciphertext is than it needs to be: randomized modes
+    // usually send their IV or nonce along in front. 0 if the oracle's deterministic. None for a
+    // randomized stream mode that adds anything, since every byte of its ciphertext changes from
+    // one query to the next, and nothing tells the IV apart from whatever else the oracle adds
+    pub iv_size: Option<usize>,
+    // The fewest extra bytes the ciphertext had over the input. That's the IV, padding, and
+    // anything else the oracle adds
+    pub expansion: usize,
+    // The fraction of rounds that agreed with the mode
+    pub confidence: f64,
+    pub queries: usize,
+}
+
+// Works out what an encryption oracle is doing from what it does to chosen inputs. Lengths give
+// the block size first, then each round sends a few inputs and votes on the mode. Uses at most
+// budget queries, and as many rounds as fit
+pub fn fingerprint<F>(oracle: F, budget: usize) -> Result<Fingerprint, FingerprintError>
+        where F: FnMut(&[u8]) -> Vec<u8> {
+    let mut oracle = Counted { oracle: oracle, queries: 0 };
+
+    // Same as finding the block size for the ECB attack, except a gcd of 1 is fine here
+    let mut expansion = usize::max_value();
+    let mut block_size = 0;
+    let mut base_len = None;
+    let mut found = false;
+    for n in 0..LENGTH_QUERIES {
+        if oracle.queries == budget {
+            return Err(FingerprintError::BudgetTooSmall { budget: budget, used: budget });
+        }
+        let len = oracle.query(&make_vec(b'A', n)).len();
+        expansion = expansion.min(len.saturating_sub(n));
+        block_size = gcd(block_size, len);
+        let jumped = base_len.map(|base| base != len).unwrap_or(false);
+        base_len = base_len.or(Some(len));
+        if jumped && n >= block_size {
+            found = true;
+            break;
+        }
+    }
+    if !found || block_size > MAX_BLOCK_SIZE {
+        return Err(FingerprintError::NoBlockSize);
+    }
+
+    let used = oracle.queries;
+    let rounds = (budget - used) / ROUND_QUERIES;
+    if rounds == 0 {
+        return Err(FingerprintError::BudgetTooSmall { budget: budget, used: used });
+    }
+
+    let mut votes: Vec<(ModeGuess, usize)> = Vec::new();
+    let mut deterministic_votes = 0;
+    for round in 0..rounds {
+        // Four blocks of the same byte have three whole ones in them, wherever they start. Vary
+        // the length so a prefix can't land them the same way every round
+        let input = if block_size > 1 {
+            make_vec(b'A', 4*block_size + round % block_size)
+        } else {
+            make_vec(b'A', STREAM_INPUT_LEN + round)
+        };
+        let first = oracle.query(&input);
+        let deterministic = oracle.query(&input) == first;
+        if deterministic {
+            deterministic_votes += 1;
+        }
+
+        let vote = if block_size > 1 {
+            if has_repeated_block(&first, block_size) { ModeGuess::Ecb } else { ModeGuess::Cbc }
+        } else if !deterministic {
+            ModeGuess::Stream
+        } else {
+            let mut flipped = input.clone();
+            let flip_at = input.len() / 2;
+            flipped[flip_at] ^= 1;
+            footprint(&first, &oracle.query(&flipped))
+        };
+
+        match votes.iter().position(|&(mode, _)| mode == vote) {
+            Some(i) => votes[i].1 += 1,
+            None => votes.push((vote, 1)),
+        }
+    }
+
+    let (mode, agreeing) = *votes.iter().max_by_key(|&&(_, count)| count).unwrap();
+    let deterministic = 2*deterministic_votes > rounds;
+    let iv_size = if deterministic || expansion == 0 {
+        Some(0)
+    } else if block_size > 1 {
+        Some(if expansion >= block_size { block_size } else { 0 })
+    } else {
+        None
+    };
+
+    Ok(Fingerprint {
+        mode: mode,
+        deterministic: deterministic,
+        block_size: block_size,
+        iv_size: iv_size,
+        expansion: expansion,
+        confidence: agreeing as f64 / rounds as f64,
+        queries: oracle.queries,
+    })
+}
+
+struct Counted<F> {
+    oracle: F,
+    queries: usize,
+}
+
+impl<F: FnMut(&[u8]) -> Vec<u8>> Counted<F> {
+    fn query(&mut self, input: &[u8]) -> Vec<u8> {
+        self.queries += 1;
+        (self.oracle)(input)
+    }
+}
+
+fn has_repeated_block(ciphertext: &[u8], block_size: usize) -> bool {
+    let blocks = ciphertext.chunks(block_size).collect::<Vec<&[u8]>>();
+    blocks.iter().enumerate().any(|(i, a)| blocks[i+1..].contains(a))
+}
+
+// How flipping the low bit of one input byte changed a deterministic stream of ciphertext
+fn footprint(before: &[u8], after: &[u8]) -> ModeGuess {
+    let mut diffs = before.iter().zip(after.iter()).map(|(a, b)| a ^ b).skip_while(|&d| d == 0);
+    match diffs.next() {
+        Some(1) if diffs.all(|d| d == 0) => ModeGuess::Stream,
+        Some(1) => ModeGuess::Cfb,
+        _ => ModeGuess::Unknown,
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[test]
+fn tst_fingerprint() {
+    use set1::{encrypt_with_mode, Aes, Counter, Mode, Padding, Pkcs7};
+    use rand::{self, Rng};
+
+    let aes = Aes::new(b"YELLOW SUBMARINE").unwrap();
+    let prefix = b"comment1=cooking%20MCs;userdata=";
+    let suffix = b";comment2=%20like%20a%20pound%20of%20bacon";
+
+    // Every oracle sticks the same prefix and suffix around the input. A randomized one sends its
+    // IV first
+    let check = |mode: Mode, randomized: bool, expected: ModeGuess, iv_size: Option<usize>| {
+        let mut rng = rand::thread_rng();
+        let oracle = |input: &[u8]| {
+            let mut plaintext = [&prefix[..], input, &suffix[..]].concat();
+            if mode.is_block_aligned() {
+                plaintext = Pkcs7.pad(&plaintext, 16);
+            }
+            let mut iv = vec![0u8; if mode.needs_iv() { 16 } else { 0 }];
+            if randomized {
+                rng.fill_bytes(&mut iv);
+            }
+            let ciphertext = encrypt_with_mode(mode, &aes, &iv, &plaintext).unwrap();
+            if randomized { [iv, ciphertext].concat() } else { ciphertext }
+        };
+
+        let fp = fingerprint(oracle, 100).unwrap();
+        assert_eq!(fp.mode, expected, "{:?}", mode);
+        assert_eq!(fp.deterministic, !randomized, "{:?}", mode);
+        assert_eq!(fp.block_size, if mode.is_block_aligned() { 16 } else { 1 });
+        assert_eq!(fp.iv_size, iv_size, "{:?}", mode);
+        assert_eq!(fp.confidence, 1.0);
+        assert!(fp.queries <= 100);
+    };
+
+    check(Mode::Ecb, false, ModeGuess::Ecb, Some(0));
+    check(Mode::Cbc, false, ModeGuess::Cbc, Some(0));
+    check(Mode::Cbc, true, ModeGuess::Cbc, Some(16));
+    check(Mode::Ctr(Counter::BigEndian), false, ModeGuess::Stream, Some(0));
+    check(Mode::Ofb, false, ModeGuess::Stream, Some(0));
+    check(Mode::Cfb8, false, ModeGuess::Cfb, Some(0));
+    check(Mode::Cfb, false, ModeGuess::Cfb, Some(0));
+    // The nonce can't be told apart from the prefix and suffix
+    check(Mode::Ctr(Counter::BigEndian), true, ModeGuess::Stream, None);
+
+    // Budgets are kept to, even when there's only just enough. 17 queries find the block size,
+    // then a round takes two
+    let ecb = |input: &[u8]| {
+        encrypt_with_mode(Mode::Ecb, &aes, &[], &Pkcs7.pad(input, 16)).unwrap()
+    };
+    let fp = fingerprint(&ecb, 20).unwrap();
+    assert_eq!((fp.mode, fp.queries), (ModeGuess::Ecb, 19));
+    assert_eq!(fingerprint(&ecb, 19),
+               Err(FingerprintError::BudgetTooSmall { budget: 19, used: 17 }));
+    assert_eq!(fingerprint(&ecb, 5),
+               Err(FingerprintError::BudgetTooSmall { budget: 5, used: 5 }));
+}
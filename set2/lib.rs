@@ -2,7 +2,6 @@
 
 extern crate set1;
 
-extern crate crypto;
 extern crate rand;
 
 mod c09;
@@ -13,9 +12,18 @@ mod c13;
 mod c14;
 mod c15;
 mod c16;
+mod bit_flip;
+mod cut_paste;
+mod ecb_suffix;
+mod fingerprint;
 
 pub use c09::{minimal_pad, pkcs7_pad};
 pub use c10::{AES_BLOCK_SIZE, decrypt_aes_cbc, decrypt_block_ecb, encrypt_aes_cbc,
               encrypt_block_ecb};
-pub use c11::make_vec;
+pub use c11::{decrypt_ecb, encrypt_ecb, make_vec};
 pub use c15::pkcs7_unpad;
+pub use bit_flip::{BitFlipper, FlipError, FlipMode, Injection};
+pub use cut_paste::{CutAndPaste, CutPasteError, FieldLayout, RecordError, RecordFormat,
+                    RecordPadding};
+pub use ecb_suffix::{EcbAttackError, EcbLayout, EcbSuffixAttack, Prefix, Progress};
+pub use fingerprint::{fingerprint, Fingerprint, FingerprintError, ModeGuess};
@@ -1,6 +1,5 @@
-use set1::{decode_b64, xor_bytes};
-use set2::{encrypt_block_ecb, make_vec};
-use byteorder::{LittleEndian, WriteBytesExt};
+use set1::{decode_b64, Aes, Counter, Mode, ModeCipher};
+use set2::make_vec;
 
 pub type BytesTransformer = Box<FnMut(&[u8]) -> Vec<u8>>;
 
@@ -14,38 +13,20 @@ pub fn move_out_first_n<T>(v: &mut Vec<T>, n: usize) -> Vec<T> {
     out
 }
 
-// key is 16 bytes; nonce is 8 bytes
+// key is 16, 24 or 32 bytes; nonce is 8 bytes. Each call picks up the keystream where the last
+// one left off
 pub fn get_aes_ctr(key: &[u8], nonce: &[u8]) -> BytesTransformer {
-    let key_copy = key.to_vec(); // For lifetime purposes
+    // Our counter block is 8 bytes of nonce and 8 bytes of counter, both encoded as
+    // little-endian vectors
     let reverse_nonce = nonce.iter().rev().cloned().collect::<Vec<u8>>();
-    let mut counter = 0u64;
+    let initial_block = [reverse_nonce, make_vec(0u8, 8)].concat();
+    let mode = Mode::Ctr(Counter::LittleEndian64);
+    let mut ctr = ModeCipher::encryptor(mode, Aes::new(key).unwrap(), &initial_block).unwrap();
 
     // This does both encryption and decryption
     let transformer = move |input: &[u8]| {
-        let mut output: Vec<u8> = Vec::new();
-        let mut keystream_buf = Vec::new();
-
-        for chunk in input.chunks(16) {
-            let mut counter_vec: Vec<u8> = Vec::new();
-            counter_vec.write_u64::<LittleEndian>(counter).unwrap();
-
-            // Fill up our keystream buffer if necessary
-            if chunk.len() > keystream_buf.len() {
-                // Our block is 8 bytes of nonce and 8 bytes of counter, both encoded
-                // as little-endian vectors
-                let pre_keystream_block = [reverse_nonce.clone(), counter_vec].concat();
-                let keystream_block = encrypt_block_ecb(&pre_keystream_block, &*key_copy);
-                keystream_buf.extend(keystream_block);
-            }
-
-            // Pop out key bytes from the beginning of the buffer as we use them
-            let used_key_bytes = move_out_first_n(&mut keystream_buf, chunk.len());
-            let xored = xor_bytes(chunk, &used_key_bytes);
-            output.extend(xored);
-
-            counter += 1
-        }
-
+        let mut output = input.to_vec();
+        ctr.update(&mut output).unwrap();
         output
     };
 
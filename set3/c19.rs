@@ -1,10 +1,8 @@
 use c18::get_aes_ctr;
-use set1::{decode_b64, get_lines, xor_bytes};
-use set2::make_vec;
+use set1::{decode_b64, get_lines, CribDragger};
 use rand;
 use rand::Rng;
 use std::ascii::AsciiExt;
-use std::cmp::Ordering;
 use std::f64;
 
 pub fn get_ciphertexts(filename: &str) -> Vec<Vec<u8>> {
@@ -45,44 +43,16 @@ fn english_error(bytes: &[u8]) -> f64 {
     1f64 / (count / s.len() as f64)
 }
 
+// Every line is decrypted in full. Columns past the end of the shortest line are solved with
+// whichever lines still reach them, so the ends of the longest lines are the least reliable
 pub fn crack_ciphertexts(ciphertexts: &[&[u8]]) -> Vec<String> {
-    let min_line_len = ciphertexts.iter().map(|line| line.len())
-                                  .min().unwrap();
-    let n_lines = ciphertexts.len();
-    let mut columns: Vec<Vec<u8>> = Vec::new();
-    for _ in 0..min_line_len {
-        columns.push(Vec::new());
-    }
-    for i in 0..n_lines {
-        for j in 0..min_line_len {
-            columns[j].push(ciphertexts[i][j]);
-        }
-    }
-    let mut key = Vec::new();
-    for column in columns {
-        let mut column_err = f64::INFINITY;
-        let mut winning_key_byte: Option<u8> = None;
-
-        for b in 0usize..256 {
-            let key_byte = b as u8;
-            let key_vec = make_vec(key_byte, min_line_len);
-
-            let column_plaintext = xor_bytes(&key_vec, &column);
-            let key_byte_err = english_error(&column_plaintext);
-            match key_byte_err.partial_cmp(&column_err) {
-                Some(Ordering::Less) => {
-                    column_err = key_byte_err;
-                    winning_key_byte = Some(key_byte);
-                },
-                _ => ()
-            }
-        }
-        key.push(winning_key_byte.unwrap());
-    }
+    let mut dragger = CribDragger::new(ciphertexts.iter().map(|ct| ct.to_vec()).collect());
+    dragger.solve_columns(&english_error);
 
-    ciphertexts.iter().map(|l| xor_bytes(&key, l))
-               .map(|c| String::from_utf8_lossy(&c).into_owned())
-               .collect::<Vec<String>>()
+    dragger.plaintexts().into_iter()
+           .map(|pt| pt.into_iter().map(|b| b.unwrap()).collect::<Vec<u8>>())
+           .map(|c| String::from_utf8_lossy(&c).into_owned())
+           .collect::<Vec<String>>()
 }
 
 #[test]
@@ -5,9 +5,13 @@ fn tst20() {
     let ciphertexts = get_ciphertexts("c20.txt");
     let borrowed = ciphertexts.iter().map(|b| &**b).collect::<Vec<&[u8]>>();
     let plaintexts = crack_ciphertexts(&borrowed);
-    assert_eq!(plaintexts[8],  "Friday the thirteenth, walking down Elm Street / You ");
-    assert_eq!(plaintexts[26], "You want to hear some sounds that not only pounds but");
-    assert_eq!(plaintexts[58], "Turn down the bass down / And let the beat just keep ");
+    // The shortest line is 53 bytes, but the lines are recovered past that. The very ends of the
+    // longest ones are only covered by a line or two, so they come out garbled
+    assert_eq!(plaintexts[8],  "Friday the thirteenth, walking down Elm Street / You come in my \
+                                realm ya get beat!");
+    assert!(plaintexts[26].starts_with("You want to hear some sounds that not only pounds but \
+                                        please your eardrums"));
+    assert_eq!(plaintexts[58], "Turn down the bass down / And let the beat just keep on rockin'");
 
     /*for (i, line) in plaintexts.iter().enumerate() {
         println!("({}) {}", i, line);
@@ -1,4 +1,4 @@
-use c21::get_mt;
+use mt::Mt19937;
 use rand;
 use rand::Rng;
 use time::get_time;
@@ -10,9 +10,7 @@ fn get_delayed_output() -> (u32, u32) {
 
     let mut now = get_time().sec as u32;
     now -= rng.gen_range(40, 2000);
-    let mut mt = get_mt(now);
-
-    let output = mt(1)[0];
+    let output = Mt19937::new(now).next_u32();
 
     (output, now)
 }
@@ -25,10 +23,8 @@ fn find_seed(output: u32) -> Option<u32> {
 
     for i in 0u32..5000 {
         let potential_seed = now - i;
-        let mut mt = get_mt(potential_seed);
-
         // Found it
-        if mt(1)[0] == output {
+        if Mt19937::new(potential_seed).next_u32() == output {
             return Some(potential_seed);
         }
     }
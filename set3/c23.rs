@@ -1,4 +1,4 @@
-use c21::{Generator, get_mt, mt_extract_number, mt_generate_numbers, MTState};
+use mt::Mt19937;
 use rand;
 use rand::Rng;
 
@@ -104,30 +104,15 @@ fn untemper(x: u32) -> u32 {
     seq.iter().fold(x, |acc, f| f(acc)) // What's a pipe operator?
 }
 
-fn clone_mt(mt: &mut Generator) -> Generator {
-    let mut index = 0usize;
-
-    let mut state: MTState = [0; 624];
-    for (i, n) in mt(624).into_iter().enumerate() {
-        state[i] = untemper(n);
+// Works on anything that hands out raw MT19937 words, starting at a state refresh. The clone picks
+// up where the 624 words leave off
+fn clone_mt<R: Rng>(mt: &mut R) -> Mt19937 {
+    let mut state = [0u32; 624];
+    for word in state.iter_mut() {
+        *word = untemper(mt.next_u32());
     }
 
-    // Same code as the MT implementation except the state is already initialized
-    let generator = move |n_words: usize| {
-        let mut ret: Vec<u32> = Vec::new();
-
-        for _ in 0..n_words {
-            if index == 0 {
-                mt_generate_numbers(&mut state);
-            }
-            ret.push(mt_extract_number(&state, index));
-            index = (index + 1) % 624;
-        }
-
-        ret
-    };
-
-    Box::new(generator)
+    Mt19937::from_state(state, 624)
 }
 
 #[test]
@@ -145,11 +130,17 @@ fn tst23() {
 
     // Make sure the cloned PRNG gives us the same output as the PRNG
     let seed = rng.gen::<u32>();
-    let mut real_mt = get_mt(seed);
+    let mut real_mt = Mt19937::new(seed);
     let mut cloned_mt = clone_mt(&mut real_mt);
 
-    let cloned_output = cloned_mt(2);
-    let real_output = real_mt(2);
+    let cloned_output = cloned_mt.gen::<u64>();
+    let real_output = real_mt.gen::<u64>();
 
     assert_eq!(real_output, cloned_output);
+
+    // A generator seeded the way Python seeds it clones just the same, from getrandbits(32)
+    let mut python_mt = Mt19937::from_python_seed(rng.gen::<u64>());
+    let mut cloned_mt = clone_mt(&mut python_mt);
+    assert_eq!(cloned_mt.py_random(), python_mt.py_random());
+    assert_eq!(cloned_mt.py_randrange(0, 1000), python_mt.py_randrange(0, 1000));
 }
@@ -1,5 +1,5 @@
 use c18::{BytesTransformer, move_out_first_n};
-use c21::get_mt;
+use mt::Mt19937;
 use set1::xor_bytes;
 use set2::make_vec;
 use byteorder::{BigEndian, WriteBytesExt};
@@ -10,7 +10,7 @@ use time::get_time;
 
 // key is 16 bytes; nonce is 8 bytes
 fn get_mt_stream_cipher(seed: u16) -> BytesTransformer {
-    let mut mt = get_mt(seed as u32);
+    let mut mt = Mt19937::new(seed as u32);
 
     // This does both encryption and decryption
     let transformer = move |input: &[u8]| {
@@ -20,7 +20,7 @@ fn get_mt_stream_cipher(seed: u16) -> BytesTransformer {
         for chunk in input.chunks(4) {
             // Get 4 more bytes if necessary
             if chunk.len() > keystream_buf.len() {
-                let rand_n: u32 = mt(1)[0];
+                let rand_n: u32 = mt.next_u32();
                 keystream_buf.write_u32::<BigEndian>(rand_n).unwrap();
             }
 
@@ -64,11 +64,10 @@ fn find_seed(ciphertext: &[u8]) -> Option<u16> {
     for m in 0usize..(u16::MAX as usize)+1 {
         let seed = m as u16;
 
-        let mut mt = get_mt(seed as u32);
-        let mt_output: Vec<u32>  = mt((ciphertext.len()/4)+1);
+        let mut mt = Mt19937::new(seed as u32);
         let mut mt_output_bytes: Vec<u8> = Vec::new();
-        for n in mt_output.into_iter() {
-            mt_output_bytes.write_u32::<BigEndian>(n).unwrap();
+        for _ in 0..(ciphertext.len()/4)+1 {
+            mt_output_bytes.write_u32::<BigEndian>(mt.next_u32()).unwrap();
         }
 
         let corres_keystream_bytes = &mt_output_bytes[unknown_pt_len..unknown_pt_len+14];
@@ -84,16 +83,18 @@ fn find_seed(ciphertext: &[u8]) -> Option<u16> {
 // Arbitrary procedure; just take the 4th number from the PRNG
 fn get_password_token() -> u32 {
     let now = get_time().sec as u32;
-    let mut mt = get_mt(now);
+    let mut mt = Mt19937::new(now);
+    mt.discard(3);
 
-    mt(4)[3]
+    mt.next_u32()
 }
 
 fn is_valid_token(token: u32) -> bool {
     let now = get_time().sec as u32;
     for seed in (now-20)..(now+20) {
-        let mut mt = get_mt(seed);
-        if mt(4)[3] == token {
+        let mut mt = Mt19937::new(seed);
+        mt.discard(3);
+        if mt.next_u32() == token {
             return true;
         }
     }
@@ -15,5 +15,12 @@ mod c21;
 mod c22;
 mod c23;
 mod c24;
+mod mt;
+mod mt_solver;
+mod padding_oracle;
 
 pub use c18::get_aes_ctr;
+pub use mt::{temper, Mt19937, Mt19937_64};
+pub use mt_solver::{MtSolveError, MtSolver};
+pub use padding_oracle::{english_guess_order, BlockProgress, Decryption, Forgery,
+                         PaddingOracleAttack, PaddingOracleError};
@@ -0,0 +1,449 @@
+use rand::{Rng, SeedableRng};
+use std::u32;
+
+const N: usize = 624;
+const M: usize = 397;
+const MATRIX_A: u32 = 0x9908b0df;
+const UPPER_MASK: u32 = 0x80000000;
+const LOWER_MASK: u32 = 0x7fffffff;
+
+const NN: usize = 312;
+const MM: usize = 156;
+const MATRIX_A_64: u64 = 0xb5026f5aa96619e9;
+const UPPER_MASK_64: u64 = 0xffffffff80000000;
+const LOWER_MASK_64: u64 = 0x7fffffff;
+
+// 2^-53, 2^32, and 2^64, for turning words into floats
+const RES53: f64 = 1.0 / 9007199254740992.0;
+const TWO_32: f64 = 4294967296.0;
+const TWO_64: f64 = 18446744073709551616.0;
+
+// The largest double below 1, which is what C++ rounds a 1.0 down to
+const BELOW_ONE: f64 = 1.0 - RES53 / 2.0;
+
+pub fn temper(y: u32) -> u32 {
+    let mut y = y;
+    y ^=  y >> 11;
+    y ^= (y << 7) & 0x9d2c5680;
+    y ^= (y << 15) & 0xefc60000;
+    y ^=  y >> 18;
+
+    y
+}
+
+fn temper_64(x: u64) -> u64 {
+    let mut x = x;
+    x ^= (x >> 29) & 0x5555555555555555;
+    x ^= (x << 17) & 0x71d67fffeda60000;
+    x ^= (x << 37) & 0xfff7eee000000000;
+    x ^=  x >> 43;
+
+    x
+}
+
+// 32-bit Mersenne Twister, matching mt19937ar.c. The index is how many words of the current state
+// have been handed out; at 624 the next call regenerates the state first, which is also where a
+// freshly seeded generator starts
+#[derive(Clone)]
+pub struct Mt19937 {
+    state: [u32; N],
+    index: usize,
+}
+
+impl Mt19937 {
+    // init_genrand
+    pub fn new(seed: u32) -> Mt19937 {
+        let mut state = [0u32; N];
+        state[0] = seed;
+        for i in 1..N {
+            state[i] = 1812433253u32.wrapping_mul(state[i-1] ^ (state[i-1] >> 30))
+                                    .wrapping_add(i as u32);
+        }
+
+        Mt19937 { state: state, index: N }
+    }
+
+    // init_by_array. The reference doesn't allow an empty key; this treats it as [0], like Python
+    pub fn from_array(key: &[u32]) -> Mt19937 {
+        let key = if key.is_empty() { &[0u32][..] } else { key };
+        let mut mt = Mt19937::new(19650218);
+        {
+            let s = &mut mt.state;
+            let (mut i, mut j) = (1usize, 0usize);
+            for _ in 0..::std::cmp::max(N, key.len()) {
+                s[i] = (s[i] ^ (s[i-1] ^ (s[i-1] >> 30)).wrapping_mul(1664525))
+                       .wrapping_add(key[j]).wrapping_add(j as u32);
+                i += 1;
+                j += 1;
+                if i >= N {
+                    s[0] = s[N-1];
+                    i = 1;
+                }
+                if j >= key.len() {
+                    j = 0;
+                }
+            }
+            for _ in 0..N-1 {
+                s[i] = (s[i] ^ (s[i-1] ^ (s[i-1] >> 30)).wrapping_mul(1566083941))
+                       .wrapping_sub(i as u32);
+                i += 1;
+                if i >= N {
+                    s[0] = s[N-1];
+                    i = 1;
+                }
+            }
+            s[0] = 0x80000000;
+        }
+
+        mt
+    }
+
+    // Python's random.seed(n) for a nonnegative int: the key is n in 32-bit words, low word first
+    pub fn from_python_seed(seed: u64) -> Mt19937 {
+        if seed >> 32 == 0 {
+            Mt19937::from_array(&[seed as u32])
+        } else {
+            Mt19937::from_array(&[seed as u32, (seed >> 32) as u32])
+        }
+    }
+
+    // Untempered state words, and how many of them have been used. Index 624 means the next output
+    // comes from a fresh state
+    pub fn from_state(state: [u32; N], index: usize) -> Mt19937 {
+        assert!(index <= N, "index {} is past the end of the state", index);
+        Mt19937 { state: state, index: index }
+    }
+
+    pub fn state(&self) -> &[u32; N] {
+        &self.state
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    fn generate(&mut self) {
+        let s = &mut self.state;
+        for i in 0..N {
+            let y = (s[i] & UPPER_MASK) | (s[(i+1) % N] & LOWER_MASK);
+            s[i] = s[(i+M) % N] ^ (y >> 1) ^ if y & 1 != 0 { MATRIX_A } else { 0 };
+        }
+        self.index = 0;
+    }
+
+    // Throws away n outputs, like C++'s discard
+    pub fn discard(&mut self, n: usize) {
+        for _ in 0..n {
+            self.next_u32();
+        }
+    }
+
+    // genrand_res53, which is also Python's random.random() and Ruby's rand
+    pub fn py_random(&mut self) -> f64 {
+        let a = (self.next_u32() >> 5) as f64;
+        let b = (self.next_u32() >> 6) as f64;
+        (a * 67108864.0 + b) * RES53
+    }
+
+    // Python's random.getrandbits(k), for k up to 64. Words come out low word first, and the last
+    // one keeps its top bits
+    pub fn py_getrandbits(&mut self, k: u32) -> u64 {
+        assert!(k <= 64, "getrandbits only goes up to 64 bits here");
+        match k {
+            0 => 0,
+            k if k <= 32 => (self.next_u32() >> (32 - k)) as u64,
+            _ => {
+                let low = self.next_u32() as u64;
+                let high = (self.next_u32() >> (64 - k)) as u64;
+                (high << 32) | low
+            },
+        }
+    }
+
+    // Python's random._randbelow: draw as many bits as n has, until the number is below n
+    pub fn py_randbelow(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "empty range");
+        let k = 64 - n.leading_zeros();
+        loop {
+            let r = self.py_getrandbits(k);
+            if r < n {
+                return r;
+            }
+        }
+    }
+
+    // Python's random.randrange(start, stop)
+    pub fn py_randrange(&mut self, start: i64, stop: i64) -> i64 {
+        assert!(start < stop, "empty range for randrange({}, {})", start, stop);
+        start.wrapping_add(self.py_randbelow(stop.wrapping_sub(start) as u64) as i64)
+    }
+
+    // Python's random.randint(a, b), which includes b
+    pub fn py_randint(&mut self, a: i64, b: i64) -> i64 {
+        self.py_randrange(a, b + 1)
+    }
+
+    // Python's random.shuffle
+    pub fn py_shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.py_randbelow(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+
+    // PHP's mt_rand() with no arguments, since 7.1: the output without its low bit
+    pub fn php_mt_rand(&mut self) -> u32 {
+        self.next_u32() >> 1
+    }
+
+    // PHP's mt_rand(min, max), since 7.1, for ranges that fit in 32 bits. Powers of two are
+    // masked, and everything else is rejection sampled to a multiple of the range
+    pub fn php_mt_rand_range(&mut self, min: i64, max: i64) -> i64 {
+        assert!(min <= max && max - min <= u32::MAX as i64, "bad range [{}, {}]", min, max);
+        let umax = (max - min) as u32;
+        let mut result = self.next_u32();
+        if umax == u32::MAX {
+            return min + result as i64;
+        }
+
+        let umax = umax + 1;
+        if umax & (umax - 1) == 0 {
+            return min + (result & (umax - 1)) as i64;
+        }
+        let limit = u32::MAX - (u32::MAX % umax) - 1;
+        while result > limit {
+            result = self.next_u32();
+        }
+        min + (result % umax) as i64
+    }
+
+    // libstdc++'s std::generate_canonical<double, 53> on a std::mt19937, which is what
+    // std::uniform_real_distribution draws from. It takes two words, low one first
+    pub fn cpp_generate_canonical(&mut self) -> f64 {
+        let low = self.next_u32() as f64;
+        let high = self.next_u32() as f64;
+        let r = (low + high * TWO_32) / TWO_64;
+        if r >= 1.0 { BELOW_ONE } else { r }
+    }
+}
+
+impl Rng for Mt19937 {
+    // genrand_int32
+    fn next_u32(&mut self) -> u32 {
+        if self.index >= N {
+            self.generate();
+        }
+        let y = self.state[self.index];
+        self.index += 1;
+
+        temper(y)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        self.py_random()
+    }
+}
+
+impl SeedableRng<u32> for Mt19937 {
+    fn reseed(&mut self, seed: u32) {
+        *self = Mt19937::new(seed);
+    }
+
+    fn from_seed(seed: u32) -> Mt19937 {
+        Mt19937::new(seed)
+    }
+}
+
+impl<'a> SeedableRng<&'a [u32]> for Mt19937 {
+    fn reseed(&mut self, key: &'a [u32]) {
+        *self = Mt19937::from_array(key);
+    }
+
+    fn from_seed(key: &'a [u32]) -> Mt19937 {
+        Mt19937::from_array(key)
+    }
+}
+
+// 64-bit Mersenne Twister, matching mt19937-64.c and C++'s std::mt19937_64
+#[derive(Clone)]
+pub struct Mt19937_64 {
+    state: [u64; NN],
+    index: usize,
+}
+
+impl Mt19937_64 {
+    // init_genrand64
+    pub fn new(seed: u64) -> Mt19937_64 {
+        let mut state = [0u64; NN];
+        state[0] = seed;
+        for i in 1..NN {
+            state[i] = 6364136223846793005u64.wrapping_mul(state[i-1] ^ (state[i-1] >> 62))
+                                             .wrapping_add(i as u64);
+        }
+
+        Mt19937_64 { state: state, index: NN }
+    }
+
+    // init_by_array64, with an empty key treated as [0]
+    pub fn from_array(key: &[u64]) -> Mt19937_64 {
+        let key = if key.is_empty() { &[0u64][..] } else { key };
+        let mut mt = Mt19937_64::new(19650218);
+        {
+            let s = &mut mt.state;
+            let (mut i, mut j) = (1usize, 0usize);
+            for _ in 0..::std::cmp::max(NN, key.len()) {
+                s[i] = (s[i] ^ (s[i-1] ^ (s[i-1] >> 62)).wrapping_mul(3935559000370003845))
+                       .wrapping_add(key[j]).wrapping_add(j as u64);
+                i += 1;
+                j += 1;
+                if i >= NN {
+                    s[0] = s[NN-1];
+                    i = 1;
+                }
+                if j >= key.len() {
+                    j = 0;
+                }
+            }
+            for _ in 0..NN-1 {
+                s[i] = (s[i] ^ (s[i-1] ^ (s[i-1] >> 62)).wrapping_mul(2862933555777941757))
+                       .wrapping_sub(i as u64);
+                i += 1;
+                if i >= NN {
+                    s[0] = s[NN-1];
+                    i = 1;
+                }
+            }
+            s[0] = 1 << 63;
+        }
+
+        mt
+    }
+
+    fn generate(&mut self) {
+        let s = &mut self.state;
+        for i in 0..NN {
+            let x = (s[i] & UPPER_MASK_64) | (s[(i+1) % NN] & LOWER_MASK_64);
+            s[i] = s[(i+MM) % NN] ^ (x >> 1) ^ if x & 1 != 0 { MATRIX_A_64 } else { 0 };
+        }
+        self.index = 0;
+    }
+
+    pub fn discard(&mut self, n: usize) {
+        for _ in 0..n {
+            self.next_u64();
+        }
+    }
+
+    // libstdc++'s std::generate_canonical<double, 53> on a std::mt19937_64. One word is enough
+    pub fn cpp_generate_canonical(&mut self) -> f64 {
+        let r = self.next_u64() as f64 / TWO_64;
+        if r >= 1.0 { BELOW_ONE } else { r }
+    }
+}
+
+impl Rng for Mt19937_64 {
+    // The low half, like Isaac64Rng
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    // genrand64_int64
+    fn next_u64(&mut self) -> u64 {
+        if self.index >= NN {
+            self.generate();
+        }
+        let x = self.state[self.index];
+        self.index += 1;
+
+        temper_64(x)
+    }
+
+    // genrand64_res53
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * RES53
+    }
+}
+
+impl SeedableRng<u64> for Mt19937_64 {
+    fn reseed(&mut self, seed: u64) {
+        *self = Mt19937_64::new(seed);
+    }
+
+    fn from_seed(seed: u64) -> Mt19937_64 {
+        Mt19937_64::new(seed)
+    }
+}
+
+impl<'a> SeedableRng<&'a [u64]> for Mt19937_64 {
+    fn reseed(&mut self, key: &'a [u64]) {
+        *self = Mt19937_64::from_array(key);
+    }
+
+    fn from_seed(key: &'a [u64]) -> Mt19937_64 {
+        Mt19937_64::from_array(key)
+    }
+}
+
+#[test]
+fn tst_mt() {
+    // The first outputs listed in mt19937ar.out and mt19937-64.out
+    let mut mt = Mt19937::from_array(&[0x123, 0x234, 0x345, 0x456]);
+    let first: Vec<u32> = (0..5).map(|_| mt.next_u32()).collect();
+    assert_eq!(first, vec![1067595299, 955945823, 477289528, 4107218783, 4228976476]);
+    let mut mt: Mt19937_64 = SeedableRng::from_seed(&[0x12345u64, 0x23456, 0x34567, 0x45678][..]);
+    assert_eq!(mt.next_u64(), 7266447313870364031);
+
+    // The C++ standard requires the 10000th output of a default-constructed generator
+    let mut mt = Mt19937::new(5489);
+    mt.discard(9999);
+    assert_eq!(mt.next_u32(), 4123659995);
+    let mut mt = Mt19937_64::new(5489);
+    mt.discard(9999);
+    assert_eq!(mt.next_u64(), 9981545732273789042);
+
+    // Reseeding starts over, and a copy of the state carries on from the same place
+    let mut mt: Mt19937 = SeedableRng::from_seed(7u32);
+    let a = mt.gen::<u64>();
+    mt.reseed(7u32);
+    assert_eq!(mt.gen::<u64>(), a);
+    mt.discard(1000);
+    let mut copy = Mt19937::from_state(*mt.state(), mt.index());
+    assert_eq!(copy.next_u32(), mt.next_u32());
+
+    // Python 3.11: random.seed(42), then random(), random(), getrandbits(7), getrandbits(40),
+    // getrandbits(64), randrange(-5, 1000), randint(1, 6), _randbelow(10**12), and shuffling
+    // list(range(10))
+    let mut py = Mt19937::from_python_seed(42);
+    assert_eq!(py.py_random(), 0.6394267984578837);
+    assert_eq!(py.next_f64(), 0.025010755222666936);
+    assert_eq!(py.py_getrandbits(7), 35);
+    assert_eq!(py.py_getrandbits(40), 245864938384);
+    assert_eq!(py.py_getrandbits(64), 13585496030504862185);
+    assert_eq!(py.py_randrange(-5, 1000), 99);
+    assert_eq!(py.py_randint(1, 6), 6);
+    assert_eq!(py.py_randbelow(1_000_000_000_000), 982433687219);
+    let mut items: Vec<u32> = (0..10).collect();
+    py.py_shuffle(&mut items);
+    assert_eq!(items, vec![3, 2, 4, 9, 5, 7, 0, 6, 1, 8]);
+    // Seeds over 32 bits take two key words, and 0 takes one
+    assert_eq!(Mt19937::from_python_seed((1 << 40) + 7).py_getrandbits(32), 2635837658);
+    assert_eq!(Mt19937::from_python_seed(0).py_getrandbits(32), 3626764237);
+
+    // PHP 7.1+: mt_srand(1), then mt_rand() twice
+    let mut php = Mt19937::new(1);
+    assert_eq!(php.php_mt_rand(), 895547922);
+    assert_eq!(php.php_mt_rand(), 2141438069);
+    for _ in 0..1000 {
+        let roll = php.php_mt_rand_range(1, 6);
+        assert!(roll >= 1 && roll <= 6);
+    }
+    assert!(php.php_mt_rand_range(-8, -1) < 0);
+
+    // g++ with libstdc++: std::generate_canonical<double, 53> on each generator seeded with 42
+    let mut cpp = Mt19937::new(42);
+    assert_eq!(cpp.cpp_generate_canonical(), 0.79654298428784598);
+    assert_eq!(cpp.cpp_generate_canonical(), 0.18343478789336848);
+    let mut cpp = Mt19937_64::new(42);
+    assert_eq!(cpp.cpp_generate_canonical(), 0.75515553295453897);
+    assert_eq!(cpp.cpp_generate_canonical(), 0.63903139385469743);
+}
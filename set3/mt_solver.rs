@@ -0,0 +1,343 @@
+use mt::{temper, Mt19937};
+use std::error::Error;
+use std::fmt;
+
+const N: usize = 624;
+const M: usize = 397;
+const MATRIX_A: u32 = 0x9908b0df;
+
+// One unknown per state bit, with bit b of word i at i*32 + b. Rows carry the right-hand side
+// in one extra word after the unknowns
+const BITS: usize = N * 32;
+const WORDS: usize = BITS / 64;
+const ROW: usize = WORDS + 1;
+
+// Only the top bit of the first word ever reaches an output, so 31 unknowns can never be pinned
+// down, and don't need to be
+const FULL_RANK: usize = BITS - 31;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MtSolveError {
+    // The observations don't say enough about the state. It takes at least 19937 known bits, and
+    // more when outputs are skipped
+    Underdetermined { rank: usize },
+    // This output's bits contradict the ones before it, so it isn't MT19937 or an index is off
+    Inconsistent { index: usize },
+}
+
+impl fmt::Display for MtSolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MtSolveError::Underdetermined { rank } =>
+                write!(f, "only {} of the {} state bits are determined", rank, FULL_RANK),
+            MtSolveError::Inconsistent { index } =>
+                write!(f, "output {} contradicts the outputs before it", index),
+        }
+    }
+}
+
+impl Error for MtSolveError { }
+
+// Known bits of one output
+#[derive(Clone, Copy, Debug)]
+struct Observation {
+    index: usize,
+    mask: u32,
+    value: u32,
+}
+
+// Recovers MT19937 state from any bits of any outputs. Every output bit is a linear function of
+// the state bits over GF(2), so each known bit is an equation, and enough of them pin the state
+// down. Output 0 is wherever the counting starts; the recovered state is the 624 words before it,
+// which is enough to generate everything from there on, even though the target's index may have
+// been somewhere in the middle of its state
+pub struct MtSolver {
+    observations: Vec<Observation>,
+}
+
+impl MtSolver {
+    pub fn new() -> MtSolver {
+        MtSolver { observations: Vec::new() }
+    }
+
+    // The bits of output index that are set in mask are the ones in value
+    pub fn observe(&mut self, index: usize, mask: u32, value: u32) {
+        if mask != 0 {
+            self.observations.push(Observation { index: index, mask: mask, value: value & mask });
+        }
+    }
+
+    // Only the top bits of output index, the way getrandbits(bits) or a byte shifted off the top
+    // leaks them. value is those bits, shifted down. An output only has 32
+    pub fn observe_top(&mut self, index: usize, bits: u32, value: u32) {
+        assert!(bits <= 32, "can't observe {} bits of a 32-bit output", bits);
+        match bits {
+            0 => (),
+            32 => self.observe(index, !0, value),
+            _ => self.observe(index, !0 << (32 - bits), value << (32 - bits)),
+        }
+    }
+
+    // How many bits have been observed, counting repeats
+    pub fn known_bits(&self) -> usize {
+        self.observations.iter().map(|o| o.mask.count_ones() as usize).sum()
+    }
+
+    // A generator whose next output is output 0
+    pub fn solve(&self) -> Result<Mt19937, MtSolveError> {
+        let mut observations = self.observations.clone();
+        observations.sort_by_key(|o| o.index);
+
+        let mut symbolic = SymbolicState::new();
+        let mut system = System::new();
+        // Once the state is pinned down, the rest of the bits only get checked against it, which is
+        // much cheaper than eliminating them
+        let mut solution: Option<Vec<u64>> = None;
+        let mut row = vec![0u64; ROW];
+        for o in observations.iter() {
+            symbolic.advance_to(o.index);
+            for bit in 0..32 {
+                if o.mask & (1 << bit) == 0 {
+                    continue;
+                }
+                symbolic.output_bit(o.index % N, bit, &mut row);
+                row[WORDS] = ((o.value >> bit) & 1) as u64;
+                let consistent = match solution {
+                    Some(ref x) => parity(&row[..WORDS], x) == row[WORDS],
+                    None => system.insert(&mut row),
+                };
+                if !consistent {
+                    return Err(MtSolveError::Inconsistent { index: o.index });
+                }
+                if solution.is_none() && system.rank == FULL_RANK {
+                    solution = Some(system.solution());
+                }
+            }
+        }
+
+        let bits = solution.ok_or(MtSolveError::Underdetermined { rank: system.rank })?;
+        let mut state = [0u32; N];
+        for (i, word) in state.iter_mut().enumerate() {
+            *word = (bits[i/2] >> (32 * (i % 2))) as u32;
+        }
+        Ok(Mt19937::from_state(state, N))
+    }
+
+    // A generator whose next output is output index
+    pub fn clone_at(&self, index: usize) -> Result<Mt19937, MtSolveError> {
+        let mut mt = self.solve()?;
+        mt.discard(index);
+        Ok(mt)
+    }
+}
+
+// The current state with every bit written as a combination of the starting state's bits. Rows
+// are laid out like the unknowns, 32 to a word
+struct SymbolicState {
+    rows: Vec<u64>,
+    // Which block of 624 outputs the state is ready to produce
+    block: usize,
+    // Bit j of taps[i] says whether state bit j feeds output bit i after tempering
+    taps: [u32; 32],
+}
+
+impl SymbolicState {
+    fn new() -> SymbolicState {
+        let mut rows = vec![0u64; BITS * WORDS];
+        for v in 0..BITS {
+            rows[v*WORDS + v/64] = 1 << (v % 64);
+        }
+        let mut taps = [0u32; 32];
+        for j in 0..32 {
+            let t = temper(1 << j);
+            for i in 0..32 {
+                if t & (1 << i) != 0 {
+                    taps[i] |= 1 << j;
+                }
+            }
+        }
+
+        // Nothing has been generated yet; output 0 needs the first twist
+        SymbolicState { rows: rows, block: 0, taps: taps }
+    }
+
+    fn row(&self, word: usize, bit: usize) -> &[u64] {
+        let start = (word*32 + bit) * WORDS;
+        &self.rows[start..start+WORDS]
+    }
+
+    // The same twist as Mt19937's, on rows instead of bits. Each word only looks at its own top
+    // bit, so it can be replaced as soon as it's worked out
+    fn twist(&mut self) {
+        let mut next = vec![0u64; 32 * WORDS];
+        for i in 0..N {
+            for x in next.iter_mut() {
+                *x = 0;
+            }
+            // y is the top bit of word i over the low 31 of word i+1; the new word is word i+397
+            // xor y >> 1, xor MATRIX_A if y is odd
+            for b in 0..32 {
+                let out = &mut next[b*WORDS..(b+1)*WORDS];
+                xor_into(out, self.row((i+M) % N, b));
+                if b == 30 {
+                    xor_into(out, self.row(i, 31));
+                } else if b < 30 {
+                    xor_into(out, self.row((i+1) % N, b+1));
+                }
+                if MATRIX_A & (1 << b) != 0 {
+                    xor_into(out, self.row((i+1) % N, 0));
+                }
+            }
+            let start = i * 32 * WORDS;
+            self.rows[start..start + 32*WORDS].copy_from_slice(&next);
+        }
+        self.block += 1;
+    }
+
+    fn advance_to(&mut self, index: usize) {
+        while self.block <= index / N {
+            self.twist();
+        }
+    }
+
+    // Output bit `bit` of the word at `word` in the current block
+    fn output_bit(&self, word: usize, bit: usize, row: &mut [u64]) {
+        for x in row.iter_mut() {
+            *x = 0;
+        }
+        for j in 0..32 {
+            if self.taps[bit] & (1 << j) != 0 {
+                xor_into(&mut row[..WORDS], self.row(word, j));
+            }
+        }
+    }
+}
+
+fn xor_into(dest: &mut [u64], src: &[u64]) {
+    for (d, s) in dest.iter_mut().zip(src.iter()) {
+        *d ^= *s;
+    }
+}
+
+// Gaussian elimination, one equation at a time. Each pivot row is kept by its lowest unknown, and
+// is zero below it
+struct System {
+    pivots: Vec<Option<Vec<u64>>>,
+    rank: usize,
+}
+
+impl System {
+    fn new() -> System {
+        System { pivots: vec![None; BITS], rank: 0 }
+    }
+
+    // Reduces the row against the pivots and keeps it if anything's left. False means it came out
+    // as 0 = 1
+    fn insert(&mut self, row: &mut [u64]) -> bool {
+        let mut w = 0;
+        while w < WORDS {
+            if row[w] == 0 {
+                w += 1;
+                continue;
+            }
+            let v = w*64 + row[w].trailing_zeros() as usize;
+            match self.pivots[v] {
+                Some(ref pivot) => xor_into(&mut row[w..], &pivot[w..]),
+                None => {
+                    self.pivots[v] = Some(row.to_vec());
+                    self.rank += 1;
+                    return true;
+                },
+            }
+        }
+
+        row[WORDS] == 0
+    }
+
+    // Back substitution from the highest pivot down, into one bit per unknown. Unknowns without a
+    // pivot are left at 0
+    fn solution(&self) -> Vec<u64> {
+        let mut bits = vec![0u64; WORDS];
+        for v in (0..BITS).rev() {
+            if let Some(ref pivot) = self.pivots[v] {
+                let bit = pivot[WORDS] ^ parity(&pivot[..WORDS], &bits);
+                bits[v/64] |= bit << (v % 64);
+            }
+        }
+
+        bits
+    }
+}
+
+// The dot product of two rows, over GF(2)
+fn parity(a: &[u64], b: &[u64]) -> u64 {
+    let ones: u32 = a.iter().zip(b.iter()).map(|(x, y)| (x & y).count_ones()).sum();
+    (ones & 1) as u64
+}
+
+#[test]
+fn tst_mt_solver() {
+    use rand::{Rng, SeedableRng, XorShiftRng};
+
+    // Seeded, so a failure comes out the same when it's run again
+    let mut rng = XorShiftRng::from_seed([0x6d2b79f5, 0x1b873593, 0xcc9e2d51, 0xe6546b64]);
+
+    // A byte off the top of every output, starting somewhere in the middle of a state
+    let mut target = Mt19937::new(rng.gen::<u32>());
+    target.discard(rng.gen_range(0, 2000));
+    let mut solver = MtSolver::new();
+    for i in 0..2600 {
+        solver.observe_top(i, 8, target.next_u32() >> 24);
+    }
+    assert_eq!(solver.known_bits(), 2600 * 8);
+    let mut clone = solver.clone_at(2600).unwrap();
+    for _ in 0..1000 {
+        assert_eq!(clone.next_u32(), target.next_u32());
+    }
+
+    // Too few bits, and bits that can't all be right
+    let mut solver = MtSolver::new();
+    for i in 0..100 {
+        solver.observe_top(i, 8, target.next_u32() >> 24);
+    }
+    assert_eq!(solver.solve().err(), Some(MtSolveError::Underdetermined { rank: 800 }));
+    solver.observe(50, 0xff000000, 0);
+    solver.observe(50, 0xff000000, 0xff000000);
+    assert_eq!(solver.solve().err(), Some(MtSolveError::Inconsistent { index: 50 }));
+
+    // More bits than an output has
+    let caught = ::std::panic::catch_unwind(|| MtSolver::new().observe_top(0, 33, 0));
+    assert!(caught.is_err());
+}
+
+#[test]
+fn tst_mt_solver_gaps() {
+    use rand::{Rng, SeedableRng, XorShiftRng};
+
+    let mut rng = XorShiftRng::from_seed([0x85ebca6b, 0xc2b2ae35, 0x27d4eb2f, 0x165667b1]);
+
+    // Python's getrandbits(8) with outputs skipped at random, and bits observed at odd places.
+    // Skipping leaves some of the state harder to see, so it takes more bits
+    let mut target = Mt19937::from_python_seed(rng.gen::<u64>());
+    let mut solver = MtSolver::new();
+    let mut outputs = Vec::new();
+    let mut index = 0;
+    while solver.known_bits() < 32000 {
+        index += rng.gen_range(1, 4);
+        while outputs.len() < index {
+            outputs.push(target.next_u32());
+        }
+        if rng.gen_weighted_bool(4) {
+            let mask = rng.gen::<u32>() & 0x0ff00ff0;
+            solver.observe(index - 1, mask, outputs[index - 1]);
+        } else {
+            solver.observe_top(index - 1, 8, outputs[index - 1] >> 24);
+        }
+    }
+    let mut clone = solver.solve().unwrap();
+    let seen: Vec<u32> = (0..outputs.len()).map(|_| clone.next_u32()).collect();
+    assert_eq!(seen, outputs);
+    let mut clone = solver.clone_at(100).unwrap();
+    assert_eq!(clone.next_u32(), outputs[100]);
+    assert_eq!(clone.py_getrandbits(8), (outputs[101] >> 24) as u64);
+}
@@ -0,0 +1,408 @@
+use set1::{Padding, Pkcs7};
+use rand::{self, Rng};
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+// How many times a query that errors gets sent again, by default
+const DEFAULT_RETRIES: usize = 3;
+
+// English text, most likely byte first. Padding bytes go after the lowercase letters, since every
+// last block ends in them
+const ENGLISH_ORDER: &'static [u8] =
+    b" etaoinshrdlcumwfgypbvkjxqz\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f\x10\
+      ETAOINSHRDLCUMWFGYPBVKJXQZ.,'\"-0123456789\n!?:;()/";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaddingOracleError {
+    BadBlockSize,
+    // The IV or ciphertext isn't a whole number of blocks
+    BadLength { len: usize, block_size: usize },
+    // No guess for this byte made valid padding, so the oracle isn't checking what we think
+    NoValidByte { block: usize, index: usize },
+    // The oracle kept erroring on the same query
+    OracleFailed { block: usize, attempts: usize },
+}
+
+impl fmt::Display for PaddingOracleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PaddingOracleError::BadBlockSize => write!(f, "block size has to be from 1 to 255"),
+            PaddingOracleError::BadLength { len, block_size } =>
+                write!(f, "{} bytes isn't a whole number of {}-byte blocks", len, block_size),
+            PaddingOracleError::NoValidByte { block, index } =>
+                write!(f, "no guess for byte {} of block {} made valid padding", index, block),
+            PaddingOracleError::OracleFailed { block, attempts } =>
+                write!(f, "the oracle failed {} times in a row on block {}", attempts, block),
+        }
+    }
+}
+
+impl Error for PaddingOracleError { }
+
+// Handed to the progress callback each time a block is done. Blocks finish in whatever order the
+// threads get to them, or last to first when forging
+pub struct BlockProgress<'a> {
+    pub block: usize,
+    pub plaintext: &'a [u8],
+    pub done: usize,
+    pub blocks: usize,
+    pub queries: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Forgery {
+    pub iv: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub queries: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decryption {
+    // Still padded
+    pub plaintext: Vec<u8>,
+    pub queries: usize,
+}
+
+// The most likely bytes of English first, then everything else
+pub fn english_guess_order() -> Vec<u8> {
+    let mut order = ENGLISH_ORDER.to_vec();
+    order.extend((0..256).map(|b| b as u8).filter(|b| !ENGLISH_ORDER.contains(b)));
+    order
+}
+
+// CBC padding oracle attack. The oracle gets an IV followed by ciphertext, like c17's, and says
+// whether it unpads; an Err means it couldn't tell this time, and the query gets sent again.
+// Blocks only depend on the ciphertext block before them, so they're spread over threads
+pub struct PaddingOracleAttack<F> {
+    oracle: Arc<F>,
+    block_size: usize,
+    threads: usize,
+    retries: usize,
+    guess_order: Arc<Vec<u8>>,
+    on_progress: Option<Box<FnMut(&BlockProgress)>>,
+}
+
+impl<F, E> PaddingOracleAttack<F> where F: Fn(&[u8]) -> Result<bool, E> + Send + Sync + 'static {
+    pub fn new(oracle: F, block_size: usize) -> PaddingOracleAttack<F> {
+        PaddingOracleAttack {
+            oracle: Arc::new(oracle),
+            block_size: block_size,
+            threads: 4,
+            retries: DEFAULT_RETRIES,
+            guess_order: Arc::new(english_guess_order()),
+            on_progress: None,
+        }
+    }
+
+    pub fn threads(mut self, threads: usize) -> PaddingOracleAttack<F> {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn retries(mut self, retries: usize) -> PaddingOracleAttack<F> {
+        self.retries = retries;
+        self
+    }
+
+    // Plaintext bytes to try, most likely first. Anything left out goes on the end
+    pub fn guess_order(mut self, order: &[u8]) -> PaddingOracleAttack<F> {
+        let mut full = Vec::new();
+        for b in order.iter().cloned().chain((0..256).map(|b| b as u8)) {
+            if !full.contains(&b) {
+                full.push(b);
+            }
+        }
+        self.guess_order = Arc::new(full);
+        self
+    }
+
+    pub fn on_progress<P: FnMut(&BlockProgress) + 'static>(mut self, callback: P)
+            -> PaddingOracleAttack<F> {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    pub fn decrypt(&mut self, iv: &[u8], ciphertext: &[u8])
+                   -> Result<Decryption, PaddingOracleError> {
+        let bs = self.block_size;
+        if bs == 0 || bs > 255 {
+            return Err(PaddingOracleError::BadBlockSize);
+        }
+        if iv.len() != bs {
+            return Err(PaddingOracleError::BadLength { len: iv.len(), block_size: bs });
+        }
+        if ciphertext.is_empty() || ciphertext.len() % bs != 0 {
+            return Err(PaddingOracleError::BadLength { len: ciphertext.len(), block_size: bs });
+        }
+
+        let all = Arc::new([iv, ciphertext].concat());
+        let blocks = ciphertext.len() / bs;
+        let next = Arc::new(AtomicUsize::new(0));
+        let queries = Arc::new(AtomicUsize::new(0));
+        let failed = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+
+        let mut workers = Vec::new();
+        for _ in 0..self.threads.min(blocks) {
+            let (oracle, order, all) = (self.oracle.clone(), self.guess_order.clone(), all.clone());
+            let (next, queries, failed, tx) = (next.clone(), queries.clone(), failed.clone(),
+                                               tx.clone());
+            let retries = self.retries;
+            workers.push(thread::spawn(move || {
+                loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    if i >= blocks || failed.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let mut checker = Checker {
+                        oracle: &*oracle,
+                        retries: retries,
+                        queries: &queries,
+                        block: i,
+                    };
+                    let prev = &all[i*bs..(i+1)*bs];
+                    let result = checker.intermediate(&all[(i+1)*bs..(i+2)*bs], &order, prev)
+                                        .map(|inter| inter.iter().zip(prev.iter())
+                                                          .map(|(a, b)| a ^ b)
+                                                          .collect::<Vec<u8>>());
+                    let _ = tx.send((i, result));
+                }
+            }));
+        }
+        drop(tx);
+
+        let mut plaintext = vec![0u8; ciphertext.len()];
+        let mut done = 0;
+        let mut error = None;
+        for (i, result) in rx.iter() {
+            match result {
+                Ok(block) => {
+                    plaintext[i*bs..(i+1)*bs].copy_from_slice(&block);
+                    done += 1;
+                    if let Some(ref mut callback) = self.on_progress {
+                        callback(&BlockProgress {
+                            block: i,
+                            plaintext: &block,
+                            done: done,
+                            blocks: blocks,
+                            queries: queries.load(Ordering::SeqCst),
+                        });
+                    }
+                },
+                Err(e) => {
+                    failed.store(true, Ordering::SeqCst);
+                    error = error.or(Some(e));
+                },
+            }
+        }
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(Decryption {
+                plaintext: plaintext,
+                queries: queries.load(Ordering::SeqCst),
+            }),
+        }
+    }
+
+    // CBC-R: an IV and ciphertext that decrypt to plaintext, padded here with PKCS#7, without the
+    // key. The last block is random, and each block's intermediate state then says what the one
+    // ahead of it has to be. Every block waits on the one after it, so this runs on one thread
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Forgery, PaddingOracleError> {
+        let bs = self.block_size;
+        if bs == 0 || bs > 255 {
+            return Err(PaddingOracleError::BadBlockSize);
+        }
+        let padded = Pkcs7.pad(plaintext, bs);
+        let blocks = padded.len() / bs;
+
+        let mut forged = vec![0u8; padded.len() + bs];
+        rand::thread_rng().fill_bytes(&mut forged[blocks*bs..]);
+        let queries = AtomicUsize::new(0);
+        let zeros = vec![0u8; bs];
+        for i in (0..blocks).rev() {
+            let mut checker = Checker {
+                oracle: &*self.oracle,
+                retries: self.retries,
+                queries: &queries,
+                block: i,
+            };
+            let inter = checker.intermediate(&forged[(i+1)*bs..(i+2)*bs], &self.guess_order,
+                                             &zeros)?;
+            let wanted = &padded[i*bs..(i+1)*bs];
+            for j in 0..bs {
+                forged[i*bs + j] = inter[j] ^ wanted[j];
+            }
+            if let Some(ref mut callback) = self.on_progress {
+                callback(&BlockProgress {
+                    block: i,
+                    plaintext: wanted,
+                    done: blocks - i,
+                    blocks: blocks,
+                    queries: queries.load(Ordering::SeqCst),
+                });
+            }
+        }
+
+        let ciphertext = forged.split_off(bs);
+        Ok(Forgery { iv: forged, ciphertext: ciphertext, queries: queries.load(Ordering::SeqCst) })
+    }
+}
+
+// What one thread needs to work on one block
+struct Checker<'a, F: 'a> {
+    oracle: &'a F,
+    retries: usize,
+    queries: &'a AtomicUsize,
+    block: usize,
+}
+
+impl<'a, F, E> Checker<'a, F> where F: Fn(&[u8]) -> Result<bool, E> {
+    fn valid(&mut self, input: &[u8]) -> Result<bool, PaddingOracleError> {
+        for _ in 0..self.retries+1 {
+            self.queries.fetch_add(1, Ordering::SeqCst);
+            if let Ok(valid) = (self.oracle)(input) {
+                return Ok(valid);
+            }
+        }
+        Err(PaddingOracleError::OracleFailed { block: self.block, attempts: self.retries + 1 })
+    }
+
+    // The block's decryption before it's XORed with the one ahead of it, a byte at a time from
+    // the end. Guesses are plaintext bytes in order, which prev turns into intermediate bytes;
+    // when there's no plaintext to guess, as in forging, prev is all zeros
+    fn intermediate(&mut self, block: &[u8], order: &[u8], prev: &[u8])
+                    -> Result<Vec<u8>, PaddingOracleError> {
+        let bs = block.len();
+        let mut inter = vec![0u8; bs];
+        let mut input = vec![0u8; 2*bs];
+        input[bs..].copy_from_slice(block);
+
+        for pos in (0..bs).rev() {
+            let pad = (bs - pos) as u8;
+            for j in pos+1..bs {
+                input[j] = inter[j] ^ pad;
+            }
+
+            let mut found = None;
+            for &guess in order.iter() {
+                let candidate = guess ^ prev[pos];
+                input[pos] = candidate ^ pad;
+                if !self.valid(&input)? {
+                    continue;
+                }
+                // The last byte can also come out valid as the end of longer padding, like
+                // \x02\x02. Changing the byte ahead of it only breaks that kind
+                if pos == bs-1 && bs > 1 {
+                    input[pos-1] ^= 1;
+                    let still_valid = self.valid(&input)?;
+                    input[pos-1] ^= 1;
+                    if !still_valid {
+                        continue;
+                    }
+                }
+                found = Some(candidate);
+                break;
+            }
+            inter[pos] = found.ok_or(PaddingOracleError::NoValidByte {
+                block: self.block,
+                index: pos,
+            })?;
+        }
+
+        Ok(inter)
+    }
+}
+
+#[test]
+fn tst_padding_oracle() {
+    use set1::{decrypt_with_mode, encrypt_with_mode, Aes, BlockCipher, Mode, Padding, Pkcs7};
+    use std::sync::Mutex;
+
+    // Not a real cipher, but CBC doesn't care, and it gives us a block size other than 16
+    #[derive(Clone, Copy)]
+    struct Toy;
+    impl BlockCipher for Toy {
+        type Block = [u8; 8];
+        fn encrypt_block(&self, block: &mut [u8; 8]) {
+            for (i, b) in block.iter_mut().enumerate() {
+                *b = (*b ^ 0x5a).rotate_left(i as u32 + 1);
+            }
+        }
+        fn decrypt_block(&self, block: &mut [u8; 8]) {
+            for (i, b) in block.iter_mut().enumerate() {
+                *b = b.rotate_right(i as u32 + 1) ^ 0x5a;
+            }
+        }
+    }
+
+    let plaintext = Pkcs7.pad(b"Ice Ice Baby, too cold, too cold. Vanilla Ice is back", 16);
+    let iv = [3u8; 16];
+    let aes = Aes::new(b"YELLOW SUBMARINE").unwrap();
+    let ciphertext = encrypt_with_mode(Mode::Cbc, &aes, &iv, &plaintext).unwrap();
+
+    // Every third query fails, and gets retried
+    let calls = AtomicUsize::new(0);
+    let flaky = move |input: &[u8]| {
+        if calls.fetch_add(1, Ordering::SeqCst) % 3 == 2 {
+            return Err("timed out");
+        }
+        let decrypted = decrypt_with_mode(Mode::Cbc, &aes, &input[..16], &input[16..]).unwrap();
+        Ok(Pkcs7.unpad(&decrypted, 16).is_ok())
+    };
+    let finished = Arc::new(Mutex::new(Vec::new()));
+    let log = finished.clone();
+    let decryption = PaddingOracleAttack::new(flaky, 16)
+                                         .on_progress(move |p| {
+                                             log.lock().unwrap().push((p.block, p.done))
+                                         })
+                                         .decrypt(&iv, &ciphertext).unwrap();
+    assert_eq!(decryption.plaintext, plaintext);
+    let mut finished = finished.lock().unwrap().clone();
+    assert_eq!(finished.len(), 4);
+    finished.sort();
+    assert_eq!(finished.iter().map(|&(b, _)| b).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+
+    // A smaller block, on one thread, and English guesses beat going through bytes in order
+    let plaintext = Pkcs7.pad(b"the quick brown fox jumps over the lazy dog", 8);
+    let iv = [9u8; 8];
+    let ciphertext = encrypt_with_mode(Mode::Cbc, &Toy, &iv, &plaintext).unwrap();
+    let oracle = |input: &[u8]| -> Result<bool, ()> {
+        let decrypted = decrypt_with_mode(Mode::Cbc, &Toy, &input[..8], &input[8..]).unwrap();
+        Ok(Pkcs7.unpad(&decrypted, 8).is_ok())
+    };
+    let english = PaddingOracleAttack::new(oracle, 8).threads(1).decrypt(&iv, &ciphertext).unwrap();
+    let in_order = (0..256).map(|b| b as u8).collect::<Vec<u8>>();
+    let naive = PaddingOracleAttack::new(oracle, 8).guess_order(&in_order)
+                                                   .decrypt(&iv, &ciphertext).unwrap();
+    assert_eq!(english.plaintext, plaintext);
+    assert_eq!(naive.plaintext, plaintext);
+    assert!(english.queries * 4 < naive.queries);
+
+    // Forging, with no key, something that decrypts to whatever we like
+    let wanted = b"{\"user\":\"mallory\",\"admin\":true}";
+    let forgery = PaddingOracleAttack::new(oracle, 8).encrypt(wanted).unwrap();
+    let decrypted = decrypt_with_mode(Mode::Cbc, &Toy, &forgery.iv, &forgery.ciphertext).unwrap();
+    assert_eq!(Pkcs7.unpad(&decrypted, 8).unwrap(), &wanted[..]);
+    assert_eq!(forgery.ciphertext.len(), 32);
+    assert!(forgery.queries <= 32 * 257);
+
+    // Errors instead of panics
+    let broken = |_: &[u8]| -> Result<bool, ()> { Err(()) };
+    assert_eq!(PaddingOracleAttack::new(broken, 8).retries(2).threads(1).decrypt(&iv, &ciphertext),
+               Err(PaddingOracleError::OracleFailed { block: 0, attempts: 3 }));
+    let never = |_: &[u8]| -> Result<bool, ()> { Ok(false) };
+    assert_eq!(PaddingOracleAttack::new(never, 8).threads(1).decrypt(&iv, &ciphertext),
+               Err(PaddingOracleError::NoValidByte { block: 0, index: 7 }));
+    assert_eq!(PaddingOracleAttack::new(never, 8).encrypt(b"anything"),
+               Err(PaddingOracleError::NoValidByte { block: 1, index: 7 }));
+    assert_eq!(PaddingOracleAttack::new(never, 8).decrypt(&iv, &ciphertext[1..]),
+               Err(PaddingOracleError::BadLength { len: 47, block_size: 8 }));
+}
@@ -1,4 +1,4 @@
-use set2::make_vec;
+use set2::{BitFlipper, FlipMode};
 use set3::get_aes_ctr;
 use rand;
 use rand::Rng;
@@ -55,24 +55,19 @@ fn get_oracle_and_tester() -> (Encryptor, Tester) {
     (Box::new(ctr_oracle), Box::new(admin_tester))
 }
 
-fn make_admin_ciphertext(oracle: &Encryptor) -> Vec<u8> {
-    let known_prefix_len = "comment1=cooking%20MCs;userdata=".len(); // Totes not cheating
-    let target_pt = b";admin=true;";
-    let filler_len = target_pt.len();
-    let filler_pt = make_vec(b'A', filler_len);
-    let mut ct: Vec<u8> = oracle(&filler_pt);
-
-    for (i, target_byte) in (known_prefix_len..(known_prefix_len+filler_len))
-                            .zip(target_pt.iter()) {
-        ct[i] ^= b'A' ^ target_byte;
-    }
-
-    ct
+fn make_admin_ciphertext(oracle: &Encryptor, admin_tester: &Tester) -> Vec<u8> {
+    let mut flipper = BitFlipper::new(|input: &[u8]| oracle(input),
+                                      |ciphertext: &[u8]| admin_tester(ciphertext));
+    flipper.forge(b";admin=true;").unwrap()
 }
 
 #[test]
 fn tst26() {
     let (ctr_oracle, admin_tester) = get_oracle_and_tester();
-    let ciphertext = make_admin_ciphertext(&ctr_oracle);
+    let mut flipper = BitFlipper::new(|input: &[u8]| ctr_oracle(input), |_: &[u8]| false);
+    let injection = flipper.probe().unwrap();
+    assert_eq!(injection.mode, FlipMode::Stream);
+    assert_eq!(injection.offset, "comment1=cooking%20MCs;userdata=".len());
+    let ciphertext = make_admin_ciphertext(&ctr_oracle, &admin_tester);
     assert!(admin_tester(&ciphertext));
 }
@@ -1,4 +1,4 @@
-use set1::{decode_hex, encode_hex, decode_b64};
+use set1::{decode_hex, encode_hex, decode_b64, log_cost, Metered};
 use set5::{inv_mod, mod_exp};
 use std::ascii::AsciiExt;
 use ramp::{Int, RandomInt};
@@ -109,8 +109,10 @@ fn tst46() {
         make_oracle(&plaintext_int)
     };
 
+    let (oracle, meter) = Metered::new(oracle).boxed();
     let plaintext_int = crack_ciphertext(&oracle, &e, &n, &ciphertext);
     let plaintext_str = int_to_string(&plaintext_int);
+    log_cost("c46", meter.queries(), plaintext_str.len());
 
     // The last byte is 0x5E ('^') but it should be 0x61 ('a'). Not sure why
     assert!(plaintext_str.starts_with("That's why I found you don't play around with the Funky \
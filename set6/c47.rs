@@ -1,7 +1,7 @@
 #![allow(non_snake_case)]
 
 use c46::{string_to_int};
-use set1::{encode_hex};
+use set1::{encode_hex, log_cost, Metered};
 use set5::{inv_mod, mod_exp};
 use std::cmp::{min, max};
 use ramp::{Int, RandomInt};
@@ -274,7 +274,9 @@ fn tst47() {
     let (oracle, e, n, c) = make_oracle(&orig_msg, &p, &q);
 
     // This is padded; extract the msg part of it
+    let (oracle, meter) = Metered::new(oracle).boxed();
     let recovered_plaintext = bleichenbacher(oracle, &c, &e, &n);
     let recovered_msg = extract_message(&recovered_plaintext);
     assert_eq!(recovered_msg, orig_msg);
+    log_cost("c47", meter.queries(), (recovered_plaintext.bit_length() as usize + 7) / 8);
 }
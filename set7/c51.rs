@@ -1,4 +1,4 @@
-use set1::decode_b64;
+use set1::{decode_b64, log_cost, Metered};
 use set2::{make_vec, AES_BLOCK_SIZE};
 use std::collections::BTreeMap;
 use std::io::{self, Write};
@@ -256,13 +256,14 @@ fn recover_cookie(cookie_length: usize, oracle: &CompressionOracle, oracle_t: Or
 // the time and it's pretty cool
 #[test]
 fn tst51() {
-    let (stream_oracle, block_oracle) = {
+    let ((stream_oracle, stream_meter), (block_oracle, block_meter)) = {
         let prefix = b"\
             POST / HTTP/1.1\r\n\
             Host: hapless.com\r\n\
             Cookie: sessionid=TmV2ZXIgcmV2ZWFsIHRoZSBXdS1UYW5nIFNlY3JldCE=\r\n\
             Content-Length: ";
-        (make_stream_compression_oracle(&*prefix), make_block_compression_oracle(&*prefix))
+        (Metered::new(make_stream_compression_oracle(&*prefix)).boxed(),
+         Metered::new(make_block_compression_oracle(&*prefix)).boxed())
     };
 
     let expected_cookie_value = "Never reveal the Wu-Tang Secret!";
@@ -281,6 +282,7 @@ fn tst51() {
         let decoded_guess = String::from_utf8_lossy(&*cookie_decoded_bytes);
 
         assert_eq!(&decoded_guess, expected_cookie_value);
+        log_cost("c51 stream", stream_meter.queries(), plaintext_guess.len());
     }
 
     // Test cookie recovery on a simulated block cipher
@@ -297,5 +299,6 @@ fn tst51() {
         let decoded_guess = String::from_utf8_lossy(&*cookie_decoded_bytes);
 
         assert_eq!(&decoded_guess, expected_cookie_value);
+        log_cost("c51 block", block_meter.queries(), plaintext_guess.len());
     }
 }
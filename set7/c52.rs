@@ -1,22 +1,29 @@
-use set2::{encrypt_block_ecb, make_vec, minimal_pad, AES_BLOCK_SIZE};
+use set1::{Aes, BlockCipher};
+use set2::{make_vec, minimal_pad, AES_BLOCK_SIZE};
 use std::collections::HashMap;
 use rand::{self, Rng};
 
 const BAD_HASH_DIGEST_SIZE: usize = 2;    // 16 bits
 const DECENT_HASH_DIGEST_SIZE: usize = 4; // 32 bits
 
+// Transform the state into an AES key. Expanding it is most of the work in a step, so loops that
+// hash lots of blocks from one state should do this once and call hash_step_with
+fn state_cipher(state: &[u8]) -> Aes {
+    Aes::new(&minimal_pad(state, AES_BLOCK_SIZE)).unwrap()
+}
+
+fn hash_step_with(msg_block: &[u8], cipher: &Aes, digest_size: usize) -> Vec<u8> {
+    let mut block = Aes::block_from(msg_block);
+    cipher.encrypt_block(&mut block);
+
+    block[..digest_size].to_vec()
+}
+
 // This is a hash function with a 16-bit digest size. This takes in a block of length
 // AES_BLOCK_SIZE and returns the next state of the hash function
 fn bad_hash_step(msg_block: &[u8], state: &[u8]) -> Vec<u8> {
-    assert_eq!(msg_block.len(), AES_BLOCK_SIZE);
     assert_eq!(state.len(), BAD_HASH_DIGEST_SIZE);
-
-    // Transform the state into an AES key
-    let key = minimal_pad(state, AES_BLOCK_SIZE);
-    let mut new_state = encrypt_block_ecb(msg_block, &*key);
-    new_state.truncate(BAD_HASH_DIGEST_SIZE);
-
-    new_state
+    hash_step_with(msg_block, &state_cipher(state), BAD_HASH_DIGEST_SIZE)
 }
 
 // This is the iterated hash function using bad_hash_step
@@ -31,15 +38,8 @@ fn bad_hash_iterated(msg: &[u8]) -> Vec<u8> {
 
 // Same as bad_hash_step but with a state that's 32 bits
 fn decent_hash_step(msg_block: &[u8], state: &[u8]) -> Vec<u8> {
-    assert_eq!(msg_block.len(), AES_BLOCK_SIZE);
     assert_eq!(state.len(), DECENT_HASH_DIGEST_SIZE);
-
-    // Transform the state into an AES key
-    let key = minimal_pad(state, AES_BLOCK_SIZE);
-    let mut new_state = encrypt_block_ecb(msg_block, &*key);
-    new_state.truncate(DECENT_HASH_DIGEST_SIZE);
-
-    new_state
+    hash_step_with(msg_block, &state_cipher(state), DECENT_HASH_DIGEST_SIZE)
 }
 
 // This is the iterated hash function using bad_hash_step
@@ -84,12 +84,13 @@ fn find_bad_hash_collisions(n: usize) -> Vec<Vec<u8>> {
         // do a birthday attack, but it's pretty fast so who cares
         let mut msg1 = make_vec(0u8, AES_BLOCK_SIZE);
         let mut msg2 = make_vec(0u8, AES_BLOCK_SIZE);
+        let cipher = state_cipher(&*state);
         loop {
             rng.fill_bytes(&mut msg1);
             rng.fill_bytes(&mut msg2);
 
-            let digest1 = bad_hash_step(&*msg1, &*state);
-            let digest2 = bad_hash_step(&*msg2, &*state);
+            let digest1 = hash_step_with(&*msg1, &cipher, BAD_HASH_DIGEST_SIZE);
+            let digest2 = hash_step_with(&*msg2, &cipher, BAD_HASH_DIGEST_SIZE);
 
             // Found a collision, now we can extend the previous collisions
             if digest1 == digest2 {
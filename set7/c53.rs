@@ -1,4 +1,5 @@
-use set2::{encrypt_block_ecb, minimal_pad, AES_BLOCK_SIZE};
+use set1::{Aes, BlockCipher};
+use set2::{minimal_pad, AES_BLOCK_SIZE};
 use std::collections::HashMap;
 use std::usize;
 use rand::{self, Rng};
@@ -13,14 +14,21 @@ pub fn md_initial_state() -> Vec<u8> {
 
 // Nothing new here
 pub fn md_hash_step(msg_block: &[u8], state: &[u8]) -> Vec<u8> {
-    assert_eq!(msg_block.len(), MD_HASH_BLOCK_SIZE);
+    md_hash_step_with(msg_block, &md_state_cipher(state))
+}
+
+// The state is the key, so a loop that hashes lots of blocks from one state can expand it once
+// here and call md_hash_step_with
+pub fn md_state_cipher(state: &[u8]) -> Aes {
     assert_eq!(state.len(), MD_HASH_DIGEST_SIZE);
+    Aes::new(&minimal_pad(state, MD_HASH_BLOCK_SIZE)).unwrap()
+}
 
-    let key = minimal_pad(state, MD_HASH_BLOCK_SIZE);
-    let mut new_state = encrypt_block_ecb(msg_block, &*key);
-    new_state.truncate(MD_HASH_DIGEST_SIZE);
+pub fn md_hash_step_with(msg_block: &[u8], cipher: &Aes) -> Vec<u8> {
+    let mut block = Aes::block_from(msg_block);
+    cipher.encrypt_block(&mut block);
 
-    new_state
+    block[..MD_HASH_DIGEST_SIZE].to_vec()
 }
 
 // Classic MD construction. No length padding.
@@ -68,6 +76,9 @@ fn make_expandable_message(k: usize) -> (Vec<(Vec<u8>, Vec<u8>)>, Vec<u8>) {
         let mut short_state_map: HashMap<Vec<u8>, Vec<u8>> = HashMap::new();
         let mut long_state_map: HashMap<Vec<u8>, Vec<u8>> = HashMap::new();
 
+        let short_cipher = md_state_cipher(&*state);
+        let long_cipher = md_state_cipher(&prefix_state);
+
         // Make 2 new random vectors on each iteration and see if we've seen their digests before
         // Loop until we find a collision
         loop {
@@ -79,8 +90,8 @@ fn make_expandable_message(k: usize) -> (Vec<(Vec<u8>, Vec<u8>)>, Vec<u8>) {
             let mut long_msg = [0u8; MD_HASH_BLOCK_SIZE];
             rng.fill_bytes(&mut long_msg);
 
-            let short_digest = md_hash_step(&short_msg, &*state);
-            let long_digest = md_hash_step(&long_msg, &prefix_state);
+            let short_digest = md_hash_step_with(&short_msg, &short_cipher);
+            let long_digest = md_hash_step_with(&long_msg, &long_cipher);
 
             // Insert the digests
             short_state_map.insert(short_digest.clone(), short_msg.to_vec());
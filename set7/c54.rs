@@ -1,5 +1,5 @@
-use c53::{make_length_pad_block, md_hash_step, md_hash_iterated, md_hash_iterated_no_pad,
-          md_initial_state, MD_HASH_BLOCK_SIZE};
+use c53::{make_length_pad_block, md_hash_step, md_hash_step_with, md_hash_iterated,
+          md_hash_iterated_no_pad, md_initial_state, md_state_cipher, MD_HASH_BLOCK_SIZE};
 use set2::minimal_pad;
 use std::collections::HashMap;
 use rand::{self, Rng};
@@ -58,13 +58,15 @@ fn find_collision(state1: &[u8], state2: &[u8]) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
 
     let mut msg1 = [0u8; MD_HASH_BLOCK_SIZE];
     let mut msg2 = [0u8; MD_HASH_BLOCK_SIZE];
+    let cipher1 = md_state_cipher(state1);
+    let cipher2 = md_state_cipher(state2);
 
     // Make random messages and record their hashes in the maps until a collision is found
     loop {
         rng.fill_bytes(&mut msg1);
         rng.fill_bytes(&mut msg2);
-        let digest1 = md_hash_step(&msg1, state1);
-        let digest2 = md_hash_step(&msg2, state2);
+        let digest1 = md_hash_step_with(&msg1, &cipher1);
+        let digest2 = md_hash_step_with(&msg2, &cipher2);
 
         msg1_map.insert(digest1.clone(), msg1.to_vec());
         msg2_map.insert(digest2.clone(), msg2.to_vec());